@@ -0,0 +1,734 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use super::{DiffTarget, GitBackend, InProgress, PullStrategy, ResetMode, StatusEntry};
+use crate::error::{Result, RfxError};
+
+/// Backend that shells out to the `git` executable
+pub struct CliBackend {
+    /// Repository to operate on (`git -C <dir>`); `None` means the process cwd
+    workdir: Option<PathBuf>,
+}
+
+impl CliBackend {
+    /// Open the repository at `path` (or the current directory), making sure
+    /// it is a git work tree before anything else runs
+    pub fn open(path: Option<&Path>) -> Result<Self> {
+        if let Some(p) = path
+            && !p.is_dir()
+        {
+            return Err(RfxError::Validation(format!("'{}' is not a directory.", p.display())));
+        }
+
+        let backend = CliBackend { workdir: path.map(Path::to_path_buf) };
+        let inside = backend.run_git_command(&["rev-parse", "--is-inside-work-tree"])?;
+        if inside != "true" {
+            return Err(RfxError::NotARepo);
+        }
+
+        Ok(backend)
+    }
+
+    /// Spawn git and turn a non-zero exit into a classified `RfxError`
+    fn exec_git(&self, args: &[&str]) -> Result<Output> {
+        Self::checked(self.exec_git_unchecked(args)?)
+    }
+
+    fn checked(output: Output) -> Result<Output> {
+        if output.status.success() {
+            Ok(output)
+        } else {
+            // Conflicts are reported on stdout, everything else on stderr
+            let combined = format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stderr),
+                String::from_utf8_lossy(&output.stdout)
+            );
+            Err(RfxError::from_git_failure(output.status.code(), &combined))
+        }
+    }
+
+    /// Run git and return its output whatever the exit status, for commands
+    /// whose non-zero exit codes carry meaning (e.g. `merge-tree`)
+    fn exec_git_unchecked(&self, args: &[&str]) -> Result<Output> {
+        let mut cmd = Command::new("git");
+        if let Some(dir) = &self.workdir {
+            cmd.arg("-C").arg(dir);
+        }
+
+        cmd.args(args)
+            .output()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => RfxError::GitNotFound,
+                _ => RfxError::Io(format!("Failed to execute git: {}", e)),
+            })
+    }
+
+    /// Run git with `input` on stdin (e.g. a patch for `git apply`)
+    fn exec_git_with_input(&self, args: &[&str], input: &str) -> Result<Output> {
+        let mut cmd = Command::new("git");
+        if let Some(dir) = &self.workdir {
+            cmd.arg("-C").arg(dir);
+        }
+
+        let spawn_error = |e: std::io::Error| match e.kind() {
+            std::io::ErrorKind::NotFound => RfxError::GitNotFound,
+            _ => RfxError::Io(format!("Failed to execute git: {}", e)),
+        };
+
+        let mut child = cmd
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(spawn_error)?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input.as_bytes()).map_err(spawn_error)?;
+        }
+
+        Self::checked(child.wait_with_output().map_err(spawn_error)?)
+    }
+
+    /// Run a git command and return the trimmed output
+    /// Use this for almost everything (getting branch names, hashes, etc.)
+    fn run_git_command(&self, args: &[&str]) -> Result<String> {
+        let output = self.exec_git(args)?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Run a git command and return the RAW output (preserving whitespace)
+    /// Use this ONLY when column alignment matters (like `git status`)
+    fn run_git_command_raw(&self, args: &[&str]) -> Result<String> {
+        let output = self.exec_git(args)?;
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+impl GitBackend for CliBackend {
+    /// Get the status of the current Git repository (porcelain v2, NUL-separated)
+    fn status(&self) -> Result<Vec<StatusEntry>> {
+        // USE RAW COMMAND HERE: paths may have leading/trailing spaces
+        let output = self.run_git_command_raw(&["status", "--porcelain=v2", "-z"])?;
+        Ok(parse_porcelain_v2(&output))
+    }
+
+    fn unstage(&self, files: &[String]) -> Result<String> {
+        let mut args = vec!["reset", "-q", "--"];
+        args.extend(files.iter().map(|s| s.as_str()));
+        self.run_git_command(&args)
+    }
+
+    /// Check if local branch is ahead/behind remote
+    fn ahead_behind(&self, branch: &str) -> Result<(usize, usize)> {
+        // "git rev-list --left-right --count HEAD...@{u}"
+        let arg = format!("{}...@{{u}}", branch);
+        let output = self.run_git_command(&["rev-list", "--left-right", "--count", &arg])?;
+
+        let parts: Vec<&str> = output.split_whitespace().collect();
+        if parts.len() >= 2 {
+            let ahead = parts[0].parse().unwrap_or(0);
+            let behind = parts[1].parse().unwrap_or(0);
+            Ok((ahead, behind))
+        } else {
+            Ok((0, 0))
+        }
+    }
+
+    /// Commits only in `local` and only in `other` (`rev-list --left-right`)
+    fn divergence(&self, local: &str, other: &str) -> Result<(usize, usize)> {
+        let arg = format!("{}...{}", local, other);
+        let output = self.run_git_command(&["rev-list", "--left-right", "--count", &arg])?;
+
+        let mut counts = output.split_whitespace().map(|n| n.parse().unwrap_or(0));
+        Ok((counts.next().unwrap_or(0), counts.next().unwrap_or(0)))
+    }
+
+    /// Push a branch to `remote`, establishing a tracking link with `set_upstream`
+    fn push(&self, remote: &str, branch: &str, set_upstream: bool) -> Result<String> {
+        let mut args = vec!["push"];
+        if set_upstream {
+            args.push("-u");
+        }
+        args.extend([remote, branch]);
+        self.run_git_command(&args)
+    }
+
+    /// Create and switch to a new branch
+    fn create_branch(&self, name: &str, start: Option<&str>, track: bool) -> Result<String> {
+        let mut args = vec!["checkout", "-b", name, if track { "--track" } else { "--no-track" }];
+        args.extend(start);
+        self.run_git_command(&args)
+    }
+
+    fn list_tags(&self) -> Result<Vec<String>> {
+        let output = self.run_git_command(&[
+            "for-each-ref",
+            "--sort=-creatordate",
+            "--format=%(refname:short)",
+            "refs/tags/",
+        ])?;
+        Ok(output.lines().map(|s| s.to_string()).collect())
+    }
+
+    /// Fetch latest changes/branches from remote (without merging)
+    fn fetch(&self) -> Result<String> {
+        self.run_git_command(&["fetch", "--all"])
+    }
+
+    fn fetch_prune(&self) -> Result<String> {
+        self.run_git_command(&["fetch", "--all", "--prune"])
+    }
+
+    /// List remote branches with details
+    fn list_remote_branches(&self) -> Result<Vec<String>> {
+        let output = self.run_git_command(&[
+            "for-each-ref",
+            "--format=%(refname:short)|%(authorname)|%(authordate:relative)",
+            "refs/remotes/",
+        ])?;
+
+        // Symbolic refs such as origin/HEAD only point at another branch
+        Ok(output
+            .lines()
+            .filter(|l| !l.split('|').next().unwrap_or_default().ends_with("/HEAD"))
+            .map(|s| s.to_string())
+            .collect())
+    }
+
+    /// Pull a specific branch from a remote
+    fn pull_branch(&self, remote: &str, branch: &str, strategy: PullStrategy) -> Result<String> {
+        // Always explicit, so the user's pull.rebase/pull.ff settings cannot surprise us
+        let flag = match strategy {
+            PullStrategy::FastForwardOnly => "--ff-only",
+            PullStrategy::Merge => "--no-rebase",
+            PullStrategy::Rebase => "--rebase",
+        };
+        // diff3 markers include the common ancestor, which `rfx resolve` shows
+        self.run_git_command(&["-c", "merge.conflictStyle=diff3", "pull", flag, remote, branch])
+    }
+
+    fn remote_head(&self, remote: &str) -> Result<Option<String>> {
+        let head = format!("refs/remotes/{}/HEAD", remote);
+        match self.run_git_command(&["symbolic-ref", "-q", "--short", &head]) {
+            Ok(target) => Ok(Some(target)),
+            // Exit code 1: the ref is missing or not symbolic
+            Err(RfxError::Git { code: Some(1), .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn config_get(&self, key: &str) -> Result<Option<String>> {
+        match self.run_git_command(&["config", "--get", key]) {
+            Ok(value) => Ok(Some(value)),
+            // Exit code 1: the key is not set
+            Err(RfxError::Git { code: Some(1), .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn config_set(&self, key: &str, value: &str) -> Result<String> {
+        self.run_git_command(&["config", key, value])
+    }
+
+    fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool> {
+        match self.run_git_command(&["merge-base", "--is-ancestor", ancestor, descendant]) {
+            Ok(_) => Ok(true),
+            Err(RfxError::Git { code: Some(1), .. }) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Move the current branch, e.g. to undo commits but keep changes
+    fn reset(&self, mode: ResetMode, target: &str) -> Result<String> {
+        let flag = match mode {
+            ResetMode::Soft => "--soft",
+            ResetMode::Keep => "--keep",
+        };
+        self.run_git_command(&["reset", "-q", flag, target])
+    }
+
+    // --- Standard Wrappers (Use trimmed output) ---
+
+    fn current_branch(&self) -> Result<String> {
+        self.run_git_command(&["rev-parse", "--abbrev-ref", "HEAD"])
+    }
+
+    fn add(&self, files: &[String]) -> Result<String> {
+        let mut args = vec!["add"];
+        args.extend(files.iter().map(|s| s.as_str()));
+        self.run_git_command(&args)
+    }
+
+    fn add_all(&self) -> Result<String> {
+        self.run_git_command(&["add", "-A"])
+    }
+
+    fn commit(&self, message: &str) -> Result<String> {
+        self.run_git_command(&["commit", "-m", message])
+    }
+
+    fn commit_amend(&self, message: &str, include_staged: bool) -> Result<String> {
+        let mut args = vec!["commit", "--amend", "-m", message];
+        if !include_staged {
+            // With --amend and no paths, --only leaves the index out of the commit
+            args.push("--only");
+        }
+        self.run_git_command(&args)
+    }
+
+    fn list_branches(&self) -> Result<Vec<String>> {
+        let output = self.run_git_command(&["for-each-ref", "--format=%(refname:short)", "refs/heads/"])?;
+        Ok(output.lines().map(|line| line.trim().to_string()).collect())
+    }
+
+    fn list_branch_refs(&self) -> Result<Vec<String>> {
+        let output = self.run_git_command(&[
+            "for-each-ref",
+            "--format=%(refname:short)|%(objectname)|%(upstream:short)|%(upstream:track)|%(committerdate:unix)",
+            "refs/heads/",
+        ])?;
+        Ok(output.lines().map(|s| s.to_string()).collect())
+    }
+
+    fn merged_branches(&self, target: &str) -> Result<Vec<String>> {
+        let output = self.run_git_command(&[
+            "for-each-ref",
+            "--format=%(refname:short)",
+            "--merged",
+            target,
+            "refs/heads/",
+        ])?;
+        Ok(output.lines().map(|s| s.to_string()).collect())
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<String> {
+        self.run_git_command(&["branch", "-D", name])
+    }
+
+    fn first_commit(&self, branch: &str) -> Result<String> {
+        let output = self.run_git_command(&["log", "--reverse", "--format=%an|%ad", "--date=short", branch])?;
+        Ok(output.lines().next().unwrap_or("Unknown|Unknown").to_string())
+    }
+
+    fn last_commit(&self, branch: &str) -> Result<String> {
+        let output = self.run_git_command(&["log", "-1", "--format=%ad|%s", "--date=short", branch])?;
+        Ok(output.lines().next().unwrap_or("Unknown|No commit").to_string())
+    }
+
+    fn commit_message(&self, rev: &str) -> Result<String> {
+        self.run_git_command(&["log", "-1", "--format=%B", rev])
+    }
+
+    fn list_remotes(&self) -> Result<Vec<String>> {
+        let output = self.run_git_command(&["remote", "-v"])?;
+        Ok(output.lines().map(|line| line.trim().to_string()).collect())
+    }
+
+    fn list_commits(&self, branch: &str, count: usize) -> Result<Vec<String>> {
+        let count_arg = format!("-{}", count);
+        let output = self.run_git_command(&["log", &count_arg, "--pretty=format:%h|%an|%ad|%s", "--date=short", branch])?;
+        Ok(output.lines().map(|line| line.trim().to_string()).collect())
+    }
+
+    fn list_commits_between(&self, from: &str, to: &str, count: usize) -> Result<Vec<String>> {
+        let count_arg = format!("-{}", count);
+        let range = format!("{}..{}", from, to);
+        let output = self.run_git_command(&["log", &count_arg, "--pretty=format:%h|%an|%ad|%s", "--date=short", &range])?;
+        Ok(output.lines().map(|line| line.trim().to_string()).collect())
+    }
+
+    fn diff_files(&self, from: &str, to: &str) -> Result<String> {
+        let range = format!("{}...{}", from, to);
+        self.run_git_command_raw(&["diff", "--name-status", "-z", &range])
+    }
+
+    fn merge_tree(&self, ours: &str, theirs: &str) -> Result<Vec<String>> {
+        let output = self.exec_git_unchecked(&["merge-tree", "--write-tree", "--name-only", "--no-messages", ours, theirs])?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        match output.status.code() {
+            // Clean merge: only the resulting tree id is printed
+            Some(0) => Ok(Vec::new()),
+            // Conflicts: tree id, then one conflicted path per line
+            Some(1) => Ok(stdout.lines().skip(1).filter(|l| !l.is_empty()).map(String::from).collect()),
+            code => Err(RfxError::from_git_failure(code, &String::from_utf8_lossy(&output.stderr))),
+        }
+    }
+
+    fn diff(&self, target: &DiffTarget, paths: &[String]) -> Result<String> {
+        let mut args = vec!["diff", "--no-color", "--no-ext-diff", "-M"];
+        match target {
+            DiffTarget::Worktree => {}
+            DiffTarget::Staged => args.push("--cached"),
+            DiffTarget::Against(rev) => args.push(rev),
+            DiffTarget::Between(from, to) => args.extend([from.as_str(), to.as_str()]),
+        }
+        args.push("--");
+        args.extend(paths.iter().map(|s| s.as_str()));
+        self.run_git_command_raw(&args)
+    }
+
+    fn apply_cached(&self, patch: &str) -> Result<String> {
+        let output = self.exec_git_with_input(&["apply", "--cached", "-"], patch)?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn work_tree(&self) -> Result<PathBuf> {
+        let dir = self.run_git_command(&["rev-parse", "--show-toplevel"])?;
+        Ok(PathBuf::from(dir))
+    }
+
+    fn remove(&self, files: &[String]) -> Result<String> {
+        let mut args = vec!["rm", "-q", "--"];
+        args.extend(files.iter().map(|s| s.as_str()));
+        self.run_git_command(&args)
+    }
+
+    fn restore_files(&self, files: &[String]) -> Result<String> {
+        let mut args = vec!["restore", "--source=HEAD", "--staged", "--worktree", "--"];
+        args.extend(files.iter().map(|s| s.as_str()));
+        self.run_git_command(&args)
+    }
+
+    fn abort_in_progress(&self, op: InProgress) -> Result<String> {
+        let args: &[&str] = match op {
+            InProgress::Merge => &["merge", "--abort"],
+            InProgress::Rebase => &["rebase", "--abort"],
+            InProgress::CherryPick => &["cherry-pick", "--abort"],
+            InProgress::Revert => &["revert", "--abort"],
+            InProgress::Bisect => &["bisect", "reset"],
+        };
+        self.run_git_command(args)
+    }
+
+    fn continue_in_progress(&self, op: InProgress) -> Result<String> {
+        // core.editor=true accepts the prepared messages instead of opening an editor
+        let args: &[&str] = match op {
+            InProgress::Merge => &["commit", "--no-edit"],
+            InProgress::Rebase => &["-c", "core.editor=true", "rebase", "--continue"],
+            InProgress::CherryPick => &["-c", "core.editor=true", "cherry-pick", "--continue"],
+            InProgress::Revert => &["-c", "core.editor=true", "revert", "--continue"],
+            InProgress::Bisect => {
+                return Err(RfxError::Validation(
+                    "A bisect has no next step to continue with.".to_string(),
+                ));
+            }
+        };
+        self.run_git_command(args)
+    }
+
+    fn mergetool(&self, path: &str) -> Result<()> {
+        let mut cmd = Command::new("git");
+        if let Some(dir) = &self.workdir {
+            cmd.arg("-C").arg(dir);
+        }
+
+        let status = cmd
+            .args(["mergetool", "--no-prompt", "--", path])
+            .status()
+            .map_err(|e| RfxError::Io(format!("Failed to execute git: {}", e)))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(RfxError::Git {
+                code: status.code(),
+                stderr: format!("git mergetool did not finish resolving '{}'.", path),
+            })
+        }
+    }
+
+    fn editor(&self) -> Result<String> {
+        self.run_git_command(&["var", "GIT_EDITOR"])
+    }
+
+    fn git_dir(&self) -> Result<PathBuf> {
+        let dir = self.run_git_command(&["rev-parse", "--absolute-git-dir"])?;
+        Ok(PathBuf::from(dir))
+    }
+
+    fn rev_parse(&self, rev: &str) -> Result<String> {
+        let spec = format!("{}^{{commit}}", rev);
+        self.run_git_command(&["rev-parse", "--verify", "--quiet", &spec])
+            .map_err(|_| RfxError::InvalidRef(format!("'{}' does not point to a commit", rev)))
+    }
+
+    fn update_ref(&self, refname: &str, sha: &str) -> Result<String> {
+        self.run_git_command(&["update-ref", "-m", "rfx", refname, sha])
+    }
+
+    fn delete_ref(&self, refname: &str) -> Result<String> {
+        self.run_git_command(&["update-ref", "-d", refname])
+    }
+
+    fn checkout(&self, branch: &str) -> Result<String> {
+        self.run_git_command(&["checkout", "-q", branch, "--"])
+    }
+
+    fn checkout_tracking(&self, name: &str, remote_branch: &str) -> Result<String> {
+        self.run_git_command(&["checkout", "-q", "-b", name, "--track", remote_branch])
+    }
+
+    fn push_with_lease(&self, remote: &str, branch: &str, new_sha: Option<&str>, expected: Option<&str>) -> Result<String> {
+        // An empty expected value means "the branch must not exist yet"
+        let lease = format!("--force-with-lease=refs/heads/{}:{}", branch, expected.unwrap_or(""));
+        let refspec = format!("{}:refs/heads/{}", new_sha.unwrap_or(""), branch);
+        self.run_git_command(&["push", "--porcelain", &lease, remote, &refspec])
+    }
+
+    fn parents(&self, rev: &str) -> Result<Vec<String>> {
+        let output = self.run_git_command(&["rev-list", "--parents", "-n", "1", rev])?;
+        Ok(output.split_whitespace().skip(1).map(|s| s.to_string()).collect())
+    }
+
+    fn remote_refs_containing(&self, sha: &str) -> Result<Vec<String>> {
+        let output = self.run_git_command(&["for-each-ref", "--format=%(refname:short)", "--contains", sha, "refs/remotes/"])?;
+        Ok(output
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty() && !l.ends_with("/HEAD"))
+            .collect())
+    }
+
+    fn revert(&self, sha: &str, mainline: Option<u32>) -> Result<String> {
+        let parent = mainline.map(|m| m.to_string());
+        let mut args = vec!["revert", "--no-edit"];
+        if let Some(p) = &parent {
+            args.extend(["-m", p.as_str()]);
+        }
+        args.push(sha);
+        self.run_git_command(&args)
+    }
+
+    fn stash_push(&self, message: &str, include_untracked: bool) -> Result<String> {
+        let mut args = vec!["stash", "push", "--quiet"];
+        if include_untracked {
+            args.push("--include-untracked");
+        }
+        if !message.is_empty() {
+            args.extend(["-m", message]);
+        }
+        self.run_git_command(&args)
+    }
+
+    fn stash_list(&self) -> Result<Vec<String>> {
+        let out = self.run_git_command(&["stash", "list", "--format=%gd|%cr|%gs"])?;
+        Ok(out.lines().map(String::from).collect())
+    }
+
+    fn stash_files(&self, stash: &str) -> Result<String> {
+        self.run_git_command_raw(&["stash", "show", "--include-untracked", "--name-status", "-z", stash])
+    }
+
+    fn stash_pop(&self, stash: &str, index: bool) -> Result<String> {
+        let mut args = vec!["stash", "pop"];
+        if index {
+            args.push("--index");
+        }
+        args.push(stash);
+        self.run_git_command(&args)
+    }
+}
+
+/// Parse `git status --porcelain=v2 -z` output.
+///
+/// Records are NUL-terminated and paths are never quoted. Rename/copy
+/// records ("2") are followed by an extra NUL-terminated field holding the
+/// original path.
+fn parse_porcelain_v2(output: &str) -> Vec<StatusEntry> {
+    let mut entries = Vec::new();
+    let mut records = output.split('\0');
+
+    while let Some(record) = records.next() {
+        let mut kind_and_rest = record.splitn(2, ' ');
+        let kind = kind_and_rest.next().unwrap_or("");
+        let rest = kind_and_rest.next().unwrap_or("");
+
+        match kind {
+            // 1 <XY> <sub> <mH> <mI> <mW> <hH> <hI> <path>
+            "1" => {
+                let fields: Vec<&str> = rest.splitn(8, ' ').collect();
+                if fields.len() == 8 {
+                    entries.push(StatusEntry {
+                        xy: fields[0].to_string(),
+                        path: fields[7].to_string(),
+                        orig_path: None,
+                        submodule: fields[1].starts_with('S'),
+                        unmerged: false,
+                    });
+                }
+            }
+            // 2 <XY> <sub> <mH> <mI> <mW> <hH> <hI> <X><score> <path>\0<origPath>
+            "2" => {
+                let fields: Vec<&str> = rest.splitn(9, ' ').collect();
+                let orig_path = records.next().map(|p| p.to_string());
+                if fields.len() == 9 {
+                    entries.push(StatusEntry {
+                        xy: fields[0].to_string(),
+                        path: fields[8].to_string(),
+                        orig_path,
+                        submodule: fields[1].starts_with('S'),
+                        unmerged: false,
+                    });
+                }
+            }
+            // u <XY> <sub> <m1> <m2> <m3> <mW> <h1> <h2> <h3> <path>
+            "u" => {
+                let fields: Vec<&str> = rest.splitn(10, ' ').collect();
+                if fields.len() == 10 {
+                    entries.push(StatusEntry {
+                        xy: fields[0].to_string(),
+                        path: fields[9].to_string(),
+                        orig_path: None,
+                        submodule: fields[1].starts_with('S'),
+                        unmerged: true,
+                    });
+                }
+            }
+            // ? <path>
+            "?" => entries.push(StatusEntry {
+                xy: "??".to_string(),
+                path: rest.to_string(),
+                ..StatusEntry::default()
+            }),
+            // Ignored entries ("!"), headers ("#") and the trailing empty record
+            _ => {}
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::adapters::fake::ScratchRepo;
+
+    /// `git status --porcelain=v2 -z` after staging an edit, a rename and a
+    /// removal, with untracked files (one with a space in its name)
+    const STATUS: &str = concat!(
+        "1 MM N... 100644 100644 100644 61780798228d17af2d34fce4cfbdf35556832472 e1998ca17e46f21f21f38e9cd8bf758071a1cd7d b\0",
+        "1 D. N... 100644 000000 000000 f2ad6c76f0115a6ba5b00456a849810e7ec0af20 0000000000000000000000000000000000000000 c\0",
+        "2 R. N... 100644 100644 100644 78981922613b2afb6025042ff6bd878ac1994e85 78981922613b2afb6025042ff6bd878ac1994e85 R100 renamed\0a\0",
+        "? c\0",
+        "? sp ace\0",
+    );
+
+    /// The same during a merge with every kind of conflict
+    const CONFLICTS: &str = concat!(
+        "u AA N... 000000 100644 100644 100644 0000000000000000000000000000000000000000 13e7564ea0c889e81bcba6f8e496b2a74cdb32fa 718f4d2ff533cf8ead8d3556cf43912bd245fbc4 added\0",
+        "u UD N... 100644 100644 000000 100644 df967b96a579e45a18b8251732d16804b2e56a55 b19a1e93bec1317dc6097229e12afaffbfa74dc2 0000000000000000000000000000000000000000 delthem\0",
+        "u DU N... 100644 000000 100644 100644 df967b96a579e45a18b8251732d16804b2e56a55 0000000000000000000000000000000000000000 950b81b7eee953d050aa05a641f8e056c85dd1bd delus\0",
+    );
+
+    #[test]
+    fn parses_changed_renamed_and_untracked_entries() {
+        let entries = parse_porcelain_v2(STATUS);
+        let summary: Vec<(&str, &str)> = entries.iter().map(|e| (e.xy.as_str(), e.path.as_str())).collect();
+        assert_eq!(
+            summary,
+            [("MM", "b"), ("D.", "c"), ("R.", "renamed"), ("??", "c"), ("??", "sp ace")]
+        );
+        assert_eq!(entries[2].orig_path.as_deref(), Some("a"));
+        assert!(entries.iter().all(|e| !e.unmerged && !e.submodule));
+    }
+
+    #[test]
+    fn parses_unmerged_entries() {
+        let entries = parse_porcelain_v2(CONFLICTS);
+        let codes: Vec<&str> = entries.iter().map(|e| e.xy.as_str()).collect();
+        assert_eq!(codes, ["AA", "UD", "DU"]);
+        assert!(entries.iter().all(|e| e.unmerged));
+        assert_eq!(entries[2].path, "delus");
+    }
+
+    #[test]
+    fn paths_keep_spaces_and_submodules_are_flagged() {
+        let entries = parse_porcelain_v2(
+            "1 .M SC.. 160000 160000 160000 aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa vendor/my lib\0",
+        );
+        assert_eq!(entries[0].path, "vendor/my lib");
+        assert!(entries[0].submodule);
+    }
+
+    #[test]
+    fn remote_branches_leave_out_symbolic_refs() {
+        let repo = ScratchRepo::new();
+        repo.git(&["update-ref", "refs/remotes/origin/main", "HEAD"]);
+        repo.git(&["symbolic-ref", "refs/remotes/origin/HEAD", "refs/remotes/origin/main"]);
+
+        let git = CliBackend::open(Some(&repo.path)).unwrap();
+        let branches = git.list_remote_branches().unwrap();
+        assert_eq!(branches.len(), 1);
+        assert!(branches[0].starts_with("origin/main|Fake Author|"));
+    }
+
+    fn same_dir(a: &Path, b: &Path) -> bool {
+        a.canonicalize().unwrap() == b.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn opening_needs_an_existing_directory() {
+        let missing = std::env::temp_dir().join(format!("rfx-missing-{}", std::process::id()));
+        assert!(matches!(CliBackend::open(Some(&missing)), Err(RfxError::Validation(_))));
+    }
+
+    #[test]
+    fn opening_outside_a_work_tree_is_not_a_repo() {
+        let repo = ScratchRepo::new();
+        // Inside .git there is a repository but no work tree
+        let git_dir = repo.path.join(".git");
+        assert!(matches!(CliBackend::open(Some(&git_dir)), Err(RfxError::NotARepo)));
+
+        repo.git(&["init", "-q", "--bare", "bare.git"]);
+        assert!(matches!(CliBackend::open(Some(&repo.path.join("bare.git"))), Err(RfxError::NotARepo)));
+
+        let plain = std::env::temp_dir().join(format!("rfx-plain-{}", std::process::id()));
+        fs::create_dir_all(&plain).unwrap();
+        let opened = CliBackend::open(Some(&plain));
+        fs::remove_dir_all(&plain).unwrap();
+        assert!(matches!(opened, Err(RfxError::NotARepo)));
+    }
+
+    #[test]
+    fn opening_a_subdirectory_finds_the_repository() {
+        let repo = ScratchRepo::new();
+        let sub = repo.path.join("src/deep");
+        fs::create_dir_all(&sub).unwrap();
+
+        let git = CliBackend::open(Some(&sub)).unwrap();
+        assert!(same_dir(&git.work_tree().unwrap(), &repo.path));
+        assert!(same_dir(&git.git_dir().unwrap(), &repo.path.join(".git")));
+    }
+
+    #[test]
+    fn git_runs_in_the_given_repository_not_the_current_directory() {
+        let repo = ScratchRepo::new();
+        repo.git(&["checkout", "-q", "-b", "only-here"]);
+        fs::write(repo.path.join("new.txt"), "x").unwrap();
+
+        let git = CliBackend::open(Some(&repo.path)).unwrap();
+        assert_ne!(std::env::current_dir().unwrap().canonicalize().unwrap(), repo.path.canonicalize().unwrap());
+        assert_eq!(git.current_branch().unwrap(), "only-here");
+        let status = git.status().unwrap();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].path, "new.txt");
+        assert!(same_dir(&git.work_tree().unwrap(), &repo.path));
+    }
+
+    #[test]
+    fn commit_messages_keep_their_description_and_trailers() {
+        let repo = ScratchRepo::new();
+        let message = "Fix the typo\n\nIt said tpyo.\n\nSigned-off-by: Ann <ann@example.com>";
+        repo.git(&["commit", "-q", "--allow-empty", "-m", message]);
+
+        let git = CliBackend::open(Some(&repo.path)).unwrap();
+        assert_eq!(git.commit_message("HEAD").unwrap(), message);
+        assert!(git.last_commit("HEAD").unwrap().ends_with("|Fix the typo"));
+    }
+}
@@ -0,0 +1,818 @@
+//! Scripted in-memory backend for deterministic tests of `core`.
+//!
+//! The fake keeps a tiny model of a repository (branches, commits, changed
+//! files, remotes) and mutates it the way git would. Any method can be
+//! scripted to fail with `fail_on`, and every call is recorded so tests can
+//! assert on what `core` asked for.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{DiffTarget, GitBackend, InProgress, PullStrategy, ResetMode, StatusEntry};
+use crate::error::{Result, RfxError};
+
+#[derive(Clone, Debug)]
+pub struct FakeCommit {
+    pub hash: String,
+    pub author: String,
+    pub date: String,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct FakeState {
+    current_branch: String,
+    /// Local branches, newest commit first
+    branches: Vec<(String, Vec<FakeCommit>)>,
+    changes: Vec<StatusEntry>,
+    staged: Vec<String>,
+    upstreams: HashMap<String, (usize, usize)>,
+    remotes: Vec<(String, String)>,
+    remote_branches: Vec<String>,
+    /// What has been pushed: `origin/<branch>` -> commits, newest first
+    remote_heads: HashMap<String, Vec<FakeCommit>>,
+    /// Stash entries, newest first: message and the changes set aside
+    stashes: Vec<(String, Vec<StatusEntry>)>,
+    /// Git config values; `remote.<name>.head` scripts `remote_head`
+    config: HashMap<String, String>,
+    /// Unstaged diff of each file, as `git diff` prints it
+    diffs: HashMap<String, String>,
+    /// Tags, newest first, with the revision each one points at
+    tags: Vec<(String, String)>,
+    /// Histories no branch points at any more (after a reset, amend or
+    /// deleted ref), so their commits can still be found like git objects
+    dropped: Vec<Vec<FakeCommit>>,
+    failures: HashMap<&'static str, RfxError>,
+    calls: Vec<String>,
+    next_hash: usize,
+    /// Scratch `.git` directory (where rfx keeps its journal), removed on
+    /// drop; the working tree is its `worktree` subdirectory
+    git_dir: PathBuf,
+}
+
+/// Build a commit for seeding branches; the hash is derived from the message
+pub fn fake_commit(message: &str) -> FakeCommit {
+    let hash = message.bytes().fold(0u32, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u32));
+    FakeCommit {
+        hash: format!("{:07x}", hash & 0xfff_ffff),
+        author: "Fake Author".to_string(),
+        date: "2024-01-01".to_string(),
+        message: message.to_string(),
+    }
+}
+
+pub struct FakeBackend {
+    state: RefCell<FakeState>,
+}
+
+/// Keeps the scratch directories of fakes in parallel tests apart
+static NEXT_FAKE: AtomicUsize = AtomicUsize::new(0);
+
+impl FakeBackend {
+    /// A repository with an empty `main` branch checked out
+    pub fn new() -> Self {
+        let n = NEXT_FAKE.fetch_add(1, Ordering::Relaxed);
+        let git_dir = std::env::temp_dir().join(format!("rfx-fake-{}-{}", std::process::id(), n));
+        let state = FakeState {
+            current_branch: "main".to_string(),
+            branches: vec![("main".to_string(), Vec::new())],
+            git_dir,
+            ..FakeState::default()
+        };
+        FakeBackend { state: RefCell::new(state) }
+    }
+
+    /// Add (or replace) a local branch with the given commits, newest first
+    pub fn with_branch(self, name: &str, commits: Vec<FakeCommit>) -> Self {
+        {
+            let mut state = self.state.borrow_mut();
+            state.branches.retain(|(b, _)| b != name);
+            state.branches.push((name.to_string(), commits));
+        }
+        self
+    }
+
+    pub fn on_branch(self, name: &str) -> Self {
+        self.state.borrow_mut().current_branch = name.to_string();
+        self
+    }
+
+    /// Add a changed file with a porcelain v2 XY code (e.g. ".M", "A.", "??", "UU")
+    pub fn with_change(self, xy: &str, path: &str) -> Self {
+        self.state.borrow_mut().changes.push(StatusEntry {
+            xy: xy.to_string(),
+            path: path.to_string(),
+            unmerged: matches!(xy, "UU" | "AA" | "DD" | "AU" | "UA" | "DU" | "UD"),
+            ..StatusEntry::default()
+        });
+        self
+    }
+
+    pub fn with_upstream(self, branch: &str, ahead: usize, behind: usize) -> Self {
+        self.state.borrow_mut().upstreams.insert(branch.to_string(), (ahead, behind));
+        self
+    }
+
+    pub fn with_remote(self, name: &str, url: &str) -> Self {
+        self.state.borrow_mut().remotes.push((name.to_string(), url.to_string()));
+        self
+    }
+
+    pub fn with_remote_branch(self, full_name: &str, author: &str, date: &str) -> Self {
+        self.state
+            .borrow_mut()
+            .remote_branches
+            .push(format!("{}|{}|{}", full_name, author, date));
+        self
+    }
+
+    /// Set a git config value
+    pub fn with_config(self, key: &str, value: &str) -> Self {
+        self.state.borrow_mut().config.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Script the diff `diff` returns for a file (whatever is compared)
+    pub fn with_diff(self, path: &str, diff: &str) -> Self {
+        self.state.borrow_mut().diffs.insert(path.to_string(), diff.to_string());
+        self
+    }
+
+    /// Tag `rev` (a branch or commit hash) as `name`
+    pub fn with_tag(self, name: &str, rev: &str) -> Self {
+        self.state.borrow_mut().tags.insert(0, (name.to_string(), rev.to_string()));
+        self
+    }
+
+    /// Make `<remote>/HEAD` point at `<remote>/<branch>`
+    pub fn with_remote_head(self, remote: &str, branch: &str) -> Self {
+        self.with_config(&format!("remote.{}.head", remote), branch)
+    }
+
+    /// Make every call to `method` fail with `err`
+    pub fn fail_on(self, method: &'static str, err: RfxError) -> Self {
+        self.state.borrow_mut().failures.insert(method, err);
+        self
+    }
+
+    /// Names of the backend methods called so far, in order
+    pub fn calls(&self) -> Vec<String> {
+        self.state.borrow().calls.clone()
+    }
+
+    /// Record the call and return the scripted failure, if any
+    fn enter(&self, method: &'static str) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        state.calls.push(method.to_string());
+        match state.failures.get(method) {
+            Some(err) => Err(err.clone()),
+            None => Ok(()),
+        }
+    }
+
+    fn commits_of(&self, branch: &str) -> Result<Vec<FakeCommit>> {
+        self.resolve(branch)
+    }
+
+    /// History (newest first) of a revision: branch, `HEAD`, `refs/heads/x`,
+    /// a commit hash, optionally followed by `~n`
+    fn resolve(&self, rev: &str) -> Result<Vec<FakeCommit>> {
+        let unknown = || RfxError::InvalidRef(format!("unknown revision '{}'", rev));
+        let (base, back) = match rev.split_once('~') {
+            Some((b, n)) => (b, n.parse::<usize>().map_err(|_| unknown())?),
+            None => (rev, 0),
+        };
+        let base = base.trim_start_matches("refs/heads/");
+
+        let state = self.state.borrow();
+        let tagged = state.tags.iter().find(|(t, _)| t == base).map(|(_, rev)| rev.as_str());
+        let base = tagged.unwrap_or(base);
+        let name = if base == "HEAD" { state.current_branch.as_str() } else { base };
+        let remote = state.remote_heads.get(name.trim_start_matches("refs/remotes/"));
+        let history = match state.branches.iter().find(|(b, _)| b == name) {
+            Some((_, commits)) => commits.clone(),
+            None if remote.is_some() => remote.cloned().unwrap_or_default(),
+            None => state
+                .branches
+                .iter()
+                .map(|(_, commits)| commits)
+                .chain(state.dropped.iter())
+                .find_map(|commits| {
+                    commits
+                        .iter()
+                        .position(|c| c.hash.starts_with(base))
+                        .map(|i| commits[i..].to_vec())
+                })
+                .ok_or_else(unknown)?,
+        };
+
+        if back > history.len() || (back > 0 && back == history.len()) {
+            return Err(unknown());
+        }
+        Ok(history[back..].to_vec())
+    }
+}
+
+impl Default for FakeBackend {
+    fn default() -> Self {
+        FakeBackend::new()
+    }
+}
+
+impl Drop for FakeBackend {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.state.borrow().git_dir);
+    }
+}
+
+/// A real repository in a scratch directory, for testing the backends
+/// against git itself; removed on drop
+pub struct ScratchRepo {
+    pub path: PathBuf,
+}
+
+impl ScratchRepo {
+    /// `git init` with one commit on `main`
+    pub fn new() -> Self {
+        let n = NEXT_FAKE.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("rfx-repo-{}-{}", std::process::id(), n));
+        std::fs::create_dir_all(&path).unwrap();
+        let repo = ScratchRepo { path };
+        repo.git(&["init", "-q", "-b", "main"]);
+        repo.git(&["commit", "-q", "--allow-empty", "-m", "First"]);
+        repo
+    }
+
+    /// Run git in the repository, panicking if it fails; returns stdout
+    pub fn git(&self, args: &[&str]) -> String {
+        git_in(&self.path, args)
+    }
+}
+
+/// Run git in `dir` without the user's or system config
+fn git_in(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(["-c", "user.name=Fake Author", "-c", "user.email=fake@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+impl Drop for ScratchRepo {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+impl GitBackend for FakeBackend {
+    fn status(&self) -> Result<Vec<StatusEntry>> {
+        self.enter("status")?;
+        Ok(self.state.borrow().changes.clone())
+    }
+
+    fn unstage(&self, files: &[String]) -> Result<String> {
+        self.enter("unstage")?;
+        self.state.borrow_mut().staged.retain(|f| !files.contains(f));
+        Ok(String::new())
+    }
+
+    fn ahead_behind(&self, branch: &str) -> Result<(usize, usize)> {
+        self.enter("ahead_behind")?;
+        self.state.borrow().upstreams.get(branch).copied().ok_or(RfxError::NoUpstream)
+    }
+
+    fn divergence(&self, local: &str, other: &str) -> Result<(usize, usize)> {
+        self.enter("divergence")?;
+        let local = self.resolve(local)?;
+        let other = self.resolve(other)?;
+        let only_local = local.iter().filter(|c| !other.iter().any(|o| o.hash == c.hash)).count();
+        let only_other = other.iter().filter(|c| !local.iter().any(|l| l.hash == c.hash)).count();
+        Ok((only_local, only_other))
+    }
+
+    fn push(&self, remote: &str, branch: &str, set_upstream: bool) -> Result<String> {
+        self.enter("push")?;
+        let commits = self.resolve(branch)?;
+        let mut state = self.state.borrow_mut();
+        state.remote_heads.insert(format!("{}/{}", remote, branch), commits);
+        if !set_upstream {
+            return Ok(String::new());
+        }
+        state.upstreams.insert(branch.to_string(), (0, 0));
+        state.config.insert(format!("branch.{}.remote", branch), remote.to_string());
+        state.config.insert(format!("branch.{}.merge", branch), format!("refs/heads/{}", branch));
+        Ok(format!("branch '{}' set up to track '{}/{}'.", branch, remote, branch))
+    }
+
+    fn create_branch(&self, name: &str, start: Option<&str>, track: bool) -> Result<String> {
+        self.enter("create_branch")?;
+        let commits = self.commits_of(start.unwrap_or("HEAD"))?;
+        let mut state = self.state.borrow_mut();
+        state.branches.push((name.to_string(), commits));
+        state.current_branch = name.to_string();
+        if track && let Some((remote, branch)) = start.and_then(|s| s.split_once('/')) {
+            state.config.insert(format!("branch.{}.remote", name), remote.to_string());
+            state.config.insert(format!("branch.{}.merge", name), format!("refs/heads/{}", branch));
+        }
+        Ok(format!("Switched to a new branch '{}'", name))
+    }
+
+    fn list_tags(&self) -> Result<Vec<String>> {
+        self.enter("list_tags")?;
+        Ok(self.state.borrow().tags.iter().map(|(t, _)| t.clone()).collect())
+    }
+
+    fn fetch(&self) -> Result<String> {
+        self.enter("fetch")?;
+        Ok(String::new())
+    }
+
+    fn fetch_prune(&self) -> Result<String> {
+        self.enter("fetch_prune")?;
+        Ok(String::new())
+    }
+
+    fn list_remote_branches(&self) -> Result<Vec<String>> {
+        self.enter("list_remote_branches")?;
+        Ok(self.state.borrow().remote_branches.clone())
+    }
+
+    fn pull_branch(&self, _remote: &str, _branch: &str, _strategy: PullStrategy) -> Result<String> {
+        self.enter("pull_branch")?;
+        let mut state = self.state.borrow_mut();
+        let current = state.current_branch.clone();
+        if let Some(counts) = state.upstreams.get_mut(&current) {
+            counts.1 = 0;
+        }
+        Ok("Already up to date.".to_string())
+    }
+
+    fn remote_head(&self, remote: &str) -> Result<Option<String>> {
+        self.enter("remote_head")?;
+        let key = format!("remote.{}.head", remote);
+        Ok(self.state.borrow().config.get(&key).map(|b| format!("{}/{}", remote, b)))
+    }
+
+    fn config_get(&self, key: &str) -> Result<Option<String>> {
+        self.enter("config_get")?;
+        Ok(self.state.borrow().config.get(key).cloned())
+    }
+
+    fn config_set(&self, key: &str, value: &str) -> Result<String> {
+        self.enter("config_set")?;
+        self.state.borrow_mut().config.insert(key.to_string(), value.to_string());
+        Ok(String::new())
+    }
+
+    fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool> {
+        self.enter("is_ancestor")?;
+        let tip = self.resolve(ancestor)?.first().map(|c| c.hash.clone());
+        let history = self.resolve(descendant)?;
+        Ok(tip.is_none_or(|t| history.iter().any(|c| c.hash == t)))
+    }
+
+    fn reset(&self, _mode: ResetMode, target: &str) -> Result<String> {
+        self.enter("reset")?;
+        let history = self.resolve(target)?;
+        let mut state = self.state.borrow_mut();
+        let current = state.current_branch.clone();
+        if let Some(i) = state.branches.iter().position(|(b, _)| *b == current) {
+            let old = std::mem::replace(&mut state.branches[i].1, history);
+            state.dropped.push(old);
+        }
+        Ok(String::new())
+    }
+
+    fn current_branch(&self) -> Result<String> {
+        self.enter("current_branch")?;
+        Ok(self.state.borrow().current_branch.clone())
+    }
+
+    fn add(&self, files: &[String]) -> Result<String> {
+        self.enter("add")?;
+        self.state.borrow_mut().staged.extend(files.iter().cloned());
+        Ok(String::new())
+    }
+
+    fn add_all(&self) -> Result<String> {
+        self.enter("add_all")?;
+        let mut state = self.state.borrow_mut();
+        let paths: Vec<String> = state.changes.iter().map(|c| c.path.clone()).collect();
+        state.staged = paths;
+        Ok(String::new())
+    }
+
+    fn commit(&self, message: &str) -> Result<String> {
+        self.enter("commit")?;
+        let mut state = self.state.borrow_mut();
+        if state.staged.is_empty() {
+            return Err(RfxError::NothingToCommit);
+        }
+        let staged = std::mem::take(&mut state.staged);
+        state.changes.retain(|c| !staged.contains(&c.path));
+        state.next_hash += 1;
+        let commit = FakeCommit {
+            hash: format!("{:07x}", state.next_hash),
+            author: "Fake Author".to_string(),
+            date: "2024-01-01".to_string(),
+            message: message.to_string(),
+        };
+        let current = state.current_branch.clone();
+        if let Some((_, commits)) = state.branches.iter_mut().find(|(b, _)| *b == current) {
+            commits.insert(0, commit.clone());
+        }
+        Ok(format!("[{} {}] {}", current, commit.hash, message))
+    }
+
+    fn commit_amend(&self, message: &str, include_staged: bool) -> Result<String> {
+        self.enter("commit_amend")?;
+        let mut state = self.state.borrow_mut();
+        if include_staged {
+            let staged = std::mem::take(&mut state.staged);
+            state.changes.retain(|c| !staged.contains(&c.path));
+        }
+        state.next_hash += 1;
+        let hash = format!("{:07x}", state.next_hash);
+        let current = state.current_branch.clone();
+        if let Some((_, commits)) = state.branches.iter().find(|(b, _)| *b == current) {
+            let old = commits.clone();
+            state.dropped.push(old);
+        }
+        let last = state
+            .branches
+            .iter_mut()
+            .find(|(b, _)| *b == current)
+            .and_then(|(_, commits)| commits.first_mut())
+            .ok_or_else(|| RfxError::Git {
+                code: Some(128),
+                stderr: "fatal: You have nothing to amend.".to_string(),
+            })?;
+        last.hash = hash.clone();
+        last.message = message.to_string();
+        Ok(format!("[{} {}] {}", current, hash, message))
+    }
+
+    fn list_branches(&self) -> Result<Vec<String>> {
+        self.enter("list_branches")?;
+        Ok(self.state.borrow().branches.iter().map(|(b, _)| b.clone()).collect())
+    }
+
+    fn list_branch_refs(&self) -> Result<Vec<String>> {
+        self.enter("list_branch_refs")?;
+        let state = self.state.borrow();
+        Ok(state
+            .branches
+            .iter()
+            .map(|(name, commits)| {
+                let sha = commits.first().map(|c| c.hash.as_str()).unwrap_or("");
+                let upstream = if state.upstreams.contains_key(name) { format!("origin/{}", name) } else { String::new() };
+                format!("{}|{}|{}||0", name, sha, upstream)
+            })
+            .collect())
+    }
+
+    fn merged_branches(&self, target: &str) -> Result<Vec<String>> {
+        self.enter("merged_branches")?;
+        let history = self.resolve(target)?;
+        let state = self.state.borrow();
+        Ok(state
+            .branches
+            .iter()
+            .filter(|(_, commits)| commits.first().is_none_or(|tip| history.iter().any(|c| c.hash == tip.hash)))
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<String> {
+        self.enter("delete_branch")?;
+        let mut state = self.state.borrow_mut();
+        if state.current_branch == name {
+            return Err(RfxError::Validation(format!("Cannot delete the checked-out branch '{}'", name)));
+        }
+        let Some(i) = state.branches.iter().position(|(b, _)| b == name) else {
+            return Err(RfxError::InvalidRef(format!("branch '{}' not found", name)));
+        };
+        let (_, old) = state.branches.remove(i);
+        state.dropped.push(old);
+        Ok(format!("Deleted branch {}", name))
+    }
+
+    fn first_commit(&self, branch: &str) -> Result<String> {
+        self.enter("first_commit")?;
+        let commits = self.commits_of(branch)?;
+        Ok(commits
+            .last()
+            .map(|c| format!("{}|{}", c.author, c.date))
+            .unwrap_or_else(|| "Unknown|Unknown".to_string()))
+    }
+
+    fn last_commit(&self, branch: &str) -> Result<String> {
+        self.enter("last_commit")?;
+        let commits = self.commits_of(branch)?;
+        // Like `git log --format=%s`: the first line only
+        Ok(commits
+            .first()
+            .map(|c| format!("{}|{}", c.date, c.message.lines().next().unwrap_or_default()))
+            .unwrap_or_else(|| "Unknown|No commit".to_string()))
+    }
+
+    fn commit_message(&self, rev: &str) -> Result<String> {
+        self.enter("commit_message")?;
+        let commits = self.commits_of(rev)?;
+        commits
+            .first()
+            .map(|c| c.message.trim().to_string())
+            .ok_or_else(|| RfxError::InvalidRef(format!("unknown revision '{}'", rev)))
+    }
+
+    fn list_remotes(&self) -> Result<Vec<String>> {
+        self.enter("list_remotes")?;
+        let state = self.state.borrow();
+        Ok(state
+            .remotes
+            .iter()
+            .flat_map(|(name, url)| {
+                vec![
+                    format!("{} {} (fetch)", name, url),
+                    format!("{} {} (push)", name, url),
+                ]
+            })
+            .collect())
+    }
+
+    fn list_commits(&self, branch: &str, count: usize) -> Result<Vec<String>> {
+        self.enter("list_commits")?;
+        let commits = self.commits_of(branch)?;
+        Ok(commits
+            .iter()
+            .take(count)
+            .map(|c| format!("{}|{}|{}|{}", c.hash, c.author, c.date, c.message))
+            .collect())
+    }
+
+    fn list_commits_between(&self, from: &str, to: &str, count: usize) -> Result<Vec<String>> {
+        self.enter("list_commits_between")?;
+        let known = self.resolve(from)?;
+        Ok(self
+            .resolve(to)?
+            .iter()
+            .filter(|c| !known.iter().any(|k| k.hash == c.hash))
+            .take(count)
+            .map(|c| format!("{}|{}|{}|{}", c.hash, c.author, c.date, c.message))
+            .collect())
+    }
+
+    fn diff_files(&self, _from: &str, _to: &str) -> Result<String> {
+        self.enter("diff_files")?;
+        Ok(String::new())
+    }
+
+    fn merge_tree(&self, _ours: &str, _theirs: &str) -> Result<Vec<String>> {
+        self.enter("merge_tree")?;
+        Ok(Vec::new())
+    }
+
+    fn diff(&self, _target: &DiffTarget, paths: &[String]) -> Result<String> {
+        self.enter("diff")?;
+        let state = self.state.borrow();
+        let mut files: Vec<(&String, &String)> = state
+            .diffs
+            .iter()
+            .filter(|(path, _)| paths.is_empty() || paths.contains(path))
+            .collect();
+        files.sort();
+        Ok(files.into_iter().map(|(_, diff)| diff.as_str()).collect())
+    }
+
+    fn apply_cached(&self, patch: &str) -> Result<String> {
+        self.enter("apply_cached")?;
+        let mut state = self.state.borrow_mut();
+        for path in patch.lines().filter_map(|l| l.strip_prefix("+++ b/")) {
+            state.staged.push(path.to_string());
+        }
+        Ok(String::new())
+    }
+
+    fn work_tree(&self) -> Result<PathBuf> {
+        self.enter("work_tree")?;
+        Ok(self.state.borrow().git_dir.join("worktree"))
+    }
+
+    fn remove(&self, files: &[String]) -> Result<String> {
+        self.enter("remove")?;
+        let mut state = self.state.borrow_mut();
+        state.changes.retain(|c| !files.contains(&c.path));
+        state.staged.extend(files.iter().cloned());
+        Ok(String::new())
+    }
+
+    fn restore_files(&self, files: &[String]) -> Result<String> {
+        self.enter("restore_files")?;
+        let mut state = self.state.borrow_mut();
+        state.changes.retain(|c| !files.contains(&c.path));
+        state.staged.retain(|p| !files.contains(p));
+        Ok(String::new())
+    }
+
+    fn abort_in_progress(&self, _op: InProgress) -> Result<String> {
+        self.enter("abort_in_progress")?;
+        self.state.borrow_mut().changes.retain(|c| !c.unmerged);
+        Ok(String::new())
+    }
+
+    fn continue_in_progress(&self, op: InProgress) -> Result<String> {
+        self.enter("continue_in_progress")?;
+        match op {
+            InProgress::Merge => self.commit("Merge"),
+            _ => Ok(String::new()),
+        }
+    }
+
+    fn mergetool(&self, path: &str) -> Result<()> {
+        self.enter("mergetool")?;
+        self.state.borrow_mut().changes.retain(|c| c.path != path);
+        Ok(())
+    }
+
+    fn editor(&self) -> Result<String> {
+        self.enter("editor")?;
+        Ok("true".to_string())
+    }
+
+    fn git_dir(&self) -> Result<PathBuf> {
+        self.enter("git_dir")?;
+        Ok(self.state.borrow().git_dir.clone())
+    }
+
+    fn rev_parse(&self, rev: &str) -> Result<String> {
+        self.enter("rev_parse")?;
+        self.resolve(rev)?
+            .first()
+            .map(|c| c.hash.clone())
+            .ok_or_else(|| RfxError::InvalidRef(format!("'{}' does not point to a commit", rev)))
+    }
+
+    fn update_ref(&self, refname: &str, sha: &str) -> Result<String> {
+        self.enter("update_ref")?;
+        if let Some(branch) = refname.strip_prefix("refs/heads/") {
+            let history = self.resolve(sha)?;
+            let mut state = self.state.borrow_mut();
+            if let Some(i) = state.branches.iter().position(|(b, _)| b == branch) {
+                let (_, old) = state.branches.remove(i);
+                state.dropped.push(old);
+            }
+            state.branches.push((branch.to_string(), history));
+        }
+        Ok(String::new())
+    }
+
+    fn delete_ref(&self, refname: &str) -> Result<String> {
+        self.enter("delete_ref")?;
+        if let Some(branch) = refname.strip_prefix("refs/heads/") {
+            let mut state = self.state.borrow_mut();
+            if let Some(i) = state.branches.iter().position(|(b, _)| b == branch) {
+                let (_, old) = state.branches.remove(i);
+                state.dropped.push(old);
+            }
+        }
+        Ok(String::new())
+    }
+
+    fn checkout(&self, branch: &str) -> Result<String> {
+        self.enter("checkout")?;
+        let mut state = self.state.borrow_mut();
+        if !state.branches.iter().any(|(b, _)| b == branch) {
+            return Err(RfxError::InvalidRef(format!("pathspec '{}' did not match", branch)));
+        }
+        state.current_branch = branch.to_string();
+        Ok(String::new())
+    }
+
+    fn checkout_tracking(&self, name: &str, remote_branch: &str) -> Result<String> {
+        self.enter("checkout_tracking")?;
+        let mut state = self.state.borrow_mut();
+        let known = state.remote_branches.iter().any(|line| line.split('|').next() == Some(remote_branch));
+        if !known && !state.remote_heads.contains_key(remote_branch) {
+            return Err(RfxError::InvalidRef(format!("'{}' is not a remote branch", remote_branch)));
+        }
+        let commits = state.remote_heads.get(remote_branch).cloned().unwrap_or_default();
+        state.branches.push((name.to_string(), commits));
+        state.current_branch = name.to_string();
+        Ok(String::new())
+    }
+
+    fn push_with_lease(&self, remote: &str, branch: &str, new_sha: Option<&str>, _expected: Option<&str>) -> Result<String> {
+        self.enter("push_with_lease")?;
+        if !self.state.borrow().remotes.iter().any(|(name, _)| name == remote) {
+            return Err(RfxError::Git {
+                code: Some(128),
+                stderr: format!("fatal: '{}' does not appear to be a git repository", remote),
+            });
+        }
+        let key = format!("{}/{}", remote, branch);
+        match new_sha {
+            Some(sha) => {
+                let history = self.resolve(sha)?;
+                self.state.borrow_mut().remote_heads.insert(key, history);
+            }
+            None => {
+                self.state.borrow_mut().remote_heads.remove(&key);
+            }
+        }
+        Ok(String::new())
+    }
+
+    fn parents(&self, rev: &str) -> Result<Vec<String>> {
+        self.enter("parents")?;
+        let history = self.resolve(rev)?;
+        Ok(history.get(1).map(|c| vec![c.hash.clone()]).unwrap_or_default())
+    }
+
+    fn remote_refs_containing(&self, sha: &str) -> Result<Vec<String>> {
+        self.enter("remote_refs_containing")?;
+        let state = self.state.borrow();
+        Ok(state
+            .remote_heads
+            .iter()
+            .filter(|(_, commits)| commits.iter().any(|c| c.hash.starts_with(sha)))
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+
+    fn revert(&self, sha: &str, _mainline: Option<u32>) -> Result<String> {
+        self.enter("revert")?;
+        let reverted = self.resolve(sha)?.first().cloned().ok_or_else(|| RfxError::InvalidRef(sha.to_string()))?;
+        self.state.borrow_mut().staged.push(String::from("(revert)"));
+        self.commit(&format!("Revert \"{}\"", reverted.message))
+    }
+
+    fn stash_push(&self, message: &str, include_untracked: bool) -> Result<String> {
+        self.enter("stash_push")?;
+        let mut state = self.state.borrow_mut();
+        let (set_aside, kept): (Vec<StatusEntry>, Vec<StatusEntry>) = state
+            .changes
+            .drain(..)
+            .partition(|c| include_untracked || c.xy != "??");
+        state.changes = kept;
+        state.staged.clear();
+
+        let label = if message.is_empty() {
+            format!("WIP on {}", state.current_branch)
+        } else {
+            format!("On {}: {}", state.current_branch, message)
+        };
+        state.stashes.insert(0, (label, set_aside));
+        Ok(String::new())
+    }
+
+    fn stash_list(&self) -> Result<Vec<String>> {
+        self.enter("stash_list")?;
+        let state = self.state.borrow();
+        Ok(state
+            .stashes
+            .iter()
+            .enumerate()
+            .map(|(i, (label, _))| format!("stash@{{{}}}|just now|{}", i, label))
+            .collect())
+    }
+
+    fn stash_files(&self, stash: &str) -> Result<String> {
+        self.enter("stash_files")?;
+        let state = self.state.borrow();
+        let (_, changes) = stash_index(stash)
+            .and_then(|i| state.stashes.get(i))
+            .ok_or_else(|| RfxError::InvalidRef(stash.to_string()))?;
+        Ok(changes
+            .iter()
+            .map(|c| {
+                let code = c.xy.chars().find(|&ch| ch != '.').unwrap_or('M');
+                let code = if code == '?' { 'A' } else { code };
+                format!("{}\0{}\0", code, c.path)
+            })
+            .collect())
+    }
+
+    fn stash_pop(&self, stash: &str, _index: bool) -> Result<String> {
+        self.enter("stash_pop")?;
+        let mut state = self.state.borrow_mut();
+        let i = stash_index(stash)
+            .filter(|&i| i < state.stashes.len())
+            .ok_or_else(|| RfxError::InvalidRef(stash.to_string()))?;
+        let (_, changes) = state.stashes.remove(i);
+        state.changes.extend(changes);
+        Ok(String::new())
+    }
+}
+
+/// `stash@{2}` -> 2
+fn stash_index(stash: &str) -> Option<usize> {
+    stash.strip_prefix("stash@{")?.strip_suffix('}')?.parse().ok()
+}
@@ -1,161 +1,254 @@
-use std::process::Command;
-
-/// Run a git command and return the trimmed output
-/// Use this for almost everything (getting branch names, hashes, etc.)
-fn run_git_command(args: &[&str]) -> Result<String, String> {
-    let output = Command::new("git")
-        .args(args)
-        .output()
-        .map_err(|e| format!("Failed to execute git: {}", e))?;
-
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
-    }
-}
+mod cli;
+#[cfg(test)]
+pub mod fake;
+#[cfg(feature = "native")]
+mod native;
 
-/// Run a git command and return the RAW output (preserving whitespace)
-/// Use this ONLY when column alignment matters (like `git status`)
-fn run_git_command_raw(args: &[&str]) -> Result<String, String> {
-    let output = Command::new("git")
-        .args(args)
-        .output()
-        .map_err(|e| format!("Failed to execute git: {}", e))?;
-
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
-    }
-}
+pub use cli::CliBackend;
+#[cfg(feature = "native")]
+pub use native::NativeBackend;
 
-/// Get the status of the current Git repository (porcelain format)
-pub fn git_status_porcelain() -> Result<Vec<(String, String)>, String> {
-    // USE RAW COMMAND HERE
-    let output = run_git_command_raw(&["status", "--porcelain=v1"])?;
-    
-    if output.is_empty() {
-        return Ok(Vec::new());
-    }
-
-    let files = output
-        .lines()
-        .map(|line| {
-            if line.len() < 4 {
-                return ("?".to_string(), line.to_string());
-            }
-            let (status, path) = line.split_at(3);
-            (status.trim().to_string(), path.trim().to_string())
-        })
-        .collect();
-
-    Ok(files)
-}
+use std::path::PathBuf;
 
-/// Check if local branch is ahead/behind remote
-pub fn git_ahead_behind(branch: &str) -> Result<(usize, usize), String> {
-    // "git rev-list --left-right --count HEAD...@{u}"
-    let arg = format!("{}...@{{u}}", branch);
-    let output = run_git_command(&["rev-list", "--left-right", "--count", &arg])?;
-
-    let parts: Vec<&str> = output.split_whitespace().collect();
-    if parts.len() >= 2 {
-        let ahead = parts[0].parse().unwrap_or(0);
-        let behind = parts[1].parse().unwrap_or(0);
-        Ok((ahead, behind))
-    } else {
-        Ok((0, 0))
-    }
-}
+use serde::Serialize;
 
-/// Push the current branch to origin, establishing a tracking link
-pub fn git_push_upstream(branch: &str) -> Result<String, String> {
-    run_git_command(&["push", "-u", "origin", branch])
-}
+use crate::error::Result;
 
-/// Create and switch to a new branch
-pub fn git_create_branch(name: &str) -> Result<String, String> {
-    run_git_command(&["checkout", "-b", name])
+/// One entry of `git status --porcelain=v2`, before interpretation by `core`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatusEntry {
+    /// Two-letter XY code: index state then worktree state (`.` = unchanged,
+    /// `?` = untracked, `U`/`A`/`D` pairs for unmerged entries)
+    pub xy: String,
+    pub path: String,
+    /// Source path of a rename or copy
+    pub orig_path: Option<String>,
+    pub submodule: bool,
+    pub unmerged: bool,
 }
 
-/// Pull changes from remote
-/*
-pub fn git_pull() -> Result<String, String> {
-    run_git_command(&["pull"]) 
-}
-*/
+/// Everything `core` needs from git.
+///
+/// Methods return git's own output formats (pipe-separated lines for
+/// listings); turning those into structured data is the job of `core`.
+pub trait GitBackend {
+    /// Changed, untracked and unmerged files
+    fn status(&self) -> Result<Vec<StatusEntry>>;
 
-/// Fetch latest changes/branches from remote (without merging)
-pub fn git_fetch() -> Result<String, String> {
-    run_git_command(&["fetch"])
-}
+    /// Remove files from the index, keeping their worktree contents
+    fn unstage(&self, files: &[String]) -> Result<String>;
 
-/// List remote branches with details
-pub fn git_list_remote_branches() -> Result<Vec<String>, String> {
-    let output = run_git_command(&[
-        "for-each-ref",
-        "--format=%(refname:short)|%(authorname)|%(authordate:relative)",
-        "refs/remotes/",
-    ])?;
-    
-    Ok(output.lines().map(|s| s.to_string()).collect())
-}
+    /// Commits `(ahead, behind)` of the branch's upstream
+    fn ahead_behind(&self, branch: &str) -> Result<(usize, usize)>;
 
-/// Pull a specific branch from origin
-pub fn git_pull_branch(branch_name: &str) -> Result<String, String> {
-    let clean_name = branch_name.trim_start_matches("origin/");
-    run_git_command(&["pull", "origin", clean_name])
-}
+    /// Commits `(only in local, only in other)` between two revisions
+    fn divergence(&self, local: &str, other: &str) -> Result<(usize, usize)>;
 
-/// Undo the last commit but keep changes in the working directory
-pub fn git_reset_soft(count: usize) -> Result<String, String> {
-    let arg = format!("HEAD~{}", count);
-    run_git_command(&["reset", "--soft", &arg])
-}
+    /// Push a branch to `remote`; with `set_upstream` the branch starts tracking it
+    fn push(&self, remote: &str, branch: &str, set_upstream: bool) -> Result<String>;
 
-// --- Standard Wrappers (Use trimmed output) ---
+    /// Create and switch to a new branch starting at `start` (HEAD when
+    /// `None`); with `track` the start (a remote branch) becomes its upstream
+    fn create_branch(&self, name: &str, start: Option<&str>, track: bool) -> Result<String>;
 
-pub fn git_branch() -> Result<String, String> {
-    run_git_command(&["rev-parse", "--abbrev-ref", "HEAD"])
-}
+    /// Tag names, newest first
+    fn list_tags(&self) -> Result<Vec<String>>;
 
-pub fn git_add(files: &[String]) -> Result<String, String> {
-    let mut args = vec!["add"];
-    args.extend(files.iter().map(|s| s.as_str()));
-    run_git_command(&args)
-}
+    /// Fetch latest changes/branches from every remote (without merging)
+    fn fetch(&self) -> Result<String>;
 
-pub fn git_add_all() -> Result<String, String> {
-    run_git_command(&["add", "-A"])
-}
+    /// `git fetch --prune`: also forget remote branches deleted on the server
+    fn fetch_prune(&self) -> Result<String>;
 
-pub fn git_commit(message: &str) -> Result<String, String> {
-    run_git_command(&["commit", "-m", message])
-}
+    /// Remote branches as `name|author|relative date` lines, without
+    /// symbolic refs such as `origin/HEAD`
+    fn list_remote_branches(&self) -> Result<Vec<String>>;
+
+    /// Pull `branch` from `remote` into the current branch
+    fn pull_branch(&self, remote: &str, branch: &str, strategy: PullStrategy) -> Result<String>;
+
+    /// Branch the remote's HEAD points at (`origin/main`), if known locally
+    fn remote_head(&self, remote: &str) -> Result<Option<String>>;
+
+    /// Value of a git config key, `None` when unset
+    fn config_get(&self, key: &str) -> Result<Option<String>>;
+
+    /// Set a git config key in the repository's config
+    fn config_set(&self, key: &str, value: &str) -> Result<String>;
+
+    /// Whether `ancestor` is contained in the history of `descendant`
+    fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool>;
+
+    /// Move the current branch to `target` (see `ResetMode`)
+    fn reset(&self, mode: ResetMode, target: &str) -> Result<String>;
+
+    /// Name of the checked-out branch
+    fn current_branch(&self) -> Result<String>;
+
+    fn add(&self, files: &[String]) -> Result<String>;
+
+    fn add_all(&self) -> Result<String>;
+
+    fn commit(&self, message: &str) -> Result<String>;
+
+    /// Replace the last commit (`git commit --amend`). Staged changes are
+    /// added to it only with `include_staged`; otherwise just the message changes.
+    fn commit_amend(&self, message: &str, include_staged: bool) -> Result<String>;
+
+    /// Local branch names
+    fn list_branches(&self) -> Result<Vec<String>>;
+
+    /// Local branches as `name|tip sha|upstream|tracking state|committer unix time`
+    /// lines; the tracking state is `[gone]` when the upstream was deleted
+    fn list_branch_refs(&self) -> Result<Vec<String>>;
+
+    /// Local branches whose tip is contained in `target`
+    fn merged_branches(&self, target: &str) -> Result<Vec<String>>;
+
+    /// Delete a local branch (and its config), merged or not
+    fn delete_branch(&self, name: &str) -> Result<String>;
+
+    /// Oldest commit on a branch as `author|date`
+    fn first_commit(&self, branch: &str) -> Result<String>;
 
-pub fn git_list_branches() -> Result<Vec<String>, String> {
-    let output = run_git_command(&["for-each-ref", "--format=%(refname:short)", "refs/heads/"])?;
-    Ok(output.lines().map(|line| line.trim().to_string()).collect())
+    /// Newest commit on a branch as `date|subject`
+    fn last_commit(&self, branch: &str) -> Result<String>;
+
+    /// Full message of a commit: subject, description and trailers
+    fn commit_message(&self, rev: &str) -> Result<String>;
+
+    /// `git remote -v` lines
+    fn list_remotes(&self) -> Result<Vec<String>>;
+
+    /// Recent commits as `hash|author|date|subject` lines
+    fn list_commits(&self, branch: &str, count: usize) -> Result<Vec<String>>;
+
+    /// Commits reachable from `to` but not from `from`, newest first,
+    /// as `hash|author|date|subject` lines
+    fn list_commits_between(&self, from: &str, to: &str, count: usize) -> Result<Vec<String>>;
+
+    /// Files `to` changed since it split off from `from`, `git diff --name-status -z` style
+    fn diff_files(&self, from: &str, to: &str) -> Result<String>;
+
+    /// Merge `theirs` into `ours` in memory (`git merge-tree --write-tree`)
+    /// and return the files that would conflict; nothing on disk is touched
+    fn merge_tree(&self, ours: &str, theirs: &str) -> Result<Vec<String>>;
+
+    /// Unified diff (`git diff -M`), limited to `paths` unless empty
+    fn diff(&self, target: &DiffTarget, paths: &[String]) -> Result<String>;
+
+    /// Apply a patch to the index only (`git apply --cached`)
+    fn apply_cached(&self, patch: &str) -> Result<String>;
+
+    /// Absolute path of the top of the working tree
+    fn work_tree(&self) -> Result<PathBuf>;
+
+    /// Delete files from the index and the working tree (`git rm`)
+    fn remove(&self, files: &[String]) -> Result<String>;
+
+    /// Put files back to their committed version, in both the index and the
+    /// working tree (`git restore --source=HEAD --staged --worktree`)
+    fn restore_files(&self, files: &[String]) -> Result<String>;
+
+    /// Give up on an interrupted operation and restore the state before it
+    /// (`git merge --abort`, `git rebase --abort`, `git bisect reset`, ...)
+    fn abort_in_progress(&self, op: InProgress) -> Result<String>;
+
+    /// Carry on with an interrupted operation once its conflicts are resolved,
+    /// keeping the commit messages git prepared
+    fn continue_in_progress(&self, op: InProgress) -> Result<String>;
+
+    /// Run the user's configured `git mergetool` on one file, attached to the terminal
+    fn mergetool(&self, path: &str) -> Result<()>;
+
+    /// The editor git would use (`core.editor`, `$VISUAL`, `$EDITOR`, ...)
+    fn editor(&self) -> Result<String>;
+
+    /// Absolute path of the `.git` directory
+    fn git_dir(&self) -> Result<PathBuf>;
+
+    /// Full commit hash of a revision
+    fn rev_parse(&self, rev: &str) -> Result<String>;
+
+    /// Point a ref (e.g. `refs/heads/main`) at a commit, creating it if needed
+    fn update_ref(&self, refname: &str, sha: &str) -> Result<String>;
+
+    /// Delete a ref without any safety checks
+    fn delete_ref(&self, refname: &str) -> Result<String>;
+
+    /// Switch to an existing local branch
+    fn checkout(&self, branch: &str) -> Result<String>;
+
+    /// Create local branch `name` tracking `remote_branch` (`origin/x`) and switch to it
+    fn checkout_tracking(&self, name: &str, remote_branch: &str) -> Result<String>;
+
+    /// Set a remote branch to `new_sha` (or delete it when `None`), but only if
+    /// it still points at `expected` (`--force-with-lease`)
+    fn push_with_lease(&self, remote: &str, branch: &str, new_sha: Option<&str>, expected: Option<&str>) -> Result<String>;
+
+    /// Full hashes of a commit's parents (empty for a root commit)
+    fn parents(&self, rev: &str) -> Result<Vec<String>>;
+
+    /// Remote-tracking branches (`origin/main`) whose history contains `sha`
+    fn remote_refs_containing(&self, sha: &str) -> Result<Vec<String>>;
+
+    /// Create a commit that reverses `sha`; merges need the `mainline` parent number
+    fn revert(&self, sha: &str, mainline: Option<u32>) -> Result<String>;
+
+    /// Stash local changes (`git stash push`), optionally with untracked files
+    fn stash_push(&self, message: &str, include_untracked: bool) -> Result<String>;
+
+    /// Stash entries, newest first, as `stash@{n}|relative date|subject` lines
+    fn stash_list(&self) -> Result<Vec<String>>;
+
+    /// Files changed by a stash entry, `git diff --name-status -z` style
+    fn stash_files(&self, stash: &str) -> Result<String>;
+
+    /// Re-apply a stash entry and drop it; the entry is kept if applying fails.
+    /// With `index` the staged/unstaged split is restored too.
+    fn stash_pop(&self, stash: &str, index: bool) -> Result<String>;
 }
 
-pub fn git_first_commit(branch: &str) -> Result<String, String> {
-    let output = run_git_command(&["log", "--reverse", "--format=%an|%ad", "--date=short", branch])?;
-    Ok(output.lines().next().unwrap_or("Unknown|Unknown").to_string())
+/// How `reset` treats the index and working directory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetMode {
+    /// Keep every change; the undone commits' changes end up staged
+    Soft,
+    /// Update files to the target, refusing if local edits would be lost
+    Keep,
 }
 
-pub fn git_last_commit(branch: &str) -> Result<String, String> {
-    let output = run_git_command(&["log", "-1", "--format=%ad|%s", "--date=short", branch])?;
-    Ok(output.lines().next().unwrap_or("Unknown|No commit").to_string())
+/// How `pull_branch` combines the remote branch with the current one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PullStrategy {
+    /// Only move the branch forward; fail if both sides have new commits
+    FastForwardOnly,
+    /// Create a merge commit when both sides have new commits
+    Merge,
+    /// Replay local commits on top of the remote branch
+    Rebase,
 }
 
-pub fn git_list_remotes() -> Result<Vec<String>, String> {
-    let output = run_git_command(&["remote", "-v"])?;
-    Ok(output.lines().map(|line| line.trim().to_string()).collect())
+/// A multi-step git operation that stopped halfway and waits for the user
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InProgress {
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    Bisect,
 }
 
-pub fn git_list_commits(branch: &str, count: usize) -> Result<Vec<String>, String> {
-    let count_arg = format!("-{}", count);
-    let output = run_git_command(&["log", &count_arg, "--pretty=format:%h|%an|%ad|%s", "--date=short", branch])?;
-    Ok(output.lines().map(|line| line.trim().to_string()).collect())
-}
\ No newline at end of file
+/// What `diff` compares
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffTarget {
+    /// Working directory against the index (unstaged changes)
+    Worktree,
+    /// Index against HEAD (staged changes)
+    Staged,
+    /// A commit against the working directory (`HEAD` = everything uncommitted)
+    Against(String),
+    /// One commit against another
+    Between(String, String),
+}
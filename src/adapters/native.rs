@@ -0,0 +1,625 @@
+//! In-process backend built on libgit2.
+//!
+//! Read-only queries (status, branches, commit walking, ahead/behind) run
+//! natively without spawning processes. Anything that mutates the repository
+//! or talks to the network is delegated to the CLI backend so credentials,
+//! hooks and config behave exactly like plain git.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use git2::{BranchType, FileMode, ReferenceType, Repository, Sort, Status, StatusOptions};
+
+use super::{CliBackend, DiffTarget, GitBackend, InProgress, PullStrategy, ResetMode, StatusEntry};
+use crate::error::{Result, RfxError};
+
+pub struct NativeBackend {
+    repo: Repository,
+    cli: CliBackend,
+}
+
+impl NativeBackend {
+    /// Open the repository at `path` (or the one containing the current directory)
+    pub fn open(path: Option<&Path>) -> Result<Self> {
+        // The CLI backend validates the work tree and gives friendly errors
+        let cli = CliBackend::open(path)?;
+        let repo = Repository::discover(path.unwrap_or(Path::new("."))).map_err(map_err)?;
+        Ok(NativeBackend { repo, cli })
+    }
+
+    /// Resolve a branch name or revision (e.g. "HEAD") to a commit
+    fn commit_of(&self, rev: &str) -> Result<git2::Commit<'_>> {
+        self.repo
+            .revparse_single(rev)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(map_err)
+    }
+}
+
+fn map_err(e: git2::Error) -> RfxError {
+    match (e.class(), e.code()) {
+        (git2::ErrorClass::Repository, git2::ErrorCode::NotFound) => RfxError::NotARepo,
+        (_, git2::ErrorCode::NotFound) | (_, git2::ErrorCode::InvalidSpec) => {
+            RfxError::InvalidRef(e.message().to_string())
+        }
+        _ => RfxError::Git { code: None, stderr: e.message().to_string() },
+    }
+}
+
+/// Format a commit time as YYYY-MM-DD in the author's timezone (like `--date=short`)
+fn short_date(time: git2::Time) -> String {
+    let local = time.seconds() + i64::from(time.offset_minutes()) * 60;
+    let (y, m, d) = civil_from_days(local.div_euclid(86_400));
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// Days since 1970-01-01 to a (year, month, day) triple
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+/// Approximate git's `--date=relative` output
+fn relative_date(time: git2::Time) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let secs = (now - time.seconds()).max(0);
+
+    let plural = |n: i64, unit: &str| {
+        if n == 1 { format!("1 {} ago", unit) } else { format!("{} {}s ago", n, unit) }
+    };
+
+    match secs {
+        s if s < 90 => plural(s, "second"),
+        s if s < 90 * 60 => plural((s + 30) / 60, "minute"),
+        s if s < 36 * 3600 => plural((s + 1800) / 3600, "hour"),
+        s if s < 14 * 86_400 => plural((s + 43_200) / 86_400, "day"),
+        s if s < 70 * 86_400 => plural((s + 302_400) / 604_800, "week"),
+        s if s < 365 * 86_400 => plural((s + 1_296_000) / 2_592_000, "month"),
+        s => plural(s / 31_536_000, "year"),
+    }
+}
+
+/// Porcelain v2 XY code for a libgit2 status
+fn porcelain_xy(status: Status) -> String {
+    if status.contains(Status::WT_NEW) && !status.intersects(Status::INDEX_NEW) {
+        return "??".to_string();
+    }
+    if status.contains(Status::CONFLICTED) {
+        return "UU".to_string();
+    }
+
+    let index = if status.contains(Status::INDEX_NEW) {
+        'A'
+    } else if status.contains(Status::INDEX_MODIFIED) {
+        'M'
+    } else if status.contains(Status::INDEX_TYPECHANGE) {
+        'T'
+    } else if status.contains(Status::INDEX_DELETED) {
+        'D'
+    } else if status.contains(Status::INDEX_RENAMED) {
+        'R'
+    } else {
+        '.'
+    };
+    let worktree = if status.contains(Status::WT_MODIFIED) {
+        'M'
+    } else if status.contains(Status::WT_TYPECHANGE) {
+        'T'
+    } else if status.contains(Status::WT_DELETED) {
+        'D'
+    } else if status.contains(Status::WT_RENAMED) {
+        'R'
+    } else {
+        '.'
+    };
+
+    format!("{}{}", index, worktree)
+}
+
+/// Porcelain v2 XY code of an unmerged path, from which of the merge base
+/// (ancestor), our and their versions are still in the index
+fn conflict_xy(ancestor: bool, ours: bool, theirs: bool) -> &'static str {
+    match (ancestor, ours, theirs) {
+        (true, false, false) => "DD",
+        (false, true, false) => "AU",
+        (true, true, false) => "UD",
+        (false, false, true) => "UA",
+        (true, false, true) => "DU",
+        (false, true, true) => "AA",
+        _ => "UU",
+    }
+}
+
+impl NativeBackend {
+    /// XY codes of the conflicted paths in the index
+    fn conflict_codes(&self) -> Result<HashMap<String, &'static str>> {
+        let index = self.repo.index().map_err(map_err)?;
+        let mut codes = HashMap::new();
+        if !index.has_conflicts() {
+            return Ok(codes);
+        }
+
+        for conflict in index.conflicts().map_err(map_err)? {
+            let conflict = conflict.map_err(map_err)?;
+            let code = conflict_xy(conflict.ancestor.is_some(), conflict.our.is_some(), conflict.their.is_some());
+            let entry = conflict.our.as_ref().or(conflict.their.as_ref()).or(conflict.ancestor.as_ref());
+            if let Some(entry) = entry {
+                codes.insert(String::from_utf8_lossy(&entry.path).to_string(), code);
+            }
+        }
+        Ok(codes)
+    }
+}
+
+impl GitBackend for NativeBackend {
+    fn status(&self) -> Result<Vec<StatusEntry>> {
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(true)
+            .recurse_untracked_dirs(false)
+            .include_ignored(false)
+            .renames_head_to_index(true);
+
+        let statuses = self.repo.statuses(Some(&mut opts)).map_err(map_err)?;
+        // libgit2 only flags a path as conflicted; the index says how
+        let conflicts = self.conflict_codes()?;
+        let entries = statuses
+            .iter()
+            .filter(|entry| entry.status() != Status::CURRENT && !entry.status().contains(Status::IGNORED))
+            .map(|entry| {
+                let status = entry.status();
+                let staged = entry.head_to_index();
+                let unstaged = entry.index_to_workdir();
+
+                let orig_path = staged
+                    .as_ref()
+                    .filter(|_| status.contains(Status::INDEX_RENAMED))
+                    .and_then(|d| d.old_file().path().map(|p| p.to_string_lossy().to_string()));
+                let submodule = [&staged, &unstaged]
+                    .into_iter()
+                    .flatten()
+                    .any(|d| d.new_file().mode() == FileMode::Commit || d.old_file().mode() == FileMode::Commit);
+
+                // For renames `entry.path()` is the old name; report the new one
+                let path = match (&orig_path, &staged) {
+                    (Some(_), Some(d)) => d.new_file().path().map(|p| p.to_string_lossy().to_string()),
+                    _ => entry.path().map(|p| p.to_string()),
+                };
+
+                let path = path.unwrap_or_default();
+                let xy = match conflicts.get(&path) {
+                    Some(code) if status.contains(Status::CONFLICTED) => code.to_string(),
+                    _ => porcelain_xy(status),
+                };
+
+                StatusEntry {
+                    xy,
+                    path,
+                    orig_path,
+                    submodule,
+                    unmerged: status.contains(Status::CONFLICTED),
+                }
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn unstage(&self, files: &[String]) -> Result<String> {
+        self.cli.unstage(files)
+    }
+
+    fn ahead_behind(&self, branch: &str) -> Result<(usize, usize)> {
+        let local = self.repo.find_branch(branch, BranchType::Local).map_err(map_err)?;
+        let upstream = local.upstream().map_err(|_| RfxError::NoUpstream)?;
+
+        let local_oid = local.get().target().ok_or(RfxError::NoUpstream)?;
+        let upstream_oid = upstream.get().target().ok_or(RfxError::NoUpstream)?;
+
+        self.repo.graph_ahead_behind(local_oid, upstream_oid).map_err(map_err)
+    }
+
+    fn divergence(&self, local: &str, other: &str) -> Result<(usize, usize)> {
+        let local = self.repo.revparse_single(local).map_err(map_err)?.peel_to_commit().map_err(map_err)?;
+        let other = self.repo.revparse_single(other).map_err(map_err)?.peel_to_commit().map_err(map_err)?;
+        self.repo.graph_ahead_behind(local.id(), other.id()).map_err(map_err)
+    }
+
+    fn push(&self, remote: &str, branch: &str, set_upstream: bool) -> Result<String> {
+        self.cli.push(remote, branch, set_upstream)
+    }
+
+    fn create_branch(&self, name: &str, start: Option<&str>, track: bool) -> Result<String> {
+        self.cli.create_branch(name, start, track)
+    }
+
+    fn list_tags(&self) -> Result<Vec<String>> {
+        self.cli.list_tags()
+    }
+
+    fn fetch(&self) -> Result<String> {
+        self.cli.fetch()
+    }
+
+    fn fetch_prune(&self) -> Result<String> {
+        self.cli.fetch_prune()
+    }
+
+    fn list_remote_branches(&self) -> Result<Vec<String>> {
+        let branches = self.repo.branches(Some(BranchType::Remote)).map_err(map_err)?;
+
+        let mut lines = Vec::new();
+        for item in branches {
+            let (branch, _) = item.map_err(map_err)?;
+            // Symbolic refs such as origin/HEAD only point at another branch
+            if branch.get().kind() == Some(ReferenceType::Symbolic) {
+                continue;
+            }
+            let name = match branch.name() {
+                Ok(Some(n)) => n.to_string(),
+                _ => continue,
+            };
+            let commit = match branch.get().peel_to_commit() {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            let author = commit.author();
+            lines.push(format!(
+                "{}|{}|{}",
+                name,
+                author.name().unwrap_or("Unknown"),
+                relative_date(author.when())
+            ));
+        }
+        lines.sort();
+
+        Ok(lines)
+    }
+
+    fn pull_branch(&self, remote: &str, branch: &str, strategy: PullStrategy) -> Result<String> {
+        self.cli.pull_branch(remote, branch, strategy)
+    }
+
+    fn remote_head(&self, remote: &str) -> Result<Option<String>> {
+        let name = format!("refs/remotes/{}/HEAD", remote);
+        match self.repo.find_reference(&name) {
+            Ok(reference) => Ok(reference
+                .symbolic_target()
+                .map(|t| t.trim_start_matches("refs/remotes/").to_string())),
+            Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(e) => Err(map_err(e)),
+        }
+    }
+
+    fn config_get(&self, key: &str) -> Result<Option<String>> {
+        self.cli.config_get(key)
+    }
+
+    fn config_set(&self, key: &str, value: &str) -> Result<String> {
+        self.cli.config_set(key, value)
+    }
+
+    fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool> {
+        let a = self.repo.revparse_single(ancestor).map_err(map_err)?.peel_to_commit().map_err(map_err)?.id();
+        let d = self.repo.revparse_single(descendant).map_err(map_err)?.peel_to_commit().map_err(map_err)?.id();
+        Ok(a == d || self.repo.graph_descendant_of(d, a).map_err(map_err)?)
+    }
+
+    fn reset(&self, mode: ResetMode, target: &str) -> Result<String> {
+        self.cli.reset(mode, target)
+    }
+
+    fn current_branch(&self) -> Result<String> {
+        let head = self.repo.head().map_err(map_err)?;
+        if self.repo.head_detached().unwrap_or(false) {
+            return Ok("HEAD".to_string());
+        }
+        Ok(head.shorthand().unwrap_or("HEAD").to_string())
+    }
+
+    fn add(&self, files: &[String]) -> Result<String> {
+        self.cli.add(files)
+    }
+
+    fn add_all(&self) -> Result<String> {
+        self.cli.add_all()
+    }
+
+    fn commit(&self, message: &str) -> Result<String> {
+        self.cli.commit(message)
+    }
+
+    fn commit_amend(&self, message: &str, include_staged: bool) -> Result<String> {
+        self.cli.commit_amend(message, include_staged)
+    }
+
+    fn list_branches(&self) -> Result<Vec<String>> {
+        let branches = self.repo.branches(Some(BranchType::Local)).map_err(map_err)?;
+
+        let mut names = Vec::new();
+        for item in branches {
+            let (branch, _) = item.map_err(map_err)?;
+            if let Ok(Some(name)) = branch.name() {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+
+        Ok(names)
+    }
+
+    fn list_branch_refs(&self) -> Result<Vec<String>> {
+        self.cli.list_branch_refs()
+    }
+
+    fn merged_branches(&self, target: &str) -> Result<Vec<String>> {
+        self.cli.merged_branches(target)
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<String> {
+        self.cli.delete_branch(name)
+    }
+
+    fn first_commit(&self, branch: &str) -> Result<String> {
+        // Follow first parents down to the root: one commit per step, without
+        // collecting and sorting the whole history
+        let mut oldest = self.commit_of(branch)?;
+        while let Ok(parent) = oldest.parent(0) {
+            oldest = parent;
+        }
+        let author = oldest.author();
+
+        Ok(format!("{}|{}", author.name().unwrap_or("Unknown"), short_date(author.when())))
+    }
+
+    fn last_commit(&self, branch: &str) -> Result<String> {
+        let commit = self.commit_of(branch)?;
+
+        Ok(format!(
+            "{}|{}",
+            short_date(commit.author().when()),
+            commit.summary().unwrap_or("No commit")
+        ))
+    }
+
+    fn commit_message(&self, rev: &str) -> Result<String> {
+        let commit = self.commit_of(rev)?;
+        Ok(String::from_utf8_lossy(commit.message_bytes()).trim().to_string())
+    }
+
+    fn list_remotes(&self) -> Result<Vec<String>> {
+        let names = self.repo.remotes().map_err(map_err)?;
+
+        let mut lines = Vec::new();
+        for name in names.iter().flatten() {
+            let remote = self.repo.find_remote(name).map_err(map_err)?;
+            let fetch_url = remote.url().unwrap_or("");
+            let push_url = remote.pushurl().unwrap_or(fetch_url);
+            lines.push(format!("{} {} (fetch)", name, fetch_url));
+            lines.push(format!("{} {} (push)", name, push_url));
+        }
+
+        Ok(lines)
+    }
+
+    fn list_commits(&self, branch: &str, count: usize) -> Result<Vec<String>> {
+        let tip = self.commit_of(branch)?;
+
+        let mut walk = self.repo.revwalk().map_err(map_err)?;
+        walk.push(tip.id()).map_err(map_err)?;
+        walk.set_sorting(Sort::TIME).map_err(map_err)?;
+
+        let mut lines = Vec::new();
+        for oid in walk.take(count) {
+            let commit = self.repo.find_commit(oid.map_err(map_err)?).map_err(map_err)?;
+            let short = commit.as_object().short_id().map_err(map_err)?;
+            lines.push(format!(
+                "{}|{}|{}|{}",
+                short.as_str().unwrap_or(""),
+                commit.author().name().unwrap_or("Unknown"),
+                short_date(commit.author().when()),
+                commit.summary().unwrap_or("")
+            ));
+        }
+
+        Ok(lines)
+    }
+
+    fn list_commits_between(&self, from: &str, to: &str, count: usize) -> Result<Vec<String>> {
+        let mut walk = self.repo.revwalk().map_err(map_err)?;
+        walk.push(self.commit_of(to)?.id()).map_err(map_err)?;
+        walk.hide(self.commit_of(from)?.id()).map_err(map_err)?;
+        walk.set_sorting(Sort::TIME).map_err(map_err)?;
+
+        let mut lines = Vec::new();
+        for oid in walk.take(count) {
+            let commit = self.repo.find_commit(oid.map_err(map_err)?).map_err(map_err)?;
+            let short = commit.as_object().short_id().map_err(map_err)?;
+            lines.push(format!(
+                "{}|{}|{}|{}",
+                short.as_str().unwrap_or(""),
+                commit.author().name().unwrap_or("Unknown"),
+                short_date(commit.author().when()),
+                commit.summary().unwrap_or("")
+            ));
+        }
+
+        Ok(lines)
+    }
+
+    fn diff_files(&self, from: &str, to: &str) -> Result<String> {
+        self.cli.diff_files(from, to)
+    }
+
+    fn merge_tree(&self, ours: &str, theirs: &str) -> Result<Vec<String>> {
+        self.cli.merge_tree(ours, theirs)
+    }
+
+    fn diff(&self, target: &DiffTarget, paths: &[String]) -> Result<String> {
+        self.cli.diff(target, paths)
+    }
+
+    fn apply_cached(&self, patch: &str) -> Result<String> {
+        self.cli.apply_cached(patch)
+    }
+
+    fn work_tree(&self) -> Result<PathBuf> {
+        self.repo
+            .workdir()
+            .map(|p| p.to_path_buf())
+            .ok_or_else(|| RfxError::Validation("This repository has no working tree.".to_string()))
+    }
+
+    fn remove(&self, files: &[String]) -> Result<String> {
+        self.cli.remove(files)
+    }
+
+    fn restore_files(&self, files: &[String]) -> Result<String> {
+        self.cli.restore_files(files)
+    }
+
+    fn abort_in_progress(&self, op: InProgress) -> Result<String> {
+        self.cli.abort_in_progress(op)
+    }
+
+    fn continue_in_progress(&self, op: InProgress) -> Result<String> {
+        self.cli.continue_in_progress(op)
+    }
+
+    fn mergetool(&self, path: &str) -> Result<()> {
+        self.cli.mergetool(path)
+    }
+
+    fn editor(&self) -> Result<String> {
+        self.cli.editor()
+    }
+
+    fn git_dir(&self) -> Result<PathBuf> {
+        Ok(self.repo.path().to_path_buf())
+    }
+
+    fn rev_parse(&self, rev: &str) -> Result<String> {
+        Ok(self.commit_of(rev)?.id().to_string())
+    }
+
+    fn update_ref(&self, refname: &str, sha: &str) -> Result<String> {
+        self.cli.update_ref(refname, sha)
+    }
+
+    fn delete_ref(&self, refname: &str) -> Result<String> {
+        self.cli.delete_ref(refname)
+    }
+
+    fn checkout(&self, branch: &str) -> Result<String> {
+        self.cli.checkout(branch)
+    }
+
+    fn checkout_tracking(&self, name: &str, remote_branch: &str) -> Result<String> {
+        self.cli.checkout_tracking(name, remote_branch)
+    }
+
+    fn push_with_lease(&self, remote: &str, branch: &str, new_sha: Option<&str>, expected: Option<&str>) -> Result<String> {
+        self.cli.push_with_lease(remote, branch, new_sha, expected)
+    }
+
+    fn parents(&self, rev: &str) -> Result<Vec<String>> {
+        let commit = self.commit_of(rev)?;
+        Ok(commit.parent_ids().map(|id| id.to_string()).collect())
+    }
+
+    fn remote_refs_containing(&self, sha: &str) -> Result<Vec<String>> {
+        let target = git2::Oid::from_str(sha).map_err(map_err)?;
+        let branches = self.repo.branches(Some(BranchType::Remote)).map_err(map_err)?;
+
+        let mut names = Vec::new();
+        for item in branches {
+            let (branch, _) = item.map_err(map_err)?;
+            let (Ok(Some(name)), Some(tip)) = (branch.name(), branch.get().target()) else {
+                continue;
+            };
+            if name.ends_with("/HEAD") {
+                continue;
+            }
+            if tip == target || self.repo.graph_descendant_of(tip, target).unwrap_or(false) {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+
+        Ok(names)
+    }
+
+    fn revert(&self, sha: &str, mainline: Option<u32>) -> Result<String> {
+        self.cli.revert(sha, mainline)
+    }
+
+    fn stash_push(&self, message: &str, include_untracked: bool) -> Result<String> {
+        self.cli.stash_push(message, include_untracked)
+    }
+
+    fn stash_list(&self) -> Result<Vec<String>> {
+        self.cli.stash_list()
+    }
+
+    fn stash_files(&self, stash: &str) -> Result<String> {
+        self.cli.stash_files(stash)
+    }
+
+    fn stash_pop(&self, stash: &str, index: bool) -> Result<String> {
+        self.cli.stash_pop(stash, index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::fake::ScratchRepo;
+
+    #[test]
+    fn commit_messages_match_the_cli_backend() {
+        let repo = ScratchRepo::new();
+        repo.git(&["commit", "-q", "--allow-empty", "-m", "Fix the typo\n\nCo-authored-by: Bo <bo@example.com>"]);
+
+        let native = NativeBackend::open(Some(&repo.path)).unwrap();
+        let cli = CliBackend::open(Some(&repo.path)).unwrap();
+        assert_eq!(native.commit_message("HEAD").unwrap(), cli.commit_message("HEAD").unwrap());
+    }
+
+    #[test]
+    fn remote_branches_match_the_cli_backend() {
+        let repo = ScratchRepo::new();
+        repo.git(&["update-ref", "refs/remotes/origin/main", "HEAD"]);
+        repo.git(&["symbolic-ref", "refs/remotes/origin/HEAD", "refs/remotes/origin/main"]);
+
+        let native = NativeBackend::open(Some(&repo.path)).unwrap();
+        let cli = CliBackend::open(Some(&repo.path)).unwrap();
+        let names = |lines: Vec<String>| -> Vec<String> {
+            lines.iter().map(|l| l.split('|').next().unwrap().to_string()).collect()
+        };
+        assert_eq!(names(native.list_remote_branches().unwrap()), ["origin/main"]);
+        assert_eq!(names(cli.list_remote_branches().unwrap()), ["origin/main"]);
+    }
+
+    #[test]
+    fn conflict_codes_match_porcelain_v2() {
+        // (ancestor, ours, theirs) still in the index
+        assert_eq!(conflict_xy(true, true, true), "UU");
+        assert_eq!(conflict_xy(false, true, true), "AA");
+        assert_eq!(conflict_xy(true, false, false), "DD");
+        assert_eq!(conflict_xy(false, true, false), "AU");
+        assert_eq!(conflict_xy(false, false, true), "UA");
+        assert_eq!(conflict_xy(true, false, true), "DU");
+        assert_eq!(conflict_xy(true, true, false), "UD");
+    }
+}
@@ -0,0 +1,185 @@
+//! Finding local branches that are safe (or likely) to delete.
+
+use serde::Serialize;
+
+use super::default_branch;
+use super::journal;
+use crate::adapters::GitBackend;
+use crate::error::Result;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Why a branch is suggested for cleanup
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case", tag = "reason")]
+pub enum StaleReason {
+    /// Everything on it is already in the default branch
+    Merged { into: String },
+    /// Its remote branch was deleted (usually after the PR was merged)
+    UpstreamGone { upstream: String },
+    /// No commits for a long time
+    Inactive { days: u64 },
+}
+
+impl StaleReason {
+    pub fn describe(&self) -> String {
+        match self {
+            StaleReason::Merged { into } => format!("merged into {}", into),
+            StaleReason::UpstreamGone { upstream } => format!("{} was deleted", upstream),
+            StaleReason::Inactive { days } => format!("no commits for {} days", days),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CleanupCandidate {
+    pub name: String,
+    pub sha: String,
+    /// Deleting loses no commits
+    pub merged: bool,
+    pub reasons: Vec<StaleReason>,
+}
+
+/// Local branch state needed to judge staleness
+#[derive(Clone, Debug)]
+pub struct LocalBranch {
+    pub name: String,
+    pub sha: String,
+    pub upstream: Option<String>,
+    pub upstream_gone: bool,
+    /// Unix time of the tip commit
+    pub last_commit: u64,
+    pub merged: bool,
+}
+
+/// Every local branch, with whether it is merged into the default branch
+pub fn local_branches<G: GitBackend>(git: &G) -> Result<(String, Vec<LocalBranch>)> {
+    let target = default_branch(git)?;
+    let merged = git.merged_branches(&target).unwrap_or_default();
+
+    let branches = git
+        .list_branch_refs()?
+        .into_iter()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split('|').collect();
+            if parts.len() < 5 {
+                return None;
+            }
+            Some(LocalBranch {
+                name: parts[0].to_string(),
+                sha: parts[1].to_string(),
+                upstream: Some(parts[2].to_string()).filter(|u| !u.is_empty()),
+                upstream_gone: parts[3] == "[gone]",
+                last_commit: parts[4].parse().unwrap_or(0),
+                merged: merged.iter().any(|m| m == parts[0]),
+            })
+        })
+        .collect();
+
+    Ok((target, branches))
+}
+
+/// Branches that look finished or abandoned.
+///
+/// Fetches with `--prune` first so deleted remote branches are noticed.
+/// The checked-out branch and the default branch are never suggested.
+pub fn cleanup_candidates<G: GitBackend>(git: &G, inactive_days: u64) -> Result<Vec<CleanupCandidate>> {
+    // Offline or no remote: still report merged and inactive branches
+    let _ = git.fetch_prune();
+
+    let current = git.current_branch()?;
+    let (target, branches) = local_branches(git)?;
+    let now = journal::now();
+
+    let candidates = branches
+        .into_iter()
+        .filter(|b| b.name != current && b.name != target)
+        .filter_map(|b| {
+            let mut reasons = Vec::new();
+            if b.merged {
+                reasons.push(StaleReason::Merged { into: target.clone() });
+            }
+            if b.upstream_gone {
+                reasons.push(StaleReason::UpstreamGone {
+                    upstream: b.upstream.clone().unwrap_or_default(),
+                });
+            }
+            let days = now.saturating_sub(b.last_commit) / SECONDS_PER_DAY;
+            if b.last_commit > 0 && days >= inactive_days {
+                reasons.push(StaleReason::Inactive { days });
+            }
+
+            (!reasons.is_empty()).then_some(CleanupCandidate {
+                name: b.name,
+                sha: b.sha,
+                merged: b.merged,
+                reasons,
+            })
+        })
+        .collect();
+
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::fake::{fake_commit, FakeBackend};
+    use crate::core::delete_branches;
+    use crate::error::RfxError;
+
+    fn repo() -> FakeBackend {
+        let base = vec![fake_commit("Second"), fake_commit("First")];
+        let mut ahead = base.clone();
+        ahead.insert(0, fake_commit("Work in progress"));
+
+        FakeBackend::new()
+            .with_branch("main", base.clone())
+            .with_branch("done", base[1..].to_vec())
+            .with_branch("wip", ahead)
+            .with_branch("current", base)
+            .on_branch("current")
+    }
+
+    #[test]
+    fn merged_branches_are_suggested() {
+        let git = repo();
+        let candidates = cleanup_candidates(&git, 10_000).unwrap();
+
+        let names: Vec<&str> = candidates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["done"]);
+        assert!(candidates[0].merged);
+        assert_eq!(candidates[0].reasons, [StaleReason::Merged { into: "main".to_string() }]);
+        assert_eq!(candidates[0].reasons[0].describe(), "merged into main");
+    }
+
+    #[test]
+    fn works_offline() {
+        let git = repo().fail_on("fetch_prune", RfxError::Network("offline".to_string()));
+        assert_eq!(cleanup_candidates(&git, 10_000).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn deleted_branches_come_back_with_undo() {
+        let git = repo();
+        let names = ["done".to_string(), "wip".to_string()];
+        let report = delete_branches(&git, &names).unwrap();
+        assert_eq!(report.deleted.len(), 2);
+        assert!(report.failed.is_empty());
+        assert_eq!(git.list_branches().unwrap(), ["main", "current"]);
+
+        journal::undo(&git).unwrap();
+        let mut branches = git.list_branches().unwrap();
+        branches.sort();
+        assert_eq!(branches, ["current", "done", "main", "wip"]);
+        assert_eq!(git.last_commit("wip").unwrap(), "2024-01-01|Work in progress");
+    }
+
+    #[test]
+    fn the_checked_out_branch_is_never_deleted() {
+        let git = repo();
+        let names = ["current".to_string()];
+        assert!(delete_branches(&git, &names).is_err());
+        assert!(!git.calls().contains(&"delete_branch".to_string()));
+    }
+}
@@ -0,0 +1,674 @@
+//! rfx settings, read from two TOML files:
+//!
+//! * `.rfx.toml` at the top of the repository, shared with the team
+//! * `~/.config/rfx/config.toml` (or `$XDG_CONFIG_HOME/rfx/config.toml`),
+//!   personal defaults for every repository
+//!
+//! The repository file wins over the user file. `rfx.pullStrategy` and
+//! `rfx.protectedBranches` from git config are still honored below both,
+//! and every setting has a built-in default.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::adapters::GitBackend;
+use crate::error::{Result, RfxError};
+
+/// Name of the repository config file
+pub const REPO_FILE: &str = ".rfx.toml";
+
+/// What values a setting takes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    Number,
+    Flag,
+    /// Strings, written as a TOML array or comma-separated on the command line
+    List,
+    /// One of a fixed set of words
+    Choice(&'static [&'static str]),
+    /// A regular expression
+    Pattern,
+}
+
+/// A known setting
+#[derive(Clone, Copy, Debug)]
+pub struct Setting {
+    /// `section.name`
+    pub key: &'static str,
+    pub kind: Kind,
+    /// Used when no file sets it (empty: no default)
+    pub default: &'static str,
+    pub about: &'static str,
+    /// Older git config key read when no file sets it
+    legacy: Option<&'static str>,
+}
+
+pub const SETTINGS: [Setting; 18] = [
+    Setting {
+        key: "remote.default",
+        kind: Kind::Text,
+        default: "",
+        about: "Remote used when none is given (default: origin, or the only remote)",
+        legacy: None,
+    },
+    Setting {
+        key: "remote.upstream",
+        kind: Kind::Text,
+        default: "upstream",
+        about: "Remote 'rfx sync' updates the fork from",
+        legacy: None,
+    },
+    Setting {
+        key: "branch.default",
+        kind: Kind::Text,
+        default: "",
+        about: "The branch everything is merged into (default: detected)",
+        legacy: None,
+    },
+    Setting {
+        key: "branch.protected",
+        kind: Kind::List,
+        default: "main, master, release/*",
+        about: "Branches rfx never force-pushes (* matches anything)",
+        legacy: Some("rfx.protectedBranches"),
+    },
+    Setting {
+        key: "branch.naming",
+        kind: Kind::Pattern,
+        default: "",
+        about: "Pattern new branch names must match, e.g. ^(feat|fix)/[a-z0-9-]+$",
+        legacy: None,
+    },
+    Setting {
+        key: "pull.strategy",
+        kind: Kind::Choice(&["ff-only", "merge", "rebase"]),
+        default: "",
+        about: "How 'rfx pull' combines diverged branches (default: ask)",
+        legacy: Some("rfx.pullStrategy"),
+    },
+    Setting {
+        key: "commit.convention",
+        kind: Kind::Choice(&["none", "conventional"]),
+        default: "none",
+        about: "Commit message convention to check and build messages for",
+        legacy: None,
+    },
+    Setting {
+        key: "commit.min_length",
+        kind: Kind::Number,
+        default: "3",
+        about: "Shortest allowed first line",
+        legacy: None,
+    },
+    Setting {
+        key: "commit.subject_length",
+        kind: Kind::Number,
+        default: "72",
+        about: "Longest allowed first line with the convention on (0: no limit)",
+        legacy: None,
+    },
+    Setting {
+        key: "commit.body_wrap",
+        kind: Kind::Number,
+        default: "72",
+        about: "Column the description is wrapped at with the convention on (0: no limit)",
+        legacy: None,
+    },
+    Setting {
+        key: "commit.types",
+        kind: Kind::List,
+        default: "feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert",
+        about: "Allowed Conventional Commits types",
+        legacy: None,
+    },
+    Setting {
+        key: "commit.scopes",
+        kind: Kind::List,
+        default: "",
+        about: "Allowed scopes (default: any)",
+        legacy: None,
+    },
+    Setting {
+        key: "commit.require_scope",
+        kind: Kind::Flag,
+        default: "false",
+        about: "Every Conventional Commits message must name a scope",
+        legacy: None,
+    },
+    Setting {
+        key: "commit.trailers",
+        kind: Kind::List,
+        default: "",
+        about: "Trailers every message needs, e.g. Signed-off-by",
+        legacy: None,
+    },
+    Setting {
+        key: "output.color",
+        kind: Kind::Choice(&["auto", "always", "never"]),
+        default: "auto",
+        about: "Colored diffs (auto: only in a terminal, unless NO_COLOR is set)",
+        legacy: None,
+    },
+    Setting {
+        key: "output.name_width",
+        kind: Kind::Number,
+        default: "10",
+        about: "Branch names longer than this are shortened in tables",
+        legacy: None,
+    },
+    Setting {
+        key: "output.author_width",
+        kind: Kind::Number,
+        default: "15",
+        about: "Author names longer than this are shortened in tables",
+        legacy: None,
+    },
+    Setting {
+        key: "output.message_width",
+        kind: Kind::Number,
+        default: "30",
+        about: "Commit messages longer than this are shortened in tables",
+        legacy: None,
+    },
+];
+
+/// Look up a known setting by key
+pub fn setting(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|s| s.key == key)
+}
+
+/// A setting's value
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum ConfigValue {
+    Text(String),
+    Number(usize),
+    Flag(bool),
+    List(Vec<String>),
+}
+
+impl fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigValue::Text(s) => write!(f, "{}", s),
+            ConfigValue::Number(n) => write!(f, "{}", n),
+            ConfigValue::Flag(b) => write!(f, "{}", b),
+            ConfigValue::List(items) => write!(f, "{}", items.join(", ")),
+        }
+    }
+}
+
+/// Where a value came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Source {
+    Repo,
+    User,
+    GitConfig,
+    Default,
+}
+
+impl Source {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Source::Repo => "repository (.rfx.toml)",
+            Source::User => "user config",
+            Source::GitConfig => "git config",
+            Source::Default => "default",
+        }
+    }
+}
+
+/// One row of `rfx config list`
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: Option<ConfigValue>,
+    pub source: Source,
+    pub about: String,
+}
+
+struct Layer {
+    source: Source,
+    values: BTreeMap<String, ConfigValue>,
+}
+
+/// All settings, layered
+pub struct Config {
+    /// Highest precedence first
+    layers: Vec<Layer>,
+}
+
+/// Parse a value typed on the command line (or found in git config)
+pub fn parse_value(setting: &Setting, raw: &str) -> std::result::Result<ConfigValue, String> {
+    let raw = raw.trim();
+    match setting.kind {
+        Kind::Text => Ok(ConfigValue::Text(raw.to_string())),
+        Kind::Number => raw
+            .parse()
+            .map(ConfigValue::Number)
+            .map_err(|_| format!("must be a number, not '{}'", raw)),
+        Kind::Flag => match raw {
+            "true" | "yes" | "on" | "1" => Ok(ConfigValue::Flag(true)),
+            "false" | "no" | "off" | "0" => Ok(ConfigValue::Flag(false)),
+            _ => Err(format!("must be true or false, not '{}'", raw)),
+        },
+        Kind::List => Ok(ConfigValue::List(
+            raw.split([',', ' '])
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+                .collect(),
+        )),
+        Kind::Pattern => regex::Regex::new(raw)
+            .map(|_| ConfigValue::Text(raw.to_string()))
+            .map_err(|e| format!("is not a valid pattern: {}", e)),
+        Kind::Choice(options) if options.contains(&raw) => Ok(ConfigValue::Text(raw.to_string())),
+        Kind::Choice(options) => Err(format!("must be one of {}, not '{}'", options.join(", "), raw)),
+    }
+}
+
+fn from_toml(setting: &Setting, value: &toml_edit::Value) -> std::result::Result<ConfigValue, String> {
+    match (setting.kind, value) {
+        (Kind::Number, toml_edit::Value::Integer(n)) => usize::try_from(*n.value())
+            .map(ConfigValue::Number)
+            .map_err(|_| "must not be negative".to_string()),
+        (Kind::Flag, toml_edit::Value::Boolean(b)) => Ok(ConfigValue::Flag(*b.value())),
+        (Kind::List, toml_edit::Value::Array(items)) => items
+            .iter()
+            .map(|v| v.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .map(ConfigValue::List)
+            .ok_or_else(|| "must be a list of strings".to_string()),
+        (_, toml_edit::Value::String(s)) if !matches!(setting.kind, Kind::Number | Kind::Flag) => {
+            parse_value(setting, s.value())
+        }
+        (Kind::Number, _) => Err("must be a number".to_string()),
+        (Kind::Flag, _) => Err("must be true or false".to_string()),
+        _ => Err("must be a string".to_string()),
+    }
+}
+
+fn to_toml(value: &ConfigValue) -> toml_edit::Value {
+    match value {
+        ConfigValue::Text(s) => s.as_str().into(),
+        ConfigValue::Number(n) => (*n as i64).into(),
+        ConfigValue::Flag(b) => (*b).into(),
+        ConfigValue::List(items) => items.iter().map(String::as_str).collect::<toml_edit::Array>().into(),
+    }
+}
+
+/// `~/.config/rfx/config.toml`, honoring `$RFX_CONFIG_HOME` and then `$XDG_CONFIG_HOME`
+pub fn user_config_path() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("RFX_CONFIG_HOME")
+        && !dir.is_empty()
+    {
+        return Some(PathBuf::from(dir).join("config.toml"));
+    }
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("rfx").join("config.toml"))
+}
+
+/// `.rfx.toml` at the top of the working tree
+pub fn repo_config_path<G: GitBackend>(git: &G) -> Result<PathBuf> {
+    Ok(git.work_tree()?.join(REPO_FILE))
+}
+
+fn read_document(path: &Path) -> Result<Option<DocumentMut>> {
+    let text = match fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(RfxError::Io(format!("Could not read {}: {}", path.display(), e))),
+    };
+    text.parse::<DocumentMut>()
+        .map(Some)
+        .map_err(|e| RfxError::Validation(format!("{} is not valid TOML:\n{}", path.display(), e)))
+}
+
+/// Read one file, rejecting unknown settings and values of the wrong type
+fn read_layer(path: &Path, source: Source) -> Result<Option<Layer>> {
+    let Some(doc) = read_document(path)? else {
+        return Ok(None);
+    };
+
+    let mut values = BTreeMap::new();
+    for (section, item) in doc.iter() {
+        let Some(table) = item.as_table_like() else {
+            return Err(RfxError::Validation(format!(
+                "{}: '{}' must be a [section]. Run 'rfx config list' to see the settings.",
+                path.display(),
+                section
+            )));
+        };
+        for (name, item) in table.iter() {
+            let key = format!("{}.{}", section, name);
+            let Some(setting) = setting(&key) else {
+                return Err(RfxError::Validation(format!(
+                    "{}: unknown setting '{}'. Run 'rfx config list' to see the settings.",
+                    path.display(),
+                    key
+                )));
+            };
+            let value = item
+                .as_value()
+                .ok_or_else(|| "must be a value".to_string())
+                .and_then(|v| from_toml(setting, v))
+                .map_err(|e| RfxError::Validation(format!("{}: '{}' {}.", path.display(), key, e)))?;
+            values.insert(key, value);
+        }
+    }
+
+    Ok(Some(Layer { source, values }))
+}
+
+/// Load the settings for this repository
+pub fn load<G: GitBackend>(git: &G) -> Result<Config> {
+    load_from(git, user_config_path().as_deref())
+}
+
+/// Load the settings with `user` as the user file, or without one when `None`
+pub fn load_from<G: GitBackend>(git: &G, user: Option<&Path>) -> Result<Config> {
+    let mut layers = Vec::new();
+
+    if let Some(layer) = read_layer(&repo_config_path(git)?, Source::Repo)? {
+        layers.push(layer);
+    }
+    if let Some(path) = user
+        && let Some(layer) = read_layer(path, Source::User)?
+    {
+        layers.push(layer);
+    }
+
+    let mut legacy = BTreeMap::new();
+    for setting in SETTINGS.iter() {
+        if let Some(key) = setting.legacy
+            && let Some(mut raw) = git.config_get(key)?
+        {
+            // rfx.pullStrategy also took `ff`, `fast-forward` and any case
+            if setting.key == "pull.strategy"
+                && let Some(strategy) = super::parse_pull_strategy(&raw)
+            {
+                raw = super::pull_strategy_name(strategy).to_string();
+            }
+            let value = parse_value(setting, &raw)
+                .map_err(|e| RfxError::Validation(format!("{} in git config {}.", key, e)))?;
+            legacy.insert(setting.key.to_string(), value);
+        }
+    }
+    layers.push(Layer {
+        source: Source::GitConfig,
+        values: legacy,
+    });
+
+    Ok(Config { layers })
+}
+
+impl Config {
+    /// Built-in defaults only, for when the files cannot be read
+    pub fn defaults() -> Config {
+        Config { layers: Vec::new() }
+    }
+
+    /// A setting's value and where it came from, falling back to its default
+    pub fn get(&self, key: &str) -> Option<(ConfigValue, Source)> {
+        if let Some((value, source)) = self
+            .layers
+            .iter()
+            .find_map(|l| l.values.get(key).map(|v| (v.clone(), l.source)))
+        {
+            return Some((value, source));
+        }
+
+        let setting = setting(key)?;
+        if setting.default.is_empty() {
+            return None;
+        }
+        parse_value(setting, setting.default).ok().map(|v| (v, Source::Default))
+    }
+
+    pub fn text(&self, key: &str) -> Option<String> {
+        match self.get(key)? {
+            (ConfigValue::Text(s), _) if !s.is_empty() => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn number(&self, key: &str) -> Option<usize> {
+        match self.get(key)? {
+            (ConfigValue::Number(n), _) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn flag(&self, key: &str) -> bool {
+        matches!(self.get(key), Some((ConfigValue::Flag(true), _)))
+    }
+
+    pub fn list(&self, key: &str) -> Vec<String> {
+        match self.get(key) {
+            Some((ConfigValue::List(items), _)) => items,
+            _ => Vec::new(),
+        }
+    }
+
+    /// Every known setting with its current value
+    pub fn entries(&self) -> Vec<ConfigEntry> {
+        SETTINGS
+            .iter()
+            .map(|s| {
+                let found = self.get(s.key);
+                ConfigEntry {
+                    key: s.key.to_string(),
+                    source: found.as_ref().map_or(Source::Default, |(_, source)| *source),
+                    value: found.map(|(v, _)| v),
+                    about: s.about.to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Write a setting to the repository file, or the user file when `global`.
+/// Returns the file that was changed.
+pub fn set_value<G: GitBackend>(git: &G, key: &str, raw: &str, global: bool) -> Result<PathBuf> {
+    let user = if global {
+        Some(user_config_path().ok_or_else(|| {
+            RfxError::Validation("Could not find your home directory for the user config.".to_string())
+        })?)
+    } else {
+        None
+    };
+    set_value_in(git, key, raw, user.as_deref())
+}
+
+/// Write a setting to the repository file, or to the `user` file when given
+pub fn set_value_in<G: GitBackend>(git: &G, key: &str, raw: &str, user: Option<&Path>) -> Result<PathBuf> {
+    let Some(setting) = setting(key) else {
+        return Err(RfxError::Validation(format!(
+            "Unknown setting '{}'. Run 'rfx config list' to see the settings.",
+            key
+        )));
+    };
+    let value = parse_value(setting, raw).map_err(|e| RfxError::Validation(format!("'{}' {}.", key, e)))?;
+
+    let path = match user {
+        Some(path) => path.to_path_buf(),
+        None => repo_config_path(git)?,
+    };
+
+    let mut doc = read_document(&path)?.unwrap_or_default();
+    let (section, name) = key.split_once('.').unwrap_or((key, ""));
+    if !doc.contains_table(section) {
+        doc.insert(section, Item::Table(Table::new()));
+    }
+    let Some(table) = doc[section].as_table_like_mut() else {
+        return Err(RfxError::Validation(format!("{}: '{}' must be a [section].", path.display(), section)));
+    };
+    table.insert(name, Item::Value(to_toml(&value)));
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, doc.to_string())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::fake::FakeBackend;
+    use crate::adapters::PullStrategy;
+    use crate::core::configured_pull_strategy;
+
+    fn repo_file(git: &FakeBackend, toml: &str) {
+        let root = git.work_tree().unwrap();
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(REPO_FILE), toml).unwrap();
+    }
+
+    /// A user file kept in the fake's scratch directory
+    fn user_file(git: &FakeBackend, toml: &str) -> PathBuf {
+        let path = git.git_dir().unwrap().join("user").join("config.toml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, toml).unwrap();
+        path
+    }
+
+    fn list(items: &[&str]) -> ConfigValue {
+        ConfigValue::List(items.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn the_repository_file_wins_over_git_config_and_defaults() {
+        let git = FakeBackend::new().with_config("rfx.protectedBranches", "main develop");
+        let config = load_from(&git, None).unwrap();
+        assert_eq!(config.get("branch.protected"), Some((list(&["main", "develop"]), Source::GitConfig)));
+        assert_eq!(config.get("remote.upstream"), Some((ConfigValue::Text("upstream".to_string()), Source::Default)));
+        assert_eq!(config.get("branch.default"), None);
+
+        repo_file(&git, "[branch]\nprotected = [\"trunk\"]\n\n[commit]\nmin_length = 10\n");
+        let config = load_from(&git, None).unwrap();
+        assert_eq!(config.get("branch.protected"), Some((list(&["trunk"]), Source::Repo)));
+        assert_eq!(config.number("commit.min_length"), Some(10));
+
+        let entry = config.entries().into_iter().find(|e| e.key == "commit.convention").unwrap();
+        assert_eq!((entry.value, entry.source), (Some(ConfigValue::Text("none".to_string())), Source::Default));
+    }
+
+    #[test]
+    fn legacy_pull_strategies_keep_working() {
+        for (raw, expected) in [
+            ("ff", PullStrategy::FastForwardOnly),
+            ("Fast-Forward", PullStrategy::FastForwardOnly),
+            ("REBASE", PullStrategy::Rebase),
+            ("merge", PullStrategy::Merge),
+        ] {
+            let git = FakeBackend::new().with_config("rfx.pullStrategy", raw);
+            assert_eq!(configured_pull_strategy(&git).unwrap(), Some(expected), "{}", raw);
+        }
+
+        let git = FakeBackend::new().with_config("rfx.pullStrategy", "squash");
+        assert!(matches!(configured_pull_strategy(&git), Err(RfxError::Validation(_))));
+    }
+
+    #[test]
+    fn files_are_checked_for_unknown_settings_and_types() {
+        let git = FakeBackend::new();
+        for toml in [
+            "[branch]\nprotect = [\"main\"]\n",
+            "[commit]\nmin_length = \"ten\"\n",
+            "[pull]\nstrategy = \"ff\"\n",
+            "strategy = \"merge\"\n",
+            "[pull\n",
+        ] {
+            repo_file(&git, toml);
+            assert!(matches!(load_from(&git, None), Err(RfxError::Validation(_))), "{}", toml);
+        }
+    }
+
+    #[test]
+    fn values_are_parsed_by_kind() {
+        let kind = |key| setting(key).unwrap();
+        assert_eq!(parse_value(kind("branch.protected"), "main, release/* hotfix"), Ok(list(&["main", "release/*", "hotfix"])));
+        assert_eq!(parse_value(kind("commit.min_length"), " 5 "), Ok(ConfigValue::Number(5)));
+        assert!(parse_value(kind("commit.min_length"), "-1").is_err());
+        assert_eq!(parse_value(kind("commit.require_scope"), "yes"), Ok(ConfigValue::Flag(true)));
+        assert!(parse_value(kind("commit.require_scope"), "maybe").is_err());
+        assert!(parse_value(kind("branch.naming"), "^(feat|fix/").is_err());
+        assert!(parse_value(kind("pull.strategy"), "rebase").is_ok());
+        assert!(parse_value(kind("pull.strategy"), "ff").is_err());
+    }
+
+    #[test]
+    fn setting_a_value_keeps_the_rest_of_the_file() {
+        let git = FakeBackend::new();
+        repo_file(&git, "# Shared with the team\n[branch]\ndefault = \"develop\" # not main\n");
+
+        let path = set_value(&git, "branch.protected", "develop, release/*", false).unwrap();
+        set_value(&git, "pull.strategy", "rebase", false).unwrap();
+
+        let text = fs::read_to_string(path).unwrap();
+        assert!(text.starts_with("# Shared with the team\n[branch]\ndefault = \"develop\" # not main\n"));
+        assert!(text.contains("protected = [\"develop\", \"release/*\"]"));
+        assert!(text.contains("[pull]\nstrategy = \"rebase\""));
+        assert_eq!(load_from(&git, None).unwrap().text("pull.strategy").as_deref(), Some("rebase"));
+    }
+
+    #[test]
+    fn unknown_settings_and_bad_values_are_not_written() {
+        let git = FakeBackend::new();
+        assert!(matches!(set_value(&git, "pull.mode", "rebase", false), Err(RfxError::Validation(_))));
+        assert!(matches!(set_value(&git, "commit.min_length", "few", false), Err(RfxError::Validation(_))));
+        let user = git.git_dir().unwrap().join("user").join("config.toml");
+        assert!(matches!(set_value_in(&git, "pull.strategy", "squash", Some(&user)), Err(RfxError::Validation(_))));
+        assert!(!git.work_tree().unwrap().join(REPO_FILE).exists());
+        assert!(!user.exists());
+    }
+
+    #[test]
+    fn the_user_file_sits_between_the_repository_and_git_config() {
+        let git = FakeBackend::new()
+            .with_config("rfx.protectedBranches", "main develop")
+            .with_config("rfx.pullStrategy", "merge");
+        let user = user_file(&git, "[branch]\nprotected = [\"main\"]\n\n[commit]\nmin_length = 8\n");
+
+        let config = load_from(&git, Some(&user)).unwrap();
+        assert_eq!(config.get("branch.protected"), Some((list(&["main"]), Source::User)));
+        assert_eq!(config.get("commit.min_length"), Some((ConfigValue::Number(8), Source::User)));
+        assert_eq!(config.get("pull.strategy"), Some((ConfigValue::Text("merge".to_string()), Source::GitConfig)));
+
+        repo_file(&git, "[commit]\nmin_length = 12\n");
+        let config = load_from(&git, Some(&user)).unwrap();
+        assert_eq!(config.get("commit.min_length"), Some((ConfigValue::Number(12), Source::Repo)));
+        assert_eq!(config.get("branch.protected"), Some((list(&["main"]), Source::User)));
+
+        // A missing user file is no error
+        let gone = user.with_file_name("missing.toml");
+        assert_eq!(load_from(&git, Some(&gone)).unwrap().get("branch.protected").map(|(_, s)| s), Some(Source::GitConfig));
+    }
+
+    #[test]
+    fn global_values_go_to_the_user_file() {
+        let git = FakeBackend::new();
+        repo_file(&git, "[branch]\ndefault = \"develop\"\n");
+        let user = git.git_dir().unwrap().join("user").join("config.toml");
+
+        let path = set_value_in(&git, "pull.strategy", "rebase", Some(&user)).unwrap();
+        assert_eq!(path, user);
+        assert_eq!(fs::read_to_string(&user).unwrap(), "[pull]\nstrategy = \"rebase\"\n");
+        assert_eq!(fs::read_to_string(git.work_tree().unwrap().join(REPO_FILE)).unwrap(), "[branch]\ndefault = \"develop\"\n");
+
+        let config = load_from(&git, Some(&user)).unwrap();
+        assert_eq!(config.get("pull.strategy"), Some((ConfigValue::Text("rebase".to_string()), Source::User)));
+        assert_eq!(config.get("branch.default"), Some((ConfigValue::Text("develop".to_string()), Source::Repo)));
+    }
+}
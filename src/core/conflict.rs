@@ -0,0 +1,403 @@
+//! Conflict resolution: reading conflict markers and writing back the result.
+//!
+//! Git leaves conflicting hunks in the working file between `<<<<<<<`,
+//! `|||||||` (diff3 only), `=======` and `>>>>>>>` lines. rfx splits a file
+//! into plain text and conflict hunks, lets each hunk be decided on its own,
+//! and only marks the file resolved once no markers are left.
+
+use std::fs;
+use std::path::PathBuf;
+
+use super::{get_changed_files, ConflictKind, FileChange};
+use crate::adapters::GitBackend;
+use crate::error::{Result, RfxError};
+
+/// One conflicting region of a file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConflictHunk {
+    /// 1-based line of the `<<<<<<<` marker
+    pub line: usize,
+    /// Label after `<<<<<<<` (usually `HEAD`)
+    pub ours_label: String,
+    /// Label after `>>>>>>>` (the commit or branch being merged in)
+    pub theirs_label: String,
+    pub ours: String,
+    /// Common ancestor, only present with `merge.conflictStyle=diff3`
+    pub base: Option<String>,
+    pub theirs: String,
+}
+
+/// A piece of a conflicted file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Segment {
+    /// Lines both sides agree on
+    Text(String),
+    Conflict(ConflictHunk),
+}
+
+/// How to settle one hunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    /// Our lines followed by theirs
+    Both,
+}
+
+/// A conflicted file split into text and hunks
+#[derive(Clone, Debug)]
+pub struct ConflictFile {
+    pub path: String,
+    pub segments: Vec<Segment>,
+}
+
+impl ConflictFile {
+    pub fn hunks(&self) -> impl Iterator<Item = &ConflictHunk> {
+        self.segments.iter().filter_map(|s| match s {
+            Segment::Conflict(h) => Some(h),
+            Segment::Text(_) => None,
+        })
+    }
+
+    pub fn hunk_count(&self) -> usize {
+        self.hunks().count()
+    }
+
+    /// The file contents with every hunk replaced according to `choices`
+    /// (one per hunk, in order)
+    pub fn render(&self, choices: &[Resolution]) -> String {
+        let mut out = String::new();
+        let mut choices = choices.iter();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => out.push_str(text),
+                Segment::Conflict(hunk) => match choices.next() {
+                    Some(Resolution::Ours) => out.push_str(&hunk.ours),
+                    Some(Resolution::Theirs) => out.push_str(&hunk.theirs),
+                    Some(Resolution::Both) => {
+                        out.push_str(&hunk.ours);
+                        out.push_str(&hunk.theirs);
+                    }
+                    // Undecided hunks keep their markers
+                    None => out.push_str(&render_markers(hunk)),
+                },
+            }
+        }
+
+        out
+    }
+}
+
+#[derive(PartialEq)]
+enum Part {
+    Text,
+    Ours,
+    Base,
+    Theirs,
+}
+
+/// `<<<<<<< HEAD` -> Some("HEAD"); `=======` -> Some("")
+fn marker(line: &str, symbol: char) -> Option<&str> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let rest = line.strip_prefix(&symbol.to_string().repeat(7))?;
+
+    if rest.is_empty() {
+        Some("")
+    } else {
+        rest.strip_prefix(' ')
+    }
+}
+
+/// Split file contents at its conflict markers.
+///
+/// An unterminated hunk is treated as plain text, so nothing is dropped.
+pub fn parse_conflicts(path: &str, content: &str) -> ConflictFile {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut part = Part::Text;
+    let mut hunk = ConflictHunk {
+        line: 0,
+        ours_label: String::new(),
+        theirs_label: String::new(),
+        ours: String::new(),
+        base: None,
+        theirs: String::new(),
+    };
+    // Raw lines of the current hunk, in case it never closes
+    let mut raw = String::new();
+
+    for (i, line) in content.split_inclusive('\n').enumerate() {
+        match part {
+            Part::Text => {
+                if let Some(label) = marker(line, '<') {
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    hunk = ConflictHunk {
+                        line: i + 1,
+                        ours_label: label.to_string(),
+                        theirs_label: String::new(),
+                        ours: String::new(),
+                        base: None,
+                        theirs: String::new(),
+                    };
+                    raw = line.to_string();
+                    part = Part::Ours;
+                } else {
+                    text.push_str(line);
+                }
+            }
+            Part::Ours | Part::Base => {
+                raw.push_str(line);
+                if marker(line, '|').is_some() && part == Part::Ours {
+                    hunk.base = Some(String::new());
+                    part = Part::Base;
+                } else if marker(line, '=') == Some("") {
+                    part = Part::Theirs;
+                } else if part == Part::Ours {
+                    hunk.ours.push_str(line);
+                } else if let Some(base) = hunk.base.as_mut() {
+                    base.push_str(line);
+                }
+            }
+            Part::Theirs => {
+                raw.push_str(line);
+                if let Some(label) = marker(line, '>') {
+                    hunk.theirs_label = label.to_string();
+                    segments.push(Segment::Conflict(hunk.clone()));
+                    raw.clear();
+                    part = Part::Text;
+                } else {
+                    hunk.theirs.push_str(line);
+                }
+            }
+        }
+    }
+
+    text.push_str(&raw);
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+
+    ConflictFile {
+        path: path.to_string(),
+        segments,
+    }
+}
+
+fn render_markers(hunk: &ConflictHunk) -> String {
+    let mut out = format!("<<<<<<< {}\n{}", hunk.ours_label, hunk.ours);
+    if let Some(base) = &hunk.base {
+        out.push_str(&format!("||||||| base\n{}", base));
+    }
+    out.push_str(&format!("=======\n{}>>>>>>> {}\n", hunk.theirs, hunk.theirs_label));
+    out
+}
+
+/// Whether the text still contains conflict markers
+pub fn has_markers(content: &str) -> bool {
+    content
+        .lines()
+        .any(|line| marker(line, '<').is_some() || marker(line, '>').is_some())
+}
+
+/// Files git still reports as conflicted
+pub fn conflicted_files<G: GitBackend>(git: &G) -> Result<Vec<FileChange>> {
+    Ok(get_changed_files(git)?
+        .into_iter()
+        .filter(|f| f.conflict.is_some())
+        .collect())
+}
+
+fn file_path<G: GitBackend>(git: &G, path: &str) -> Result<PathBuf> {
+    Ok(git.work_tree()?.join(path))
+}
+
+/// Read a conflicted file from the working directory.
+///
+/// Returns `None` when the file is gone (deleted on one side) and an error
+/// for files that are not text.
+pub fn load_conflicts<G: GitBackend>(git: &G, path: &str) -> Result<Option<ConflictFile>> {
+    let full = file_path(git, path)?;
+    if !full.exists() {
+        return Ok(None);
+    }
+
+    let bytes = fs::read(&full).map_err(|e| RfxError::Io(format!("Could not read '{}': {}", path, e)))?;
+    let content = String::from_utf8(bytes).map_err(|_| {
+        RfxError::Validation(format!("'{}' is not a text file. Use a merge tool for it, or keep it as it is.", path))
+    })?;
+
+    Ok(Some(parse_conflicts(path, &content)))
+}
+
+/// Write the resolved contents of a file and mark it resolved
+pub fn resolve_file<G: GitBackend>(git: &G, file: &ConflictFile, choices: &[Resolution]) -> Result<()> {
+    if choices.len() < file.hunk_count() {
+        return Err(RfxError::Validation(format!(
+            "'{}' still has {} undecided conflict(s).",
+            file.path,
+            file.hunk_count() - choices.len()
+        )));
+    }
+
+    let full = file_path(git, &file.path)?;
+    fs::write(&full, file.render(choices))
+        .map_err(|e| RfxError::Io(format!("Could not write '{}': {}", file.path, e)))?;
+
+    mark_resolved(git, &file.path)
+}
+
+/// Mark a file resolved after checking that no conflict markers are left.
+///
+/// A file that no longer exists is resolved as deleted.
+pub fn mark_resolved<G: GitBackend>(git: &G, path: &str) -> Result<()> {
+    let full = file_path(git, path)?;
+    if !full.exists() {
+        git.remove(&[path.to_string()])?;
+        return Ok(());
+    }
+
+    // Binary files cannot contain markers; trust whoever produced them
+    if let Ok(content) = fs::read_to_string(&full)
+        && has_markers(&content)
+    {
+        return Err(RfxError::Validation(format!("'{}' still contains conflict markers.", path)));
+    }
+
+    git.add(&[path.to_string()])?;
+    Ok(())
+}
+
+/// Settle a conflict where one side deleted the file
+pub fn keep_or_delete<G: GitBackend>(git: &G, path: &str, keep: bool) -> Result<()> {
+    if keep && file_path(git, path)?.exists() {
+        git.add(&[path.to_string()])?;
+    } else {
+        git.remove(&[path.to_string()])?;
+    }
+    Ok(())
+}
+
+/// Whether this kind of conflict is about a file existing at all, rather
+/// than about its contents
+pub fn is_delete_conflict(kind: ConflictKind) -> bool {
+    matches!(
+        kind,
+        ConflictKind::DeletedByUs | ConflictKind::DeletedByThem | ConflictKind::BothDeleted
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::fake::FakeBackend;
+
+    const MERGED: &str = "\
+fn main() {
+<<<<<<< HEAD
+    println!(\"ours\");
+=======
+    println!(\"theirs\");
+>>>>>>> feature
+}
+<<<<<<< HEAD
+// ours
+||||||| base
+// base
+=======
+// theirs
+>>>>>>> feature
+";
+
+    #[test]
+    fn splits_a_file_into_text_and_hunks() {
+        let file = parse_conflicts("main.rs", MERGED);
+        assert_eq!(file.hunk_count(), 2);
+        assert_eq!(file.segments[0], Segment::Text("fn main() {\n".to_string()));
+
+        let hunks: Vec<&ConflictHunk> = file.hunks().collect();
+        assert_eq!(hunks[0].line, 2);
+        assert_eq!((hunks[0].ours_label.as_str(), hunks[0].theirs_label.as_str()), ("HEAD", "feature"));
+        assert_eq!(hunks[0].ours, "    println!(\"ours\");\n");
+        assert_eq!(hunks[0].base, None);
+        assert_eq!(hunks[1].base.as_deref(), Some("// base\n"));
+        assert_eq!(hunks[1].theirs, "// theirs\n");
+    }
+
+    #[test]
+    fn renders_each_hunk_as_chosen() {
+        let file = parse_conflicts("main.rs", MERGED);
+        assert_eq!(
+            file.render(&[Resolution::Theirs, Resolution::Both]),
+            "fn main() {\n    println!(\"theirs\");\n}\n// ours\n// theirs\n"
+        );
+
+        // Undecided hunks keep their markers
+        let partial = file.render(&[Resolution::Ours]);
+        assert!(partial.starts_with("fn main() {\n    println!(\"ours\");\n}\n<<<<<<< HEAD\n"));
+        assert!(has_markers(&partial));
+        assert!(!has_markers(&file.render(&[Resolution::Ours, Resolution::Ours])));
+    }
+
+    #[test]
+    fn unterminated_hunks_stay_text() {
+        let content = "a\n<<<<<<< HEAD\nb\n=======\nc\n";
+        let file = parse_conflicts("x", content);
+        assert_eq!(file.hunk_count(), 0);
+        assert_eq!(file.render(&[]), content);
+    }
+
+    #[test]
+    fn markers_need_seven_characters() {
+        assert!(!has_markers("<<<<<< six\n====\n>>>>>> six\n"));
+        assert!(has_markers(">>>>>>> theirs\n"));
+    }
+
+    fn work_file(git: &FakeBackend, path: &str, content: &str) {
+        let full = git.work_tree().unwrap().join(path);
+        fs::create_dir_all(full.parent().unwrap()).unwrap();
+        fs::write(full, content).unwrap();
+    }
+
+    #[test]
+    fn resolving_writes_the_file_and_stages_it() {
+        let git = FakeBackend::new().with_change("UU", "main.rs");
+        work_file(&git, "main.rs", MERGED);
+
+        let file = load_conflicts(&git, "main.rs").unwrap().unwrap();
+        assert!(resolve_file(&git, &file, &[Resolution::Ours]).is_err());
+        resolve_file(&git, &file, &[Resolution::Ours, Resolution::Theirs]).unwrap();
+
+        let written = fs::read_to_string(git.work_tree().unwrap().join("main.rs")).unwrap();
+        assert_eq!(written, "fn main() {\n    println!(\"ours\");\n}\n// theirs\n");
+        assert!(git.calls().contains(&"add".to_string()));
+    }
+
+    #[test]
+    fn files_with_markers_are_not_marked_resolved() {
+        let git = FakeBackend::new().with_change("UU", "main.rs");
+        work_file(&git, "main.rs", MERGED);
+        assert!(matches!(mark_resolved(&git, "main.rs"), Err(RfxError::Validation(_))));
+        assert!(!git.calls().contains(&"add".to_string()));
+    }
+
+    #[test]
+    fn deleted_files_are_resolved_by_removing_them() {
+        let git = FakeBackend::new().with_change("DU", "gone.rs");
+        assert!(load_conflicts(&git, "gone.rs").unwrap().is_none());
+        keep_or_delete(&git, "gone.rs", true).unwrap();
+        assert!(git.calls().contains(&"remove".to_string()));
+        assert!(conflicted_files(&git).unwrap().is_empty());
+    }
+
+    #[test]
+    fn delete_conflicts_are_about_existence() {
+        assert!(is_delete_conflict(ConflictKind::DeletedByUs));
+        assert!(is_delete_conflict(ConflictKind::DeletedByThem));
+        assert!(is_delete_conflict(ConflictKind::BothDeleted));
+        assert!(!is_delete_conflict(ConflictKind::BothModified));
+        assert!(!is_delete_conflict(ConflictKind::AddedByThem));
+    }
+}
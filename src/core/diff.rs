@@ -0,0 +1,559 @@
+//! Unified diffs: parsing `git diff` output into files and hunks, lining
+//! them up for display, and building patches from a subset of hunks for
+//! partial staging.
+
+use std::fs;
+
+use serde::Serialize;
+
+use super::{current_commit, FileChange, FileState};
+use crate::adapters::{DiffTarget, GitBackend};
+use crate::error::{Result, RfxError};
+
+/// One `@@` section of a file diff
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Hunk {
+    /// The `@@ -a,b +c,d @@ ...` line
+    pub header: String,
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    /// Body lines with their ` `, `+`, `-` or `\` prefix, without line endings
+    pub lines: Vec<String>,
+}
+
+impl Hunk {
+    pub fn added(&self) -> usize {
+        self.lines.iter().filter(|l| l.starts_with('+')).count()
+    }
+
+    pub fn removed(&self) -> usize {
+        self.lines.iter().filter(|l| l.starts_with('-')).count()
+    }
+
+    /// Body lines with their line numbers on each side
+    pub fn numbered(&self) -> Vec<DiffLine> {
+        let (mut old, mut new) = (self.old_start, self.new_start);
+
+        self.lines
+            .iter()
+            .map(|line| {
+                let (kind, text) = match line.chars().next() {
+                    Some('+') => (LineKind::Added, &line[1..]),
+                    Some('-') => (LineKind::Removed, &line[1..]),
+                    Some('\\') => (LineKind::Note, line.as_str()),
+                    Some(_) => (LineKind::Context, &line[1..]),
+                    None => (LineKind::Context, ""),
+                };
+                let (old_no, new_no) = match kind {
+                    LineKind::Added => (None, Some(new)),
+                    LineKind::Removed => (Some(old), None),
+                    LineKind::Context => (Some(old), Some(new)),
+                    LineKind::Note => (None, None),
+                };
+                match kind {
+                    LineKind::Added => new += 1,
+                    LineKind::Removed => old += 1,
+                    LineKind::Context => {
+                        old += 1;
+                        new += 1;
+                    }
+                    LineKind::Note => {}
+                }
+                DiffLine { kind, old_no, new_no, text: text.to_string() }
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+    /// `\ No newline at end of file`
+    Note,
+}
+
+/// A hunk line without its prefix, with its old and new line numbers
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    pub old_no: Option<usize>,
+    pub new_no: Option<usize>,
+    pub text: String,
+}
+
+/// One row of a side-by-side view: old version left, new version right
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Row {
+    pub left: Option<DiffLine>,
+    pub right: Option<DiffLine>,
+}
+
+/// Line a hunk up in two columns: context on both sides, and each run of
+/// removed lines next to the added lines that replace it
+pub fn side_by_side(hunk: &Hunk) -> Vec<Row> {
+    let mut rows = Vec::new();
+    let mut removed: Vec<DiffLine> = Vec::new();
+    let mut added: Vec<DiffLine> = Vec::new();
+
+    let flush = |rows: &mut Vec<Row>, removed: &mut Vec<DiffLine>, added: &mut Vec<DiffLine>| {
+        let len = removed.len().max(added.len());
+        let mut left = removed.drain(..);
+        let mut right = added.drain(..);
+        for _ in 0..len {
+            rows.push(Row { left: left.next(), right: right.next() });
+        }
+    };
+
+    for line in hunk.numbered() {
+        match line.kind {
+            LineKind::Removed => {
+                // A new removal run starts after the previous pair was complete
+                if !added.is_empty() {
+                    flush(&mut rows, &mut removed, &mut added);
+                }
+                removed.push(line);
+            }
+            LineKind::Added => added.push(line),
+            LineKind::Context | LineKind::Note => {
+                flush(&mut rows, &mut removed, &mut added);
+                rows.push(Row { left: Some(line.clone()), right: Some(line) });
+            }
+        }
+    }
+    flush(&mut rows, &mut removed, &mut added);
+
+    rows
+}
+
+/// Everything `git diff` says about one file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FileDiff {
+    pub path: String,
+    /// Previous path of a renamed or copied file
+    pub old_path: Option<String>,
+    pub change: FileState,
+    pub binary: bool,
+    /// Lines added and removed over all hunks
+    pub added: usize,
+    pub removed: usize,
+    /// `diff --git`, `index`, `---` and `+++` lines, needed to rebuild a patch
+    #[serde(skip)]
+    pub header: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+/// `@@ -12,7 +12,8 @@ fn main()` -> (12, 7, 12, 8)
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize, usize)> {
+    let ranges = line.strip_prefix("@@ ")?.split(" @@").next()?;
+    let (old, new) = ranges.split_once(' ')?;
+
+    // A missing count means one line
+    let range = |r: &str| -> Option<(usize, usize)> {
+        match r.split_once(',') {
+            Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+            None => Some((r.parse().ok()?, 1)),
+        }
+    };
+
+    let (old_start, old_lines) = range(old.strip_prefix('-')?)?;
+    let (new_start, new_lines) = range(new.strip_prefix('+')?)?;
+    Some((old_start, old_lines, new_start, new_lines))
+}
+
+/// `a/src/main.rs` -> `src/main.rs`
+fn strip_side(path: &str) -> &str {
+    path.strip_prefix("a/").or_else(|| path.strip_prefix("b/")).unwrap_or(path)
+}
+
+/// Split `git diff` output (run with `--no-color`) into files and hunks
+pub fn parse_diff(raw: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+
+    for line in raw.lines() {
+        if line.starts_with("diff --git ") {
+            // The path is corrected by the `+++`/`---` lines when they follow
+            let path = line.rsplit(" b/").next().unwrap_or_default().to_string();
+            files.push(FileDiff {
+                path,
+                old_path: None,
+                change: FileState::Modified,
+                binary: false,
+                added: 0,
+                removed: 0,
+                header: vec![line.to_string()],
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+
+        if let Some((old_start, old_lines, new_start, new_lines)) = parse_hunk_header(line) {
+            file.hunks.push(Hunk {
+                header: line.to_string(),
+                old_start,
+                old_lines,
+                new_start,
+                new_lines,
+                lines: Vec::new(),
+            });
+        } else if let Some(hunk) = file.hunks.last_mut() {
+            hunk.lines.push(line.to_string());
+        } else {
+            if let Some(path) = line.strip_prefix("+++ ").filter(|p| *p != "/dev/null") {
+                file.path = strip_side(path).to_string();
+            } else if let Some(path) = line.strip_prefix("--- ").filter(|p| *p != "/dev/null")
+                && !file.header.iter().any(|h| h.starts_with("+++ "))
+            {
+                file.path = strip_side(path).to_string();
+            }
+            if line.starts_with("Binary files ") || line == "GIT binary patch" {
+                file.binary = true;
+            }
+            if line.starts_with("new file mode") {
+                file.change = FileState::Added;
+            } else if line.starts_with("deleted file mode") {
+                file.change = FileState::Deleted;
+            } else if let Some(from) = line.strip_prefix("rename from ") {
+                file.change = FileState::Renamed;
+                file.old_path = Some(from.to_string());
+            } else if let Some(from) = line.strip_prefix("copy from ") {
+                file.change = FileState::Copied;
+                file.old_path = Some(from.to_string());
+            } else if let Some(to) = line.strip_prefix("rename to ").or_else(|| line.strip_prefix("copy to ")) {
+                file.path = to.to_string();
+            }
+            file.header.push(line.to_string());
+        }
+    }
+
+    for file in &mut files {
+        file.added = file.hunks.iter().map(Hunk::added).sum();
+        file.removed = file.hunks.iter().map(Hunk::removed).sum();
+    }
+
+    files
+}
+
+/// Parsed diff of `target`, limited to `paths` unless empty
+pub fn load_diff<G: GitBackend>(git: &G, target: &DiffTarget, paths: &[String]) -> Result<Vec<FileDiff>> {
+    Ok(parse_diff(&git.diff(target, paths)?))
+}
+
+/// Everything not yet committed in one file (staged and unstaged), for
+/// previews. New files show up as entirely added.
+pub fn file_preview<G: GitBackend>(git: &G, file: &FileChange) -> Result<Option<FileDiff>> {
+    if file.worktree == FileState::Untracked {
+        return new_file_diff(git, &file.path).map(Some);
+    }
+
+    // Before the first commit there is no HEAD to compare with
+    let target = match current_commit(git) {
+        Some(_) => DiffTarget::Against("HEAD".to_string()),
+        None => DiffTarget::Staged,
+    };
+    let mut paths = vec![file.path.clone()];
+    paths.extend(file.renamed_from.clone());
+
+    Ok(load_diff(git, &target, &paths)?.into_iter().next())
+}
+
+/// A file git does not know yet, shown as one hunk of added lines
+fn new_file_diff<G: GitBackend>(git: &G, path: &str) -> Result<FileDiff> {
+    let bytes = fs::read(git.work_tree()?.join(path))
+        .map_err(|e| RfxError::Io(format!("Could not read '{}': {}", path, e)))?;
+
+    let mut file = FileDiff {
+        path: path.to_string(),
+        old_path: None,
+        change: FileState::Added,
+        binary: false,
+        added: 0,
+        removed: 0,
+        header: Vec::new(),
+        hunks: Vec::new(),
+    };
+
+    let Ok(text) = String::from_utf8(bytes) else {
+        file.binary = true;
+        return Ok(file);
+    };
+
+    let lines: Vec<String> = text.lines().map(|l| format!("+{}", l)).collect();
+    if !lines.is_empty() {
+        file.added = lines.len();
+        file.hunks.push(Hunk {
+            header: format!("@@ -0,0 +1,{} @@", lines.len()),
+            old_start: 0,
+            old_lines: 0,
+            new_start: 1,
+            new_lines: lines.len(),
+            lines,
+        });
+    }
+
+    Ok(file)
+}
+
+/// A patch holding only the chosen hunks of `file` (by index), ready for
+/// `git apply --cached`.
+///
+/// Hunks are independent, so each keeps its old-side position; the new-side
+/// start is shifted by the lines the skipped hunks would have added or removed.
+pub fn build_patch(file: &FileDiff, chosen: &[usize]) -> String {
+    let mut patch = String::new();
+    for line in &file.header {
+        patch.push_str(line);
+        patch.push('\n');
+    }
+
+    let mut offset: isize = 0;
+    for (i, hunk) in file.hunks.iter().enumerate() {
+        if !chosen.contains(&i) {
+            continue;
+        }
+
+        let new_start = (hunk.old_start as isize + offset).max(0) as usize;
+        // Keep the function context git printed after the second `@@`
+        let context = hunk.header.splitn(3, "@@").nth(2).unwrap_or_default();
+        patch.push_str(&format!(
+            "@@ -{},{} +{},{} @@{}\n",
+            hunk.old_start, hunk.old_lines, new_start, hunk.new_lines, context
+        ));
+        for line in &hunk.lines {
+            patch.push_str(line);
+            patch.push('\n');
+        }
+
+        offset += hunk.new_lines as isize - hunk.old_lines as isize;
+    }
+
+    patch
+}
+
+/// Whether parts of a file can be staged on their own: a text file already
+/// known to git, with unstaged edits
+pub fn can_split(file: &FileChange) -> bool {
+    file.conflict.is_none()
+        && !file.submodule
+        && file.worktree == FileState::Modified
+}
+
+/// Unstaged changes of one file, split into hunks (`None` for binary files
+/// or when nothing is unstaged)
+pub fn unstaged_hunks<G: GitBackend>(git: &G, path: &str) -> Result<Option<FileDiff>> {
+    let raw = git.diff(&DiffTarget::Worktree, &[path.to_string()])?;
+    let file = parse_diff(&raw).into_iter().next();
+    Ok(file.filter(|f| !f.binary && !f.hunks.is_empty()))
+}
+
+/// Stage only the chosen hunks of a file
+pub fn stage_hunks<G: GitBackend>(git: &G, file: &FileDiff, chosen: &[usize]) -> Result<()> {
+    if chosen.is_empty() {
+        return Ok(());
+    }
+    if chosen.iter().any(|&i| i >= file.hunks.len()) {
+        return Err(RfxError::Validation(format!("'{}' has no such change.", file.path)));
+    }
+
+    git.apply_cached(&build_patch(file, chosen))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::fake::FakeBackend;
+    use crate::core::get_changed_files;
+
+    /// `git diff` after editing line 3, deleting line 10 and adding a line after 17
+    const TWO_HUNKS: &str = "\
+diff --git a/n.txt b/n.txt
+index 0ff3bbb..e056d7e 100644
+--- a/n.txt
++++ b/n.txt
+@@ -1,13 +1,12 @@
+ 1
+ 2
+-3
++three
+ 4
+ 5
+ 6
+ 7
+ 8
+ 9
+-10
+ 11
+ 12
+ 13
+@@ -15,6 +14,7 @@
+ 15
+ 16
+ 17
++seventeen-and-a-half
+ 18
+ 19
+ 20
+";
+
+    const HEADER: &str = "diff --git a/n.txt b/n.txt\nindex 0ff3bbb..e056d7e 100644\n--- a/n.txt\n+++ b/n.txt\n";
+
+    #[test]
+    fn patches_keep_only_the_chosen_hunks() {
+        let file = parse_diff(TWO_HUNKS).remove(0);
+        assert_eq!(build_patch(&file, &[0, 1]), TWO_HUNKS);
+
+        // Without the first hunk (one line shorter) the second one starts
+        // where it did in the old file
+        let second = build_patch(&file, &[1]);
+        assert!(second.starts_with(&format!("{}@@ -15,6 +15,7 @@\n 15\n", HEADER)));
+        assert!(!second.contains("three"));
+
+        let first = build_patch(&file, &[0]);
+        assert!(first.ends_with(" 12\n 13\n"));
+        assert!(!first.contains("seventeen"));
+    }
+
+    #[test]
+    fn patches_shift_later_hunks_by_skipped_line_counts() {
+        let raw = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1,2 +1,4 @@ fn a()\n a\n+b\n+c\n d\n@@ -10,1 +12,1 @@ fn z()\n-x\n+y\n";
+        let file = parse_diff(raw).remove(0);
+        assert!(build_patch(&file, &[1]).ends_with("@@ -10,1 +10,1 @@ fn z()\n-x\n+y\n"));
+    }
+
+    #[test]
+    fn staging_hunks_applies_a_patch_to_the_index() {
+        let git = FakeBackend::new();
+        let file = parse_diff(TWO_HUNKS).remove(0);
+
+        stage_hunks(&git, &file, &[]).unwrap();
+        assert!(matches!(stage_hunks(&git, &file, &[2]), Err(RfxError::Validation(_))));
+        assert!(!git.calls().contains(&"apply_cached".to_string()));
+
+        stage_hunks(&git, &file, &[1]).unwrap();
+        assert!(git.calls().contains(&"apply_cached".to_string()));
+    }
+
+    #[test]
+    fn only_edited_known_text_files_split() {
+        let git = FakeBackend::new()
+            .with_change(".M", "edited")
+            .with_change("??", "new")
+            .with_change("UU", "conflicted");
+        let files = get_changed_files(&git).unwrap();
+        let split: Vec<&str> = files.iter().filter(|f| can_split(f)).map(|f| f.path.as_str()).collect();
+        assert_eq!(split, ["edited"]);
+
+        let git = git.with_diff("edited", TWO_HUNKS);
+        assert_eq!(unstaged_hunks(&git, "edited").unwrap().unwrap().hunks.len(), 2);
+        let binary = "diff --git a/img b/img\nindex 1..2 100644\nBinary files a/img and b/img differ\n";
+        let git = git.with_diff("img", binary);
+        assert_eq!(unstaged_hunks(&git, "img").unwrap(), None);
+    }
+
+    /// `git diff --cached -M` after renaming and editing, adding, deleting
+    /// and replacing a binary file
+    const MIXED: &str = "\
+diff --git a/bin b/bin
+index 27844bb..c610103 100644
+Binary files a/bin and b/bin differ
+diff --git a/fresh b/fresh
+new file mode 100644
+index 0000000..ce01362
+--- /dev/null
++++ b/fresh
+@@ -0,0 +1 @@
++hello
+diff --git a/r.txt b/moved.txt
+similarity index 83%
+rename from r.txt
+rename to moved.txt
+index 0fdf397..72ce94f 100644
+--- a/r.txt
++++ b/moved.txt
+@@ -1,6 +1,6 @@
+ a
+ b
+-c
++C
+ d
+ e
+ f
+diff --git a/old b/old
+deleted file mode 100644
+index c1b0730..0000000
+--- a/old
++++ /dev/null
+@@ -1 +0,0 @@
+-x
+\\ No newline at end of file
+";
+
+    #[test]
+    fn parses_every_kind_of_file_change() {
+        let files = parse_diff(MIXED);
+        let summary: Vec<(&str, FileState, bool, usize, usize)> = files
+            .iter()
+            .map(|f| (f.path.as_str(), f.change, f.binary, f.added, f.removed))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("bin", FileState::Modified, true, 0, 0),
+                ("fresh", FileState::Added, false, 1, 0),
+                ("moved.txt", FileState::Renamed, false, 1, 1),
+                ("old", FileState::Deleted, false, 0, 1),
+            ]
+        );
+        assert_eq!(files[2].old_path.as_deref(), Some("r.txt"));
+        assert_eq!(files[3].hunks[0].lines, ["-x", "\\ No newline at end of file"]);
+        assert_eq!((files[1].hunks[0].old_start, files[1].hunks[0].old_lines), (0, 0));
+    }
+
+    #[test]
+    fn hunk_headers_default_to_one_line() {
+        assert_eq!(parse_hunk_header("@@ -12,7 +12,8 @@ fn main()"), Some((12, 7, 12, 8)));
+        assert_eq!(parse_hunk_header("@@ -1 +0,0 @@"), Some((1, 1, 0, 0)));
+        assert_eq!(parse_hunk_header("@@@ -1,2 -1,2 +1,3 @@@"), None);
+    }
+
+    #[test]
+    fn numbers_lines_on_both_sides() {
+        let hunk = &parse_diff(TWO_HUNKS)[0].hunks[0];
+        let numbered = hunk.numbered();
+        let at = |i: usize| (numbered[i].kind, numbered[i].old_no, numbered[i].new_no, numbered[i].text.as_str());
+        assert_eq!(at(0), (LineKind::Context, Some(1), Some(1), "1"));
+        assert_eq!(at(2), (LineKind::Removed, Some(3), None, "3"));
+        assert_eq!(at(3), (LineKind::Added, None, Some(3), "three"));
+        assert_eq!(at(10), (LineKind::Removed, Some(10), None, "10"));
+        assert_eq!(at(11), (LineKind::Context, Some(11), Some(10), "11"));
+
+        let note = &parse_diff(MIXED)[3].hunks[0].numbered()[1];
+        assert_eq!((note.kind, note.old_no, note.new_no), (LineKind::Note, None, None));
+    }
+
+    #[test]
+    fn side_by_side_pairs_removals_with_their_replacements() {
+        let raw = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1,4 +1,4 @@\n a\n-b\n-c\n+B\n-d\n+D\n+E\n";
+        let rows = side_by_side(&parse_diff(raw)[0].hunks[0]);
+        let text = |side: &Option<DiffLine>| side.as_ref().map(|l| l.text.clone()).unwrap_or_default();
+        let pairs: Vec<(String, String)> = rows.iter().map(|r| (text(&r.left), text(&r.right))).collect();
+        let expected = [("a", "a"), ("b", "B"), ("c", ""), ("d", "D"), ("", "E")];
+        assert_eq!(pairs, expected.map(|(l, r)| (l.to_string(), r.to_string())));
+    }
+
+    #[test]
+    fn loads_the_diff_of_a_target() {
+        let git = FakeBackend::new().with_diff("moved.txt", MIXED);
+        let files = load_diff(&git, &DiffTarget::Staged, &["moved.txt".to_string()]).unwrap();
+        assert_eq!(files.len(), 4);
+        assert!(load_diff(&git, &DiffTarget::Worktree, &["other".to_string()]).unwrap().is_empty());
+    }
+}
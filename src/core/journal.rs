@@ -0,0 +1,567 @@
+//! Journal of every mutating rfx operation.
+//!
+//! Each entry records which refs an operation moved (before and after) and
+//! which branch was checked out, so any operation can be reverted or
+//! re-applied later. Entries live in `.git/rfx/journal.json`; undone entries
+//! stay in the file (for `rfx redo`) until a new operation is recorded.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::{remote_names, split_remote};
+use crate::adapters::{GitBackend, ResetMode};
+use crate::error::{Result, RfxError};
+
+/// Oldest entries are dropped beyond this many
+const MAX_ENTRIES: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OperationKind {
+    Commit,
+    Branch,
+    Switch,
+    DeleteBranch,
+    Pull,
+    Push,
+    Reset,
+    UndoMerge,
+    Revert,
+    Amend,
+}
+
+impl OperationKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            OperationKind::Commit => "Commit",
+            OperationKind::Branch => "New branch",
+            OperationKind::Switch => "Switch branch",
+            OperationKind::DeleteBranch => "Delete branch",
+            OperationKind::Pull => "Pull",
+            OperationKind::Push => "Push",
+            OperationKind::Reset => "Undo commit",
+            OperationKind::UndoMerge => "Undo merge",
+            OperationKind::Revert => "Revert",
+            OperationKind::Amend => "Amend commit",
+        }
+    }
+
+    /// How the checked-out branch is moved when reverting this kind of operation
+    fn reset_mode(&self) -> ResetMode {
+        match self {
+            // Commits come back as staged changes instead of disappearing
+            OperationKind::Commit | OperationKind::Reset | OperationKind::Amend => ResetMode::Soft,
+            _ => ResetMode::Keep,
+        }
+    }
+}
+
+/// A ref moved by an operation (`None` = the ref did not exist)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RefUpdate {
+    pub name: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: u64,
+    pub kind: OperationKind,
+    pub description: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Checked-out branch (or commit hash when detached) before and after
+    pub head_before: String,
+    pub head_after: String,
+    pub refs: Vec<RefUpdate>,
+    #[serde(default)]
+    pub undone: bool,
+}
+
+impl Operation {
+    /// Remote branches this operation changed, as `(remote, branch)` pairs;
+    /// `remotes` are the configured remote names, which may contain `/`
+    pub fn remote_branches(&self, remotes: &[String]) -> Vec<(String, String)> {
+        self.refs
+            .iter()
+            .filter_map(|r| remote_branch_of(&r.name, remotes))
+            .collect()
+    }
+
+    /// The branch a `Branch` operation created; its description may also name the start
+    pub fn branch_name(&self) -> &str {
+        self.refs.first().map_or(self.head_after.as_str(), |r| short_ref(&r.name))
+    }
+}
+
+fn remote_branch_of(refname: &str, remotes: &[String]) -> Option<(String, String)> {
+    split_remote(remotes, refname.strip_prefix("refs/remotes/")?)
+}
+
+fn journal_path<G: GitBackend>(git: &G) -> Result<PathBuf> {
+    Ok(git.git_dir()?.join("rfx").join("journal.json"))
+}
+
+/// All recorded operations, oldest first
+pub fn load<G: GitBackend>(git: &G) -> Result<Vec<Operation>> {
+    let path = journal_path(git)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let raw = fs::read_to_string(&path)?;
+    serde_json::from_str(&raw)
+        .map_err(|e| RfxError::Io(format!("The rfx journal at {} is corrupted: {}", path.display(), e)))
+}
+
+fn save<G: GitBackend>(git: &G, ops: &[Operation]) -> Result<()> {
+    let path = journal_path(git)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let json = serde_json::to_string_pretty(ops).map_err(|e| RfxError::Io(e.to_string()))?;
+
+    // Write then rename, so a crash never leaves a half-written journal
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// Seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Commit a ref points at, or `None` if it does not exist
+fn resolve<G: GitBackend>(git: &G, refname: &str) -> Option<String> {
+    git.rev_parse(refname).ok()
+}
+
+/// Name of the checked-out branch, or the commit hash when detached
+fn head_name<G: GitBackend>(git: &G) -> String {
+    match git.current_branch() {
+        Ok(b) if b != "HEAD" => b,
+        _ => resolve(git, "HEAD").unwrap_or_else(|| "HEAD".to_string()),
+    }
+}
+
+/// The ref to watch for the checked-out branch (`HEAD` itself when detached)
+pub fn current_ref<G: GitBackend>(git: &G) -> String {
+    match git.current_branch() {
+        Ok(b) if b != "HEAD" => format!("refs/heads/{}", b),
+        _ => "HEAD".to_string(),
+    }
+}
+
+/// Run `op` and record the refs it moved.
+///
+/// Nothing is recorded when the operation fails or changes nothing (for
+/// example a pull that was already up to date). Failing to write the
+/// journal never fails the operation itself.
+pub fn record<G, T, F>(git: &G, kind: OperationKind, description: &str, refs: &[String], op: F) -> Result<T>
+where
+    G: GitBackend,
+    F: FnOnce() -> Result<T>,
+{
+    let head_before = head_name(git);
+    let before: Vec<Option<String>> = refs.iter().map(|r| resolve(git, r)).collect();
+
+    let out = op()?;
+
+    let head_after = head_name(git);
+    let updates: Vec<RefUpdate> = refs
+        .iter()
+        .zip(before)
+        .map(|(name, before)| RefUpdate {
+            name: name.clone(),
+            before,
+            after: resolve(git, name),
+        })
+        .filter(|u| u.before != u.after)
+        .collect();
+
+    if updates.is_empty() && head_before == head_after {
+        return Ok(out);
+    }
+
+    let _ = append(git, kind, description, head_before, head_after, updates);
+    Ok(out)
+}
+
+fn append<G: GitBackend>(
+    git: &G,
+    kind: OperationKind,
+    description: &str,
+    head_before: String,
+    head_after: String,
+    refs: Vec<RefUpdate>,
+) -> Result<()> {
+    let mut ops = load(git)?;
+
+    // A new operation starts a new timeline: forget what could be redone
+    ops.retain(|op| !op.undone);
+
+    let id = ops.last().map(|op| op.id + 1).unwrap_or(1);
+    ops.push(Operation {
+        id,
+        kind,
+        description: description.to_string(),
+        timestamp: now(),
+        head_before,
+        head_after,
+        refs,
+        undone: false,
+    });
+
+    if ops.len() > MAX_ENTRIES {
+        let excess = ops.len() - MAX_ENTRIES;
+        ops.drain(..excess);
+    }
+
+    save(git, &ops)
+}
+
+/// The operation `undo` would revert
+pub fn next_undo<G: GitBackend>(git: &G) -> Result<Option<Operation>> {
+    Ok(load(git)?.into_iter().rev().find(|op| !op.undone))
+}
+
+/// The operation `redo` would re-apply
+pub fn next_redo<G: GitBackend>(git: &G) -> Result<Option<Operation>> {
+    Ok(load(git)?.into_iter().find(|op| op.undone))
+}
+
+/// Whether the repository still looks exactly as `op` left it
+pub fn is_current<G: GitBackend>(git: &G, op: &Operation) -> bool {
+    op.refs.iter().all(|r| resolve(git, &r.name) == r.after)
+}
+
+/// Revert the most recent operation
+pub fn undo<G: GitBackend>(git: &G) -> Result<Operation> {
+    let mut ops = load(git)?;
+    let index = ops
+        .iter()
+        .rposition(|op| !op.undone)
+        .ok_or_else(|| RfxError::Validation("There is nothing to undo.".to_string()))?;
+
+    apply(git, &ops[index], false)?;
+    ops[index].undone = true;
+    save(git, &ops)?;
+
+    Ok(ops[index].clone())
+}
+
+/// Re-apply the most recently undone operation
+pub fn redo<G: GitBackend>(git: &G) -> Result<Operation> {
+    let mut ops = load(git)?;
+    let index = ops
+        .iter()
+        .position(|op| op.undone)
+        .ok_or_else(|| RfxError::Validation("There is nothing to redo.".to_string()))?;
+
+    apply(git, &ops[index], true)?;
+    ops[index].undone = false;
+    save(git, &ops)?;
+
+    Ok(ops[index].clone())
+}
+
+/// Refuse operations whose undo or redo would force-push a protected branch
+pub fn ensure_not_protected<G: GitBackend>(git: &G, op: &Operation) -> Result<()> {
+    for (_, branch) in op.remote_branches(&remote_names(git)?) {
+        super::ensure_not_protected(git, &branch)?;
+    }
+    Ok(())
+}
+
+/// Undo or redo until operation `id` is the latest applied one.
+/// Returns the operations that were reverted or re-applied, in order.
+pub fn jump_to<G: GitBackend>(git: &G, id: u64) -> Result<Vec<Operation>> {
+    let ops = load(git)?;
+    let target = ops
+        .iter()
+        .find(|op| op.id == id)
+        .ok_or_else(|| RfxError::Validation(format!("No operation #{} in the history.", id)))?;
+
+    // Check every step up front so a jump never stops halfway at a protected branch
+    for op in ops.iter().filter(|op| if target.undone { op.undone && op.id <= id } else { !op.undone && op.id > id }) {
+        ensure_not_protected(git, op)?;
+    }
+
+    let mut changed = Vec::new();
+    if target.undone {
+        let steps = ops.iter().filter(|op| op.undone && op.id <= id).count();
+        for _ in 0..steps {
+            changed.push(redo(git)?);
+        }
+    } else {
+        let steps = ops.iter().filter(|op| !op.undone && op.id > id).count();
+        for _ in 0..steps {
+            changed.push(undo(git)?);
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Move every ref touched by `op` to its `after` (forward) or `before` state
+fn apply<G: GitBackend>(git: &G, op: &Operation, forward: bool) -> Result<()> {
+    let target_of = |r: &RefUpdate| if forward { r.after.clone() } else { r.before.clone() };
+    let source_of = |r: &RefUpdate| if forward { r.before.clone() } else { r.after.clone() };
+    let head_target = if forward { &op.head_after } else { &op.head_before };
+
+    // 1. Refuse if anything moved since the operation: we would lose newer work
+    for r in &op.refs {
+        if resolve(git, &r.name) != source_of(r) {
+            return Err(RfxError::Validation(format!(
+                "'{}' has changed since this operation, so rfx cannot safely revert it.",
+                short_ref(&r.name)
+            )));
+        }
+    }
+
+    // 2. Remote branches can only be moved by pushing, which protected
+    //    branches never are
+    ensure_not_protected(git, op)?;
+    let remotes = remote_names(git)?;
+    for r in op.refs.iter().filter(|r| r.name.starts_with("refs/remotes/")) {
+        if let Some((remote, branch)) = remote_branch_of(&r.name, &remotes) {
+            git.push_with_lease(&remote, &branch, target_of(r).as_deref(), source_of(r).as_deref())?;
+        }
+    }
+
+    let local: Vec<&RefUpdate> = op
+        .refs
+        .iter()
+        .filter(|r| !r.name.starts_with("refs/remotes/"))
+        .collect();
+    // The branch that will be checked out once we are done
+    let switches_branch = op.head_before != op.head_after;
+    let checked_out = if switches_branch {
+        format!("refs/heads/{}", head_target)
+    } else {
+        current_ref(git)
+    };
+
+    // 3. Create or move branches that will not be checked out (and create
+    //    the one we are about to switch to, if it does not exist yet)
+    for r in local.iter().filter(|r| r.name != checked_out || source_of(r).is_none()) {
+        if let Some(sha) = target_of(r) {
+            git.update_ref(&r.name, &sha)?;
+        }
+    }
+
+    // 4. Switch back (or forward) to the branch the operation left us on
+    if switches_branch && head_name(git) != *head_target {
+        git.checkout(head_target)?;
+    }
+
+    // 5. Move the checked-out branch, updating the index/worktree as needed
+    for r in local.iter().filter(|r| r.name == checked_out && resolve(git, &r.name) != target_of(r)) {
+        match target_of(r) {
+            Some(sha) => {
+                git.reset(op.kind.reset_mode(), &sha)?;
+            }
+            // Back to an unborn branch (undoing the very first commit)
+            None => {
+                git.delete_ref(&r.name)?;
+            }
+        }
+    }
+
+    // 6. Remove branches that did not exist at that point
+    for r in local.iter().filter(|r| r.name != checked_out) {
+        if target_of(r).is_none() {
+            git.delete_ref(&r.name)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `refs/heads/main` -> `main`, `refs/remotes/origin/main` -> `origin/main`
+pub fn short_ref(refname: &str) -> &str {
+    refname
+        .strip_prefix("refs/heads/")
+        .or_else(|| refname.strip_prefix("refs/remotes/"))
+        .unwrap_or(refname)
+}
+
+/// Human-friendly age of a timestamp ("5 minutes ago")
+pub fn age(timestamp: u64) -> String {
+    let secs = now().saturating_sub(timestamp);
+    let (n, unit) = match secs {
+        s if s < 60 => return "just now".to_string(),
+        s if s < 3600 => (s / 60, "minute"),
+        s if s < 86_400 => (s / 3600, "hour"),
+        s => (s / 86_400, "day"),
+    };
+    if n == 1 { format!("1 {} ago", unit) } else { format!("{} {}s ago", n, unit) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::fake::{fake_commit, FakeBackend};
+    use crate::core::{create_branch, create_commit, push_branch, stage_files, BranchStart};
+
+    fn repo() -> FakeBackend {
+        FakeBackend::new()
+            .with_branch("main", vec![fake_commit("Initial")])
+            .with_change(".M", "a.rs")
+    }
+
+    fn commit(git: &FakeBackend, message: &str) {
+        stage_files(git, &["a.rs".to_string()]).unwrap();
+        create_commit(git, message).unwrap();
+    }
+
+    #[test]
+    fn records_the_refs_an_operation_moved() {
+        let git = repo();
+        let before = git.rev_parse("HEAD").unwrap();
+        commit(&git, "Add a");
+
+        let ops = load(&git).unwrap();
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].kind, OperationKind::Commit);
+        assert_eq!(ops[0].description, "\"Add a\"");
+        assert_eq!(ops[0].refs[0].name, "refs/heads/main");
+        assert_eq!(ops[0].refs[0].before, Some(before));
+        assert_eq!(ops[0].refs[0].after, git.rev_parse("HEAD").ok());
+    }
+
+    #[test]
+    fn failed_or_empty_operations_are_not_recorded() {
+        let git = repo();
+        let refs = ["refs/heads/main".to_string()];
+        let _ = record(&git, OperationKind::Pull, "nothing", &refs, || Ok(()));
+        let _: Result<()> = record(&git, OperationKind::Pull, "fails", &refs, || Err(RfxError::NoUpstream));
+        assert!(load(&git).unwrap().is_empty());
+    }
+
+    #[test]
+    fn undo_and_redo_a_commit() {
+        let git = repo();
+        let initial = git.rev_parse("HEAD").unwrap();
+        commit(&git, "Add a");
+        let committed = git.rev_parse("HEAD").unwrap();
+
+        let undone = undo(&git).unwrap();
+        assert_eq!(undone.kind, OperationKind::Commit);
+        assert_eq!(git.rev_parse("HEAD").unwrap(), initial);
+        assert_eq!(next_redo(&git).unwrap().map(|op| op.id), Some(undone.id));
+
+        redo(&git).unwrap();
+        assert_eq!(git.rev_parse("HEAD").unwrap(), committed);
+        assert!(next_redo(&git).unwrap().is_none());
+        assert!(matches!(redo(&git), Err(RfxError::Validation(_))));
+    }
+
+    #[test]
+    fn undoing_a_new_branch_goes_back_and_deletes_it() {
+        let git = repo();
+        create_branch(&git, "feature", &BranchStart::Head).unwrap();
+        assert_eq!(git.current_branch().unwrap(), "feature");
+
+        undo(&git).unwrap();
+        assert_eq!(git.current_branch().unwrap(), "main");
+        assert!(!git.list_branches().unwrap().contains(&"feature".to_string()));
+    }
+
+    #[test]
+    fn refuses_to_undo_when_the_branch_moved_since() {
+        let git = repo();
+        commit(&git, "Add a");
+        // Moved behind rfx's back
+        git.update_ref("refs/heads/main", &fake_commit("Initial").hash).unwrap();
+
+        assert!(matches!(undo(&git), Err(RfxError::Validation(_))));
+        assert!(!load(&git).unwrap()[0].undone);
+    }
+
+    #[test]
+    fn a_new_operation_drops_what_could_be_redone() {
+        let git = repo().with_change(".M", "b.rs");
+        commit(&git, "Add a");
+        undo(&git).unwrap();
+
+        stage_files(&git, &["b.rs".to_string()]).unwrap();
+        create_commit(&git, "Add b").unwrap();
+
+        let ops = load(&git).unwrap();
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].description, "\"Add b\"");
+    }
+
+    #[test]
+    fn jumping_undoes_everything_after_the_target() {
+        let git = repo();
+        let initial = git.rev_parse("HEAD").unwrap();
+        commit(&git, "One");
+        let one = load(&git).unwrap()[0].id;
+        create_branch(&git, "feature", &BranchStart::Head).unwrap();
+
+        let changed = jump_to(&git, one).unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(git.current_branch().unwrap(), "main");
+
+        // And back again
+        let last = load(&git).unwrap().last().unwrap().id;
+        assert_eq!(jump_to(&git, last).unwrap().len(), 1);
+        assert_eq!(git.current_branch().unwrap(), "feature");
+        assert_ne!(git.rev_parse("HEAD").unwrap(), initial);
+    }
+
+    #[test]
+    fn short_names_and_ages() {
+        assert_eq!(short_ref("refs/heads/feature/x"), "feature/x");
+        assert_eq!(short_ref("refs/remotes/origin/main"), "origin/main");
+        assert_eq!(short_ref("HEAD"), "HEAD");
+        assert_eq!(age(now()), "just now");
+        assert_eq!(age(now() - 3600), "1 hour ago");
+        assert_eq!(age(now() - 3 * 86_400), "3 days ago");
+    }
+
+    /// `feature` pushed twice to a remote whose name contains a `/`
+    fn pushed_to_team_remote(branch: &str) -> FakeBackend {
+        let git = FakeBackend::new()
+            .with_branch(branch, vec![fake_commit("Initial")])
+            .on_branch(branch)
+            .with_remote("team/origin", "git@example.com:team/app.git")
+            .with_change(".M", "a.rs");
+        push_branch(&git, branch, "team/origin").unwrap();
+        commit(&git, "Add a");
+        push_branch(&git, branch, "team/origin").unwrap();
+        git
+    }
+
+    #[test]
+    fn undoing_a_push_targets_remotes_with_slashes() {
+        let git = pushed_to_team_remote("feature");
+        let op = next_undo(&git).unwrap().unwrap();
+        let remotes = remote_names(&git).unwrap();
+        assert_eq!(op.remote_branches(&remotes), [("team/origin".to_string(), "feature".to_string())]);
+
+        undo(&git).unwrap();
+        assert_eq!(git.rev_parse("refs/remotes/team/origin/feature").unwrap(), fake_commit("Initial").hash);
+        redo(&git).unwrap();
+        assert_eq!(git.rev_parse("refs/remotes/team/origin/feature").unwrap(), git.rev_parse("feature").unwrap());
+    }
+
+    #[test]
+    fn protection_is_checked_against_the_real_branch_name() {
+        let git = pushed_to_team_remote("main");
+        assert!(matches!(undo(&git), Err(RfxError::Validation(_))));
+        assert!(!git.calls().contains(&"push_with_lease".to_string()));
+    }
+}
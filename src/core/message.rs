@@ -0,0 +1,553 @@
+//! Commit message policy: the rules a message has to follow before rfx
+//! commits it, and a builder for Conventional Commits messages.
+//!
+//! By default only a minimum length is enforced. Setting
+//! `commit.convention` to `conventional` in the rfx config turns on the
+//! Conventional Commits rules (`type(scope)!: description`), tuned by the
+//! other `commit.*` settings.
+
+use super::config;
+use crate::adapters::GitBackend;
+use crate::error::Result;
+
+/// The standard Conventional Commits types, with what they are for
+pub const DEFAULT_TYPES: [(&str, &str); 11] = [
+    ("feat", "A new feature"),
+    ("fix", "A bug fix"),
+    ("docs", "Documentation only"),
+    ("style", "Formatting, no code change"),
+    ("refactor", "Code change that neither fixes a bug nor adds a feature"),
+    ("perf", "Performance improvement"),
+    ("test", "Adding or fixing tests"),
+    ("build", "Build system or dependencies"),
+    ("ci", "CI configuration"),
+    ("chore", "Other changes that don't touch the code"),
+    ("revert", "Reverts a previous commit"),
+];
+
+/// Trailer used by the builder for issue references
+pub const ISSUE_TRAILER: &str = "Refs";
+
+/// One check a commit message has to pass
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// First line at least this many characters
+    MinLength(usize),
+    /// The first line is `type(scope)!: description` with one of these types
+    Conventional { types: Vec<String> },
+    /// Conventional header must name a scope
+    RequireScope,
+    /// Scope must be one of these
+    Scopes(Vec<String>),
+    /// First line at most this long
+    MaxSubjectLength(usize),
+    /// Body lines at most this long (lines without spaces, like URLs, are exempt)
+    BodyWrap(usize),
+    /// This trailer (e.g. `Signed-off-by`) must be present
+    RequireTrailer(String),
+}
+
+/// The rules configured for a repository
+#[derive(Clone, Debug)]
+pub struct CommitPolicy {
+    pub rules: Vec<Rule>,
+}
+
+/// The header of a Conventional Commits message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub kind: String,
+    pub scope: Option<String>,
+    pub breaking: bool,
+    pub description: String,
+}
+
+/// A commit message split into its parts
+#[derive(Clone, Debug, Default)]
+pub struct ParsedMessage {
+    pub subject: String,
+    /// Lines between the subject and the trailers
+    pub body: Vec<String>,
+    /// `Key: value` lines in the last paragraph
+    pub trailers: Vec<(String, String)>,
+    /// Whether a blank line separates the subject from what follows
+    pub separated: bool,
+}
+
+/// What the guided builder asks for
+#[derive(Clone, Debug, Default)]
+pub struct MessageParts {
+    pub kind: String,
+    pub scope: Option<String>,
+    pub description: String,
+    pub body: String,
+    /// What breaks, for a `BREAKING CHANGE:` trailer
+    pub breaking: Option<String>,
+    /// Issue reference such as `#123`
+    pub issue: Option<String>,
+    pub trailers: Vec<(String, String)>,
+}
+
+impl CommitPolicy {
+    pub fn is_conventional(&self) -> bool {
+        self.types().is_some()
+    }
+
+    /// Allowed Conventional Commits types (`None` when the convention is off)
+    pub fn types(&self) -> Option<&[String]> {
+        self.rules.iter().find_map(|r| match r {
+            Rule::Conventional { types } => Some(types.as_slice()),
+            _ => None,
+        })
+    }
+
+    /// Allowed scopes (empty: any scope)
+    pub fn scopes(&self) -> &[String] {
+        self.rules
+            .iter()
+            .find_map(|r| match r {
+                Rule::Scopes(scopes) => Some(scopes.as_slice()),
+                _ => None,
+            })
+            .unwrap_or(&[])
+    }
+
+    pub fn requires_scope(&self) -> bool {
+        self.rules.contains(&Rule::RequireScope)
+    }
+
+    /// Column to wrap the body at, if the policy asks for it
+    pub fn body_wrap(&self) -> Option<usize> {
+        self.rules.iter().find_map(|r| match r {
+            Rule::BodyWrap(width) => Some(*width),
+            _ => None,
+        })
+    }
+
+    pub fn required_trailers(&self) -> Vec<&str> {
+        self.rules
+            .iter()
+            .filter_map(|r| match r {
+                Rule::RequireTrailer(key) => Some(key.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Everything wrong with a message, one sentence per problem
+    pub fn check(&self, message: &str) -> Vec<String> {
+        let message = message.trim();
+        if message.is_empty() {
+            return vec!["Commit message cannot be empty.".to_string()];
+        }
+
+        let parsed = parse_message(message);
+        let mut problems = self.check_subject(&parsed.subject);
+
+        if !parsed.body.is_empty() || !parsed.trailers.is_empty() {
+            if !parsed.separated {
+                problems.push("Leave a blank line after the first line.".to_string());
+            }
+            if let Some(width) = self.body_wrap()
+                && let Some(line) = parsed.body.iter().find(|l| l.chars().count() > width && l.contains(' '))
+            {
+                problems.push(format!(
+                    "A line of the description is {} characters long; wrap it at {}.",
+                    line.chars().count(),
+                    width
+                ));
+            }
+        }
+
+        for key in self.required_trailers() {
+            if !parsed.trailers.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)) {
+                problems.push(format!("Add a '{}: ...' line at the end of the message.", key));
+            }
+        }
+
+        problems
+    }
+
+    /// Problems with the first line alone (what a one-line prompt can check)
+    pub fn check_subject(&self, subject: &str) -> Vec<String> {
+        let subject = subject.trim();
+        let mut problems = Vec::new();
+
+        for rule in &self.rules {
+            match rule {
+                Rule::MinLength(min) if subject.chars().count() < *min => {
+                    problems.push("Commit message is too short.".to_string());
+                }
+                Rule::MaxSubjectLength(max) if subject.chars().count() > *max => {
+                    problems.push(format!(
+                        "The first line is {} characters; keep it to {}.",
+                        subject.chars().count(),
+                        max
+                    ));
+                }
+                Rule::Conventional { types } => match parse_header(subject) {
+                    None => problems.push(
+                        "The first line must look like 'type(scope): description', e.g. 'feat(login): add remember-me'."
+                            .to_string(),
+                    ),
+                    Some(header) => {
+                        if !types.contains(&header.kind) {
+                            problems.push(format!("Unknown type '{}'. Use one of: {}.", header.kind, types.join(", ")));
+                        }
+                        if header.description.is_empty() {
+                            problems.push("Add a description after the ':'.".to_string());
+                        }
+                        let scopes = self.scopes();
+                        match &header.scope {
+                            None if self.requires_scope() => {
+                                problems.push("Name a scope, e.g. 'fix(parser): ...'.".to_string());
+                            }
+                            Some(scope) if !scopes.is_empty() && !scopes.contains(scope) => {
+                                problems.push(format!("Unknown scope '{}'. Use one of: {}.", scope, scopes.join(", ")));
+                            }
+                            _ => {}
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        problems
+    }
+}
+
+/// The policy configured for this repository.
+///
+/// `commit.convention` is `none` (default) or `conventional`; with the
+/// convention on, `commit.types`, `commit.scopes`, `commit.require_scope`,
+/// `commit.subject_length` and `commit.body_wrap` adjust it (0 turns a
+/// length check off). `commit.trailers` lists trailers every message needs
+/// either way.
+pub fn commit_policy<G: GitBackend>(git: &G) -> Result<CommitPolicy> {
+    let config = config::load(git)?;
+    let mut rules = vec![Rule::MinLength(config.number("commit.min_length").unwrap_or(3))];
+
+    if config.text("commit.convention").as_deref() == Some("conventional") {
+        let mut types = config.list("commit.types");
+        if types.is_empty() {
+            types = DEFAULT_TYPES.iter().map(|(t, _)| t.to_string()).collect();
+        }
+        rules.push(Rule::Conventional { types });
+
+        let scopes = config.list("commit.scopes");
+        if !scopes.is_empty() {
+            rules.push(Rule::Scopes(scopes));
+        }
+        if config.flag("commit.require_scope") {
+            rules.push(Rule::RequireScope);
+        }
+
+        match config.number("commit.subject_length") {
+            Some(0) | None => {}
+            Some(max) => rules.push(Rule::MaxSubjectLength(max)),
+        }
+        match config.number("commit.body_wrap") {
+            Some(0) | None => {}
+            Some(width) => rules.push(Rule::BodyWrap(width)),
+        }
+    }
+
+    for key in config.list("commit.trailers") {
+        rules.push(Rule::RequireTrailer(key));
+    }
+
+    Ok(CommitPolicy { rules })
+}
+
+/// `feat(login)!: add remember-me` -> its parts
+pub fn parse_header(subject: &str) -> Option<Header> {
+    let (prefix, description) = subject.split_once(':')?;
+    let (prefix, breaking) = match prefix.strip_suffix('!') {
+        Some(p) => (p, true),
+        None => (prefix, false),
+    };
+
+    let (kind, scope) = match prefix.split_once('(') {
+        Some((kind, rest)) => {
+            let scope = rest.strip_suffix(')')?;
+            if scope.is_empty() || scope.contains(['(', ')']) {
+                return None;
+            }
+            (kind, Some(scope.to_string()))
+        }
+        None => (prefix, None),
+    };
+
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return None;
+    }
+
+    Some(Header {
+        kind: kind.to_string(),
+        scope,
+        breaking,
+        description: description.trim().to_string(),
+    })
+}
+
+/// `Signed-off-by: Jane <jane@example.com>` / `BREAKING CHANGE: ...`
+fn parse_trailer(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once(": ")?;
+    let valid = key == "BREAKING CHANGE"
+        || (!key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'));
+    valid.then(|| (key.to_string(), value.trim().to_string()))
+}
+
+/// Split a message into subject, body and trailers
+pub fn parse_message(message: &str) -> ParsedMessage {
+    let mut lines = message.trim().lines();
+    let subject = lines.next().unwrap_or("").trim().to_string();
+    let mut rest: Vec<&str> = lines.collect();
+    let separated = rest.first().is_none_or(|l| l.trim().is_empty());
+
+    // Trailers are the last paragraph, if every line in it is one
+    let start = rest.iter().rposition(|l| l.trim().is_empty()).map_or(0, |i| i + 1);
+    let trailers: Option<Vec<(String, String)>> = rest[start..].iter().map(|l| parse_trailer(l)).collect();
+    let trailers = match trailers {
+        Some(t) if !t.is_empty() && start > 0 => {
+            rest.truncate(start);
+            t
+        }
+        _ => Vec::new(),
+    };
+
+    let body = rest
+        .into_iter()
+        .skip_while(|l| l.trim().is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let end = body.iter().rposition(|l| !l.trim().is_empty()).map_or(0, |i| i + 1);
+
+    ParsedMessage {
+        subject,
+        body: body[..end].to_vec(),
+        trailers,
+        separated,
+    }
+}
+
+/// Re-flow text to lines of at most `width` characters, keeping paragraphs
+pub fn wrap_text(text: &str, width: usize) -> String {
+    let mut out = Vec::new();
+
+    for paragraph in text.split("\n\n") {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                out.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        if !line.is_empty() {
+            out.push(line);
+        }
+        out.push(String::new());
+    }
+
+    out.pop();
+    out.join("\n")
+}
+
+/// `type(scope)!: description`
+pub fn build_header(parts: &MessageParts) -> String {
+    format!(
+        "{}{}{}: {}",
+        parts.kind,
+        parts.scope.as_ref().map(|s| format!("({})", s)).unwrap_or_default(),
+        if parts.breaking.is_some() { "!" } else { "" },
+        parts.description.trim()
+    )
+}
+
+/// Put a Conventional Commits message together from its parts, wrapping
+/// the body as the policy asks
+pub fn build_message(policy: &CommitPolicy, parts: &MessageParts) -> String {
+    let mut message = build_header(parts);
+
+    let body = parts.body.trim();
+    if !body.is_empty() {
+        message.push_str("\n\n");
+        match policy.body_wrap() {
+            Some(width) => message.push_str(&wrap_text(body, width)),
+            None => message.push_str(body),
+        }
+    }
+
+    let mut trailers = Vec::new();
+    if let Some(breaking) = parts.breaking.as_deref().map(str::trim).filter(|b| !b.is_empty()) {
+        trailers.push(format!("BREAKING CHANGE: {}", breaking));
+    }
+    if let Some(issue) = parts.issue.as_deref().map(str::trim).filter(|i| !i.is_empty()) {
+        trailers.push(format!("{}: {}", ISSUE_TRAILER, issue));
+    }
+    trailers.extend(parts.trailers.iter().map(|(k, v)| format!("{}: {}", k, v.trim())));
+
+    if !trailers.is_empty() {
+        message.push_str("\n\n");
+        message.push_str(&trailers.join("\n"));
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::adapters::fake::FakeBackend;
+
+    fn conventional() -> CommitPolicy {
+        CommitPolicy {
+            rules: vec![
+                Rule::MinLength(3),
+                Rule::Conventional { types: vec!["feat".to_string(), "fix".to_string()] },
+                Rule::Scopes(vec!["api".to_string(), "ui".to_string()]),
+                Rule::MaxSubjectLength(30),
+                Rule::BodyWrap(20),
+            ],
+        }
+    }
+
+    #[test]
+    fn headers_split_into_type_scope_and_description() {
+        let header = parse_header("feat(login)!: add remember-me").unwrap();
+        assert_eq!(
+            header,
+            Header {
+                kind: "feat".to_string(),
+                scope: Some("login".to_string()),
+                breaking: true,
+                description: "add remember-me".to_string(),
+            }
+        );
+        assert_eq!(parse_header("fix: typo").unwrap().scope, None);
+        assert_eq!(parse_header("Fix the typo"), None);
+        assert_eq!(parse_header("feat(): empty scope"), None);
+        assert_eq!(parse_header("feat(a)(b): two scopes"), None);
+        assert_eq!(parse_header("big feat: spaces"), None);
+    }
+
+    #[test]
+    fn subjects_are_checked_against_every_rule() {
+        let policy = conventional();
+        assert!(policy.check_subject("fix(api): handle 404").is_empty());
+        assert!(policy.check_subject("fix: no scope is fine").is_empty());
+        assert_eq!(policy.check_subject("Fix the login").len(), 1);
+        assert_eq!(
+            policy.check_subject("docs(db): a very long description indeed"),
+            [
+                "Unknown type 'docs'. Use one of: feat, fix.",
+                "Unknown scope 'db'. Use one of: api, ui.",
+                "The first line is 40 characters; keep it to 30.",
+            ]
+        );
+        assert_eq!(policy.check_subject("fix:"), ["Add a description after the ':'."]);
+
+        let plain = CommitPolicy { rules: vec![Rule::MinLength(3)] };
+        assert_eq!(plain.check_subject("wip"), Vec::<String>::new());
+        assert_eq!(plain.check_subject(" x "), ["Commit message is too short."]);
+    }
+
+    #[test]
+    fn scopes_can_be_required() {
+        let policy = CommitPolicy {
+            rules: vec![Rule::Conventional { types: vec!["fix".to_string()] }, Rule::RequireScope],
+        };
+        assert_eq!(policy.check_subject("fix: typo"), ["Name a scope, e.g. 'fix(parser): ...'."]);
+        assert!(policy.check_subject("fix(any): typo").is_empty());
+    }
+
+    #[test]
+    fn messages_split_into_subject_body_and_trailers() {
+        let parsed = parse_message("fix: typo\n\nFirst paragraph.\n\nSecond.\n\nRefs: #12\nSigned-off-by: Ann <ann@example.com>\n");
+        assert_eq!(parsed.subject, "fix: typo");
+        assert_eq!(parsed.body, ["First paragraph.", "", "Second."]);
+        assert_eq!(parsed.trailers[1], ("Signed-off-by".to_string(), "Ann <ann@example.com>".to_string()));
+        assert!(parsed.separated);
+
+        // A last paragraph that is not all trailers is body
+        let parsed = parse_message("fix: typo\nsee: the docs\nand more");
+        assert!(!parsed.separated);
+        assert!(parsed.trailers.is_empty());
+        assert_eq!(parsed.body.len(), 2);
+    }
+
+    #[test]
+    fn whole_messages_check_layout_wrapping_and_trailers() {
+        let mut policy = conventional();
+        policy.rules.push(Rule::RequireTrailer("Signed-off-by".to_string()));
+
+        assert_eq!(policy.check("   "), ["Commit message cannot be empty."]);
+        assert!(policy.check("fix: typo\n\nShort lines\nhttps://example.com/a/very/long/url\n\nsigned-off-by: Ann").is_empty());
+        assert_eq!(
+            policy.check("fix: typo\nThis line is much too long to keep"),
+            [
+                "Leave a blank line after the first line.",
+                "A line of the description is 34 characters long; wrap it at 20.",
+                "Add a 'Signed-off-by: ...' line at the end of the message.",
+            ]
+        );
+    }
+
+    #[test]
+    fn wrapping_keeps_paragraphs() {
+        assert_eq!(wrap_text("one two three four\n\nfive", 9), "one two\nthree\nfour\n\nfive");
+        assert_eq!(wrap_text("unbreakable-word", 4), "unbreakable-word");
+    }
+
+    #[test]
+    fn builds_a_message_from_its_parts() {
+        let parts = MessageParts {
+            kind: "feat".to_string(),
+            scope: Some("api".to_string()),
+            description: " paginate results ".to_string(),
+            body: "Lists now come back one page at a time.".to_string(),
+            breaking: Some("list endpoints return a page".to_string()),
+            issue: Some("#42".to_string()),
+            trailers: vec![("Signed-off-by".to_string(), "Ann".to_string())],
+        };
+        let message = build_message(&conventional(), &parts);
+        assert_eq!(
+            message,
+            "feat(api)!: paginate results\n\nLists now come back\none page at a time.\n\n\
+             BREAKING CHANGE: list endpoints return a page\nRefs: #42\nSigned-off-by: Ann"
+        );
+        assert_eq!(parse_message(&message).trailers.len(), 3);
+
+        let minimal = MessageParts { kind: "fix".to_string(), description: "typo".to_string(), ..Default::default() };
+        assert_eq!(build_message(&CommitPolicy { rules: Vec::new() }, &minimal), "fix: typo");
+    }
+
+    #[test]
+    fn the_policy_comes_from_the_config() {
+        let git = FakeBackend::new();
+        let policy = commit_policy(&git).unwrap();
+        assert_eq!(policy.rules, [Rule::MinLength(3)]);
+        assert!(!policy.is_conventional());
+
+        let root = git.work_tree().unwrap();
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join(config::REPO_FILE),
+            "[commit]\nconvention = \"conventional\"\nscopes = [\"api\"]\nrequire_scope = true\nsubject_length = 0\ntrailers = [\"Signed-off-by\"]\n",
+        )
+        .unwrap();
+
+        let policy = commit_policy(&git).unwrap();
+        assert_eq!(policy.types().unwrap().len(), DEFAULT_TYPES.len());
+        assert_eq!(policy.scopes(), ["api"]);
+        assert!(policy.requires_scope());
+        assert!(!policy.rules.iter().any(|r| matches!(r, Rule::MaxSubjectLength(_))));
+        assert_eq!(policy.required_trailers(), ["Signed-off-by"]);
+    }
+}
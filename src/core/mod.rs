@@ -1,4 +1,17 @@
-use crate::adapters;
+pub mod cleanup;
+pub mod config;
+pub mod conflict;
+pub mod diff;
+pub mod journal;
+pub mod message;
+pub mod progress;
+pub mod refname;
+pub mod shelf;
+pub mod trash;
+
+use crate::adapters::{GitBackend, PullStrategy, ResetMode};
+use crate::error::{Result, RfxError};
+use journal::OperationKind;
 use serde::Serialize;
 
 #[derive(Clone, Debug, Serialize)]
@@ -33,16 +46,126 @@ pub struct RemoteInfo {
 #[derive(Serialize, Clone)]
 pub struct RemoteBranchInfo {
     pub full_name: String, // e.g. origin/main
+    pub remote: String, // e.g. origin
     pub short_name: String, // e.g. main
     pub author: String,
     pub date: String,
 }
 
-/// Represents a file change in a commit
+/// State of a file on one side (index or worktree) of `git status`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileState {
+    Unmodified,
+    Modified,
+    TypeChanged,
+    Added,
+    Deleted,
+    Renamed,
+    Copied,
+    Untracked,
+}
+
+impl FileState {
+    fn from_code(code: char) -> FileState {
+        match code {
+            'M' | 'U' => FileState::Modified,
+            'T' => FileState::TypeChanged,
+            'A' => FileState::Added,
+            'D' => FileState::Deleted,
+            'R' => FileState::Renamed,
+            'C' => FileState::Copied,
+            '?' => FileState::Untracked,
+            _ => FileState::Unmodified,
+        }
+    }
+
+    /// Short label used when listing files
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileState::Unmodified => "[...]",
+            FileState::Modified => "[Mod]",
+            FileState::TypeChanged => "[Typ]",
+            FileState::Added => "[Add]",
+            FileState::Deleted => "[Del]",
+            FileState::Renamed => "[Ren]",
+            FileState::Copied => "[Cpy]",
+            FileState::Untracked => "[New]",
+        }
+    }
+}
+
+/// Which side(s) of a merge touched a conflicted file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictKind {
+    BothModified,
+    BothAdded,
+    BothDeleted,
+    AddedByUs,
+    AddedByThem,
+    DeletedByUs,
+    DeletedByThem,
+}
+
+impl ConflictKind {
+    fn from_xy(xy: &str) -> ConflictKind {
+        match xy {
+            "AA" => ConflictKind::BothAdded,
+            "DD" => ConflictKind::BothDeleted,
+            "AU" => ConflictKind::AddedByUs,
+            "UA" => ConflictKind::AddedByThem,
+            "DU" => ConflictKind::DeletedByUs,
+            "UD" => ConflictKind::DeletedByThem,
+            _ => ConflictKind::BothModified,
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            ConflictKind::BothModified => "both modified",
+            ConflictKind::BothAdded => "both added",
+            ConflictKind::BothDeleted => "both deleted",
+            ConflictKind::AddedByUs => "added by us",
+            ConflictKind::AddedByThem => "added by them",
+            ConflictKind::DeletedByUs => "deleted by us",
+            ConflictKind::DeletedByThem => "deleted by them",
+        }
+    }
+}
+
+/// Represents a changed file, with its staged and unstaged state
 #[derive(Clone, Debug, Serialize)]
 pub struct FileChange {
-    pub status: String, // e.g., "M", "??", "D"
     pub path: String,
+    /// State in the index (what the next commit will contain)
+    pub index: FileState,
+    /// State in the working directory relative to the index
+    pub worktree: FileState,
+    /// Original path when the file was renamed or copied
+    pub renamed_from: Option<String>,
+    pub submodule: bool,
+    pub conflict: Option<ConflictKind>,
+}
+
+impl FileChange {
+    /// Has changes ready to commit
+    pub fn is_staged(&self) -> bool {
+        self.conflict.is_none() && !matches!(self.index, FileState::Unmodified | FileState::Untracked)
+    }
+
+    /// Has changes (or is untracked) in the working directory only
+    pub fn is_unstaged(&self) -> bool {
+        self.conflict.is_none() && self.worktree != FileState::Unmodified
+    }
+
+    /// Path for display, showing the source of renames (`old -> new`)
+    pub fn display_path(&self) -> String {
+        match &self.renamed_from {
+            Some(from) => format!("{} -> {}", from, self.path),
+            None => self.path.clone(),
+        }
+    }
 }
 
 /// Summary of the repository status
@@ -52,14 +175,16 @@ pub struct StatusSummary {
     pub ahead: Option<usize>,
     pub behind: Option<usize>,
     pub changes: Vec<FileChange>,
+    /// Merge, rebase, etc. that stopped halfway
+    pub pending: Option<progress::PendingOperation>,
 }
 
 /// Get the current status summary
-pub fn get_status() -> Result<StatusSummary, String> {
-    let branch = adapters::git_branch()?;
-    let changes = get_changed_files()?;
+pub fn get_status<G: GitBackend>(git: &G) -> Result<StatusSummary> {
+    let branch = git.current_branch()?;
+    let changes = get_changed_files(git)?;
     
-    let (ahead, behind) = match adapters::git_ahead_behind(&branch) {
+    let (ahead, behind) = match git.ahead_behind(&branch) {
         Ok((a, b)) => (Some(a), Some(b)),
         Err(_) => (None, None),
     };
@@ -69,25 +194,26 @@ pub fn get_status() -> Result<StatusSummary, String> {
         ahead,
         behind,
         changes,
+        pending: progress::detect(git)?,
     })
 }
 
 /// List branches with detailed info
-pub fn branches_detailed() -> Result<Vec<BranchInfo>, String> {
-    let branch_names = adapters::git_list_branches()?;
+pub fn branches_detailed<G: GitBackend>(git: &G) -> Result<Vec<BranchInfo>> {
+    let branch_names = git.list_branches()?;
 
     let branches: Vec<BranchInfo> = branch_names
         .into_iter()
         .map(|branch| {
             // First commit (creator info)
-            let first_commit = adapters::git_first_commit(&branch)
+            let first_commit = git.first_commit(&branch)
                 .unwrap_or_else(|_| "Unknown|Unknown".to_string());
             let mut parts = first_commit.split('|');
             let author = parts.next().unwrap_or("Unknown").to_string();
             let time_created = parts.next().unwrap_or("Unknown").to_string();
 
             // Last commit info
-            let last_commit = adapters::git_last_commit(&branch)
+            let last_commit = git.last_commit(&branch)
                 .unwrap_or_else(|_| "Unknown|No commit".to_string());
             let mut last_parts = last_commit.split('|');
             let last_change = last_parts.next().unwrap_or("Unknown").to_string();
@@ -140,8 +266,8 @@ fn parse_remote_url(url: &str) -> (Option<String>, Option<String>, Option<String
 }
 
 /// List remotes with detailed info
-pub fn remotes_detailed() -> Result<Vec<RemoteInfo>, String> {
-    let raw_remotes = adapters::git_list_remotes()?;
+pub fn remotes_detailed<G: GitBackend>(git: &G) -> Result<Vec<RemoteInfo>> {
+    let raw_remotes = git.list_remotes()?;
 
     let remotes = raw_remotes
         .into_iter()
@@ -177,108 +303,418 @@ pub fn remotes_detailed() -> Result<Vec<RemoteInfo>, String> {
 }
 
 /// List commits with detailed info
-pub fn commits_detailed(branch: &str, count: usize) -> Result<Vec<CommitInfo>, String> {
-    let raw_commits = crate::adapters::git_list_commits(branch, count)?;
-    let commits: Vec<CommitInfo> = raw_commits.into_iter().map(|line| {
-        let parts: Vec<&str> = line.split('|').collect();
-        CommitInfo {
-            hash: parts.get(0).unwrap_or(&"").to_string(),
-            author: parts.get(1).unwrap_or(&"").to_string(),
-            date: parts.get(2).unwrap_or(&"").to_string(),
-            message: parts.get(3).unwrap_or(&"").to_string(),
+pub fn commits_detailed<G: GitBackend>(git: &G, branch: &str, count: usize) -> Result<Vec<CommitInfo>> {
+    let raw_commits = git.list_commits(branch, count)?;
+    Ok(raw_commits.iter().map(|line| parse_commit_line(line)).collect())
+}
+
+/// Parse a `hash|author|date|subject` line (the subject may contain `|`)
+fn parse_commit_line(line: &str) -> CommitInfo {
+    let parts: Vec<&str> = line.splitn(4, '|').collect();
+    CommitInfo {
+        hash: parts.first().unwrap_or(&"").to_string(),
+        author: parts.get(1).unwrap_or(&"").to_string(),
+        date: parts.get(2).unwrap_or(&"").to_string(),
+        message: parts.get(3).unwrap_or(&"").to_string(),
+    }
+}
+
+/// A file touched by a commit range or stash, from `--name-status` output
+#[derive(Clone, Debug, Serialize)]
+pub struct PathChange {
+    pub state: FileState,
+    pub path: String,
+}
+
+/// Parse `--name-status -z` output: a status field followed by one path
+/// (two for renames and copies, of which the new path is kept)
+fn parse_name_status(raw: &str) -> Vec<PathChange> {
+    let mut fields = raw.split('\0').filter(|f| !f.is_empty());
+    let mut files = Vec::new();
+
+    while let Some(status) = fields.next() {
+        let code = status.chars().next().unwrap_or('M');
+        let mut path = fields.next();
+        if matches!(code, 'R' | 'C') {
+            path = fields.next();
         }
-    }).collect();
-    Ok(commits)
+
+        if let Some(path) = path {
+            files.push(PathChange {
+                state: FileState::from_code(code),
+                path: path.to_string(),
+            });
+        }
+    }
+
+    files
 }
 
 // Get list of changed files
-pub fn get_changed_files() -> Result<Vec<FileChange>, String> {
-    let raw_files = adapters::git_status_porcelain()?;
-    
-    let changes = raw_files
+pub fn get_changed_files<G: GitBackend>(git: &G) -> Result<Vec<FileChange>> {
+    let entries = git.status()?;
+
+    let changes = entries
         .into_iter()
-        .map(|(status, path)| FileChange { status, path })
+        .map(|entry| {
+            let mut codes = entry.xy.chars();
+            let x = codes.next().unwrap_or('.');
+            let y = codes.next().unwrap_or('.');
+            let (index, worktree) = if entry.xy == "??" {
+                (FileState::Unmodified, FileState::Untracked)
+            } else {
+                (FileState::from_code(x), FileState::from_code(y))
+            };
+
+            FileChange {
+                path: entry.path,
+                index,
+                worktree,
+                renamed_from: entry.orig_path,
+                submodule: entry.submodule,
+                conflict: entry.unmerged.then(|| ConflictKind::from_xy(&entry.xy)),
+            }
+        })
         .collect();
 
     Ok(changes)
 }
 
 // Stage specific files
-pub fn stage_files(files: &[String]) -> Result<String, String> {
+pub fn stage_files<G: GitBackend>(git: &G, files: &[String]) -> Result<String> {
     if files.is_empty() {
         return Ok("No files to stage".to_string());
     }
-    adapters::git_add(files)
+    git.add(files)
+}
+
+// Unstage specific files (their edits stay in the working directory)
+pub fn unstage_files<G: GitBackend>(git: &G, files: &[String]) -> Result<String> {
+    if files.is_empty() {
+        return Ok("No files to unstage".to_string());
+    }
+    git.unstage(files)
 }
 
 // Stage all files
-pub fn stage_all_files() -> Result<String, String> {
-    adapters::git_add_all()
+pub fn stage_all_files<G: GitBackend>(git: &G) -> Result<String> {
+    git.add_all()
 }
 
 // Create commit with message
-pub fn create_commit(message: &str) -> Result<String, String> {
-    let msg = message.trim();
+pub fn create_commit<G: GitBackend>(git: &G, message: &str) -> Result<String> {
+    let msg = check_commit_message(git, message)?;
+
+    let refs = [journal::current_ref(git)];
+    journal::record(git, OperationKind::Commit, &format!("\"{}\"", subject_line(msg)), &refs, || git.commit(msg))
+}
 
-    if msg.is_empty() {
-        return Err("Commit message cannot be empty.".to_string());
+/// Check a message against the repository's commit policy
+fn check_commit_message<'a, G: GitBackend>(git: &G, message: &'a str) -> Result<&'a str> {
+    let problems = message::commit_policy(git)?.check(message);
+    if !problems.is_empty() {
+        return Err(RfxError::Validation(problems.join("\n")));
     }
-    
-    // We can keep the length check here as a business rule
-    if msg.len() < 3 {
-        return Err("Commit message is too short.".to_string());
+
+    Ok(message.trim())
+}
+
+fn subject_line(message: &str) -> &str {
+    message.lines().next().unwrap_or("")
+}
+
+/// Rewrite the last commit with a new message, adding the staged changes
+/// when `include_staged` is set. `rfx undo` brings the old commit back,
+/// leaving the added changes staged.
+pub fn amend_last_commit<G: GitBackend>(git: &G, message: &str, include_staged: bool) -> Result<String> {
+    let msg = check_commit_message(git, message)?;
+
+    if current_commit(git).is_none() {
+        return Err(RfxError::Validation("There is no commit to amend yet.".to_string()));
     }
 
-    adapters::git_commit(msg)
+    let refs = [journal::current_ref(git)];
+    journal::record(git, OperationKind::Amend, &format!("\"{}\"", subject_line(msg)), &refs, || git.commit_amend(msg, include_staged))
 }
 
 /// Check if a branch name is valid and available
-pub fn validate_new_branch_name(name: &str) -> Result<(), String> {
+pub fn validate_new_branch_name<G: GitBackend>(git: &G, name: &str) -> Result<()> {
+    refname::branch_name_rules(git)?.check(name)
+}
+
+/// Names of all local branches
+pub fn local_branch_names<G: GitBackend>(git: &G) -> Result<Vec<String>> {
+    git.list_branches()
+}
+
+/// Where a new branch starts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BranchStart {
+    /// The current commit
+    Head,
+    Local(String),
+    /// A remote branch as `origin/main`
+    Remote(String),
+    Tag(String),
+    Commit(String),
+}
+
+impl BranchStart {
+    /// The revision handed to git (`None` for HEAD)
+    pub fn rev(&self) -> Option<&str> {
+        match self {
+            BranchStart::Head => None,
+            BranchStart::Local(r) | BranchStart::Remote(r) | BranchStart::Tag(r) | BranchStart::Commit(r) => Some(r),
+        }
+    }
+
+    /// Short name: the ref, or the abbreviated hash
+    pub fn label(&self) -> String {
+        match self {
+            BranchStart::Head => "HEAD".to_string(),
+            BranchStart::Commit(c) => c[..c.len().min(7)].to_string(),
+            BranchStart::Local(r) | BranchStart::Remote(r) | BranchStart::Tag(r) => r.clone(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            BranchStart::Head => "the current commit".to_string(),
+            BranchStart::Local(b) => format!("branch '{}'", b),
+            BranchStart::Remote(b) => format!("remote branch '{}'", b),
+            BranchStart::Tag(t) => format!("tag '{}'", t),
+            BranchStart::Commit(_) => format!("commit {}", self.label()),
+        }
+    }
+}
+
+/// Work out what a user-given starting point is: a local branch, a remote
+/// branch, a tag or a commit, in that order
+pub fn resolve_start<G: GitBackend>(git: &G, input: &str) -> Result<BranchStart> {
+    let input = input.trim();
+    if input.is_empty() || input == "HEAD" {
+        return Ok(BranchStart::Head);
+    }
+
+    if git.list_branches()?.iter().any(|b| b == input) {
+        return Ok(BranchStart::Local(input.to_string()));
+    }
+    let on_remote = git
+        .list_remote_branches()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|line| line.split('|').next())
+        .any(|b| b == input && !b.ends_with("/HEAD"));
+    if on_remote {
+        return Ok(BranchStart::Remote(input.to_string()));
+    }
+    if git.list_tags().unwrap_or_default().iter().any(|t| t == input) {
+        return Ok(BranchStart::Tag(input.to_string()));
+    }
+    match git.rev_parse(input) {
+        Ok(hash) => Ok(BranchStart::Commit(hash.trim().to_string())),
+        Err(_) => Err(RfxError::Validation(format!(
+            "'{}' is not a branch, remote branch, tag or commit. Fetch first if it is new on the remote.",
+            input
+        ))),
+    }
+}
+
+/// Create and switch to a new branch starting at `start`.
+///
+/// A branch started from a remote branch tracks it, like `git checkout -b`
+/// does; its first `rfx push` moves the upstream to its own remote branch.
+pub fn create_branch<G: GitBackend>(git: &G, name: &str, start: &BranchStart) -> Result<String> {
+    validate_new_branch_name(git, name)?;
     let name = name.trim();
+    let track = matches!(start, BranchStart::Remote(_));
+
+    let refs = [format!("refs/heads/{}", name)];
+    let description = match start {
+        BranchStart::Head => name.to_string(),
+        _ => format!("{} from {}", name, start.label()),
+    };
+    journal::record(git, OperationKind::Branch, &description, &refs, || {
+        git.create_branch(name, start.rev(), track)
+    })
+}
 
-    // 1. Basic Syntax Rules
-    if name.is_empty() {
-        return Err("Branch name cannot be empty.".to_string());
+/// Names of the configured remotes
+pub fn remote_names<G: GitBackend>(git: &G) -> Result<Vec<String>> {
+    let mut names: Vec<String> = remotes_detailed(git)?.into_iter().map(|r| r.name).collect();
+    names.dedup();
+    Ok(names)
+}
+
+/// The remote to use when none is given: `remote.default` from the config,
+/// `origin` if it exists, otherwise the only (or first) remote
+pub fn default_remote<G: GitBackend>(git: &G) -> Result<String> {
+    let names = remote_names(git)?;
+    if let Some(configured) = config::load(git)?.text("remote.default") {
+        if !names.contains(&configured) {
+            return Err(RfxError::Validation(format!(
+                "The configured default remote '{}' (remote.default) does not exist.",
+                configured
+            )));
+        }
+        return Ok(configured);
     }
-    if name.contains(char::is_whitespace) {
-        return Err("Branch names cannot contain spaces.".to_string());
+    if names.iter().any(|n| n == "origin") {
+        return Ok("origin".to_string());
+    }
+    names.into_iter().next().ok_or_else(|| {
+        RfxError::Validation(
+            "This repository has no remotes. Add one with 'git remote add origin <url>'.".to_string(),
+        )
+    })
+}
+
+/// Split `origin/feature/x` into (`origin`, `feature/x`) using the configured
+/// remote names, so remotes and branches containing `/` are handled
+pub(crate) fn split_remote(names: &[String], full_name: &str) -> Option<(String, String)> {
+    names
+        .iter()
+        .filter_map(|r| full_name.strip_prefix(r.as_str())?.strip_prefix('/').map(|b| (r.clone(), b.to_string())))
+        .max_by_key(|(r, _)| r.len())
+        .or_else(|| full_name.split_once('/').map(|(r, b)| (r.to_string(), b.to_string())))
+}
+
+/// Branch names that usually mean "the default branch", in order of preference
+const WELL_KNOWN_DEFAULTS: [&str; 4] = ["main", "master", "develop", "trunk"];
+
+/// The branch everything is merged into.
+///
+/// `branch.default` from the config if set; otherwise resolved from
+/// `refs/remotes/<remote>/HEAD`, then `init.defaultBranch`, then well-known
+/// names (local first, then on the remote), falling back to the only local
+/// branch or the checked-out one.
+pub fn default_branch<G: GitBackend>(git: &G) -> Result<String> {
+    if let Some(configured) = config::load(git)?.text("branch.default") {
+        return Ok(configured);
     }
 
-    // 2. Check Existence
-    let existing_branches = adapters::git_list_branches()?;
-    if existing_branches.iter().any(|b| b == name) {
-        return Err(format!("A branch named '{}' already exists.", name));
+    let locals = git.list_branches()?;
+    let remote = default_remote(git).ok();
+
+    // 1. What the server says (set by clone or `git remote set-head`)
+    if let Some(remote) = &remote
+        && let Some(head) = git.remote_head(remote)?
+        && let Some((_, branch)) = head.split_once('/')
+    {
+        return Ok(branch.to_string());
     }
 
-    Ok(())
+    // 2. What the user configured for new repositories
+    if let Some(configured) = git.config_get("init.defaultBranch")?
+        && locals.contains(&configured)
+    {
+        return Ok(configured);
+    }
+
+    // 3. Well-known names
+    if let Some(name) = WELL_KNOWN_DEFAULTS.iter().find(|n| locals.iter().any(|b| b == *n)) {
+        return Ok(name.to_string());
+    }
+    if let Some(remote) = &remote {
+        let remote_branches: Vec<String> = git
+            .list_remote_branches()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|line| line.split('|').next().map(String::from))
+            .collect();
+        if let Some(name) = WELL_KNOWN_DEFAULTS
+            .iter()
+            .find(|n| remote_branches.contains(&format!("{}/{}", remote, n)))
+        {
+            return Ok(name.to_string());
+        }
+    }
+
+    // 4. Nothing to go on
+    match locals.as_slice() {
+        [only] => Ok(only.clone()),
+        _ => git.current_branch(),
+    }
 }
 
-/// Create and switch to a new branch
-pub fn create_branch(name: &str) -> Result<String, String> {
-    adapters::git_create_branch(name)
+/// Branches that were deleted and the commit each one pointed at
+#[derive(Debug, Default)]
+pub struct DeleteReport {
+    pub deleted: Vec<(String, String)>,
+    pub failed: Vec<(String, RfxError)>,
 }
 
-/// Pull changes safely
+/// Delete local branches, recording their tips so `rfx undo` can restore them.
+/// The checked-out branch is never deleted.
+pub fn delete_branches<G: GitBackend>(git: &G, names: &[String]) -> Result<DeleteReport> {
+    let current = git.current_branch()?;
+    if names.contains(&current) {
+        return Err(RfxError::Validation(format!(
+            "'{}' is checked out. Switch to another branch before deleting it.",
+            current
+        )));
+    }
+
+    let refs: Vec<String> = names.iter().map(|n| format!("refs/heads/{}", n)).collect();
+    let description = names.join(", ");
+
+    journal::record(git, OperationKind::DeleteBranch, &description, &refs, || {
+        let mut report = DeleteReport::default();
+        for name in names {
+            let tip = git.rev_parse(&format!("refs/heads/{}", name));
+            match tip.and_then(|sha| git.delete_branch(name).map(|_| sha)) {
+                Ok(sha) => report.deleted.push((name.clone(), sha)),
+                Err(e) => report.failed.push((name.clone(), e)),
+            }
+        }
+        Ok(report)
+    })
+}
+
+/// Switch to an existing local branch
+pub fn switch_branch<G: GitBackend>(git: &G, name: &str) -> Result<String> {
+    if git.current_branch()? == name {
+        return Err(RfxError::Validation(format!("You are already on '{}'.", name)));
+    }
+    journal::record(git, OperationKind::Switch, name, &[], || git.checkout(name))
+}
+
+/// Create a local branch tracking a remote-only branch and switch to it
+pub fn switch_to_remote_branch<G: GitBackend>(git: &G, branch: &RemoteBranchInfo) -> Result<String> {
+    let existing = local_branch_names(git)?;
+    if existing.contains(&branch.short_name) {
+        return switch_branch(git, &branch.short_name);
+    }
+
+    let refs = [format!("refs/heads/{}", branch.short_name)];
+    let description = format!("{} (tracking {})", branch.short_name, branch.full_name);
+    journal::record(git, OperationKind::Switch, &description, &refs, || {
+        git.checkout_tracking(&branch.short_name, &branch.full_name)
+    })
+}
+
+// Pull changes safely
 /*
-pub fn pull_changes() -> Result<String, String> {
+pub fn pull_changes<G: GitBackend>(git: &G) -> Result<String> {
     // 1. Safety Check: Ensure working directory is clean
-    let changes = get_changed_files()?;
+    let changes = get_changed_files(git)?;
     if !changes.is_empty() {
-        return Err("You have uncommitted changes. Please commit or stash them before pulling.".to_string());
+        return Err(RfxError::Validation("You have uncommitted changes. Please commit or stash them before pulling.".to_string()));
     }
 
     // 2. Execute Pull
-    adapters::git_pull()
+    git.pull()
 }
 */
 
 /// Get list of remote branches with details
-pub fn get_remote_branches() -> Result<Vec<RemoteBranchInfo>, String> {
+pub fn get_remote_branches<G: GitBackend>(git: &G) -> Result<Vec<RemoteBranchInfo>> {
     // 1. Fetch first
-    let _ = adapters::git_fetch(); 
+    let _ = git.fetch(); 
 
     // 2. Get list
-    let raw = adapters::git_list_remote_branches()?;
+    let raw = git.list_remote_branches()?;
+    let remotes = remote_names(git).unwrap_or_default();
     
     let branches = raw.into_iter().filter_map(|line| {
         let parts: Vec<&str> = line.split('|').collect();
@@ -291,14 +727,11 @@ pub fn get_remote_branches() -> Result<Vec<RemoteBranchInfo>, String> {
             return None;
         }
         
-        if !full_name.contains('/') {
-            return None;
-        }
-
-        let short_name = full_name.splitn(2, '/').nth(1).unwrap_or(&full_name).to_string();
+        let (remote, short_name) = split_remote(&remotes, &full_name)?;
 
         Some(RemoteBranchInfo {
             full_name,
+            remote,
             short_name,
             author: parts[1].to_string(),
             date: parts[2].to_string(),
@@ -308,24 +741,1122 @@ pub fn get_remote_branches() -> Result<Vec<RemoteBranchInfo>, String> {
     Ok(branches)
 }
 
+/// Where the current branch stands against a remote branch, before pulling
+#[derive(Clone, Debug)]
+pub struct PullCheck {
+    /// Local commits the remote branch does not have
+    pub ahead: usize,
+    /// Remote commits the local branch does not have
+    pub behind: usize,
+    /// Some local commits are already on a remote (rebasing would rewrite them)
+    pub local_pushed: bool,
+}
+
+impl PullCheck {
+    pub fn up_to_date(&self) -> bool {
+        self.behind == 0
+    }
+
+    /// Only the remote moved: every strategy gives the same result
+    pub fn can_fast_forward(&self) -> bool {
+        self.ahead == 0
+    }
+
+    pub fn allows(&self, strategy: PullStrategy) -> bool {
+        strategy != PullStrategy::FastForwardOnly || self.can_fast_forward()
+    }
+}
+
+/// Compare HEAD with an already-fetched remote branch
+pub fn check_pull<G: GitBackend>(git: &G, branch: &RemoteBranchInfo) -> Result<PullCheck> {
+    let (ahead, behind) = git.divergence("HEAD", &format!("refs/remotes/{}", branch.full_name))?;
+    let local_pushed = ahead > 0
+        && current_commit(git).is_some_and(|head| !pushed_to(git, &head).is_empty());
+    Ok(PullCheck { ahead, behind, local_pushed })
+}
+
+/// `ff-only`, `merge` or `rebase`
+pub fn parse_pull_strategy(value: &str) -> Option<PullStrategy> {
+    match value.trim().to_lowercase().as_str() {
+        "ff-only" | "ff" | "fast-forward" => Some(PullStrategy::FastForwardOnly),
+        "merge" => Some(PullStrategy::Merge),
+        "rebase" => Some(PullStrategy::Rebase),
+        _ => None,
+    }
+}
+
+/// The config spelling of a strategy, as `parse_pull_strategy` reads it back
+pub fn pull_strategy_name(strategy: PullStrategy) -> &'static str {
+    match strategy {
+        PullStrategy::FastForwardOnly => "ff-only",
+        PullStrategy::Merge => "merge",
+        PullStrategy::Rebase => "rebase",
+    }
+}
+
+/// The pull strategy set in config: `pull.strategy`, or git's own
+/// `pull.rebase` / `pull.ff` settings
+pub fn configured_pull_strategy<G: GitBackend>(git: &G) -> Result<Option<PullStrategy>> {
+    if let Some(value) = config::load(git)?.text("pull.strategy") {
+        return parse_pull_strategy(&value).map(Some).ok_or_else(|| {
+            RfxError::Validation(format!("pull.strategy must be ff-only, merge or rebase, not '{}'.", value))
+        });
+    }
+
+    match git.config_get("pull.rebase")?.as_deref() {
+        Some("true" | "merges" | "interactive" | "i" | "m") => return Ok(Some(PullStrategy::Rebase)),
+        Some("false") => return Ok(Some(PullStrategy::Merge)),
+        _ => {}
+    }
+    if git.config_get("pull.ff")?.as_deref() == Some("only") {
+        return Ok(Some(PullStrategy::FastForwardOnly));
+    }
+    Ok(None)
+}
+
+/// What a pull would bring in, worked out before touching the working tree
+#[derive(Clone, Debug, Serialize)]
+pub struct PullPreview {
+    /// Incoming commits, newest first
+    pub incoming: Vec<CommitInfo>,
+    /// Files the incoming commits change
+    pub files: Vec<PathChange>,
+    /// Files that would conflict when merging; `None` if this git cannot
+    /// predict conflicts (`merge-tree --write-tree` needs git 2.38)
+    pub conflicts: Option<Vec<String>>,
+}
+
+/// Most incoming commits listed in a preview
+const PREVIEW_COMMITS: usize = 50;
+
+/// Preview pulling an already-fetched remote branch into HEAD
+pub fn preview_pull<G: GitBackend>(git: &G, branch: &RemoteBranchInfo) -> Result<PullPreview> {
+    let theirs = format!("refs/remotes/{}", branch.full_name);
+
+    let incoming = git
+        .list_commits_between("HEAD", &theirs, PREVIEW_COMMITS)?
+        .iter()
+        .map(|line| parse_commit_line(line))
+        .collect();
+    let files = parse_name_status(&git.diff_files("HEAD", &theirs)?);
+    let conflicts = git.merge_tree("HEAD", &theirs).ok();
+
+    Ok(PullPreview { incoming, files, conflicts })
+}
+
 /// Execute the pull for a specific branch
-pub fn pull_specific_branch(branch_full_name: &str) -> Result<String, String> {
-    adapters::git_pull_branch(branch_full_name)
+pub fn pull_specific_branch<G: GitBackend>(git: &G, branch: &RemoteBranchInfo, strategy: PullStrategy) -> Result<String> {
+    let refs = [journal::current_ref(git)];
+    let description = format!("from {}", branch.full_name);
+    journal::record(git, OperationKind::Pull, &description, &refs, || {
+        git.pull_branch(&branch.remote, &branch.short_name, strategy)
+    })
 }
 
 /// Push changes to the remote
 /*
-pub fn push_changes() -> Result<String, String> {
-    let branch = adapters::git_branch()?;
-    adapters::git_push_upstream(&branch)
+pub fn push_changes<G: GitBackend>(git: &G) -> Result<String> {
+    let branch = git.current_branch()?;
+    git.push_upstream("origin", &branch)
 }
 */
-pub fn push_branch(branch_name: &str) -> Result<String, String> {
-    adapters::git_push_upstream(branch_name)
+pub fn push_branch<G: GitBackend>(git: &G, branch_name: &str, remote: &str) -> Result<String> {
+    // Only link the branch to the remote the first time; a fork's branch keeps
+    // tracking `upstream` even when it is pushed to `origin`. A branch still
+    // tracking the remote branch it was started from (origin/main) is linked
+    // to its own one instead.
+    let tracked = git.config_get(&format!("branch.{}.merge", branch_name))?;
+    let set_upstream = git.config_get(&format!("branch.{}.remote", branch_name))?.is_none()
+        || tracked.is_some_and(|m| m.trim() != format!("refs/heads/{}", branch_name));
+
+    let refs = [format!("refs/remotes/{}/{}", remote, branch_name)];
+    let description = format!("{} to {}", branch_name, remote);
+    journal::record(git, OperationKind::Push, &description, &refs, || {
+        git.push(remote, branch_name, set_upstream)
+    })
+}
+
+/// Branch patterns that must not be force-pushed (`branch.protected`,
+/// `*` as wildcard; main, master and release/* unless configured)
+pub fn protected_branches<G: GitBackend>(git: &G) -> Result<Vec<String>> {
+    Ok(config::load(git)?.list("branch.protected"))
+}
+
+/// Whether `branch` matches one of the protected patterns
+pub fn is_protected(patterns: &[String], branch: &str) -> bool {
+    patterns.iter().any(|p| wildcard_match(p, branch))
+}
+
+pub(crate) fn ensure_not_protected<G: GitBackend>(git: &G, branch: &str) -> Result<()> {
+    if is_protected(&protected_branches(git)?, branch) {
+        return Err(RfxError::Validation(format!(
+            "'{}' is a protected branch. rfx will not force-push to it.",
+            branch
+        )));
+    }
+    Ok(())
+}
+
+/// Match a name against a pattern where `*` stands for any run of characters
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and the name position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == name[n] {
+            p += 1;
+            n += 1;
+        } else if let Some((sp, sn)) = star {
+            // Let the star swallow one more character and retry
+            p = sp + 1;
+            n = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
 }
 
-/// Undo the last commit
-pub fn undo_last_commit() -> Result<String, String> {
-    // We strictly undo 1 commit
-    adapters::git_reset_soft(1)
-}
\ No newline at end of file
+/// What a force-push would do
+#[derive(Clone, Debug)]
+pub struct ForcePushPlan {
+    pub branch: String,
+    pub remote: String,
+    /// Commit the local branch points at
+    pub local: String,
+    /// Where rfx last saw the remote branch; the push only goes through if
+    /// it is still there (`None` = the branch must not exist on the remote)
+    pub expected: Option<String>,
+    /// Commits on the remote branch that the push throws away, newest first
+    /// (at most [`FORCE_PUSH_PREVIEW`] of them)
+    pub overwritten: Vec<CommitInfo>,
+    /// How many commits the push throws away in total
+    pub overwritten_count: usize,
+}
+
+/// How many overwritten commits a force-push plan lists
+pub const FORCE_PUSH_PREVIEW: usize = 100;
+
+/// Work out what force-pushing `branch` to `remote` would overwrite.
+/// Fails for protected branches.
+pub fn plan_force_push<G: GitBackend>(git: &G, branch: &str, remote: &str) -> Result<ForcePushPlan> {
+    ensure_not_protected(git, branch)?;
+
+    // Best effort: offline, the lease still stops us from overwriting
+    // anything we have not seen
+    let _ = git.fetch();
+
+    let local = git.rev_parse(&format!("refs/heads/{}", branch))?;
+    let remote_ref = format!("refs/remotes/{}/{}", remote, branch);
+    let expected = git.rev_parse(&remote_ref).ok();
+
+    let (overwritten, overwritten_count) = match &expected {
+        Some(_) => (
+            git.list_commits_between(&local, &remote_ref, FORCE_PUSH_PREVIEW)?
+                .iter()
+                .map(|line| parse_commit_line(line))
+                .collect(),
+            git.divergence(&local, &remote_ref)?.1,
+        ),
+        None => (Vec::new(), 0),
+    };
+
+    Ok(ForcePushPlan {
+        branch: branch.to_string(),
+        remote: remote.to_string(),
+        local,
+        expected,
+        overwritten,
+        overwritten_count,
+    })
+}
+
+/// Replace the remote branch with the local one, but only if nobody pushed
+/// since the plan was made (`--force-with-lease` with the expected SHA)
+pub fn force_push<G: GitBackend>(git: &G, plan: &ForcePushPlan) -> Result<String> {
+    ensure_not_protected(git, &plan.branch)?;
+
+    let refs = [format!("refs/remotes/{}/{}", plan.remote, plan.branch)];
+    let description = format!("{} to {} (forced)", plan.branch, plan.remote);
+    journal::record(git, OperationKind::Push, &description, &refs, || {
+        git.push_with_lease(&plan.remote, &plan.branch, Some(&plan.local), plan.expected.as_deref())
+    })
+}
+
+/// Where a branch is pushed when the user already decided: its
+/// `branch.<name>.pushRemote`, then `remote.pushDefault`, then the remote it tracks
+pub fn configured_push_remote<G: GitBackend>(git: &G, branch: &str) -> Result<Option<String>> {
+    let keys = [
+        format!("branch.{}.pushRemote", branch),
+        "remote.pushDefault".to_string(),
+        format!("branch.{}.remote", branch),
+    ];
+    for key in keys {
+        if let Some(remote) = git.config_get(&key)?
+            && remote != "."
+        {
+            return Ok(Some(remote));
+        }
+    }
+    Ok(None)
+}
+
+/// Always push `branch` to `remote` from now on
+pub fn remember_push_remote<G: GitBackend>(git: &G, branch: &str, remote: &str) -> Result<()> {
+    git.config_set(&format!("branch.{}.pushRemote", branch), remote)?;
+    Ok(())
+}
+
+/// What `sync_fork` did
+#[derive(Clone, Debug)]
+pub struct SyncReport {
+    pub branch: String,
+    /// The local branch moved (false when it was already up to date)
+    pub updated: bool,
+}
+
+/// Bring a fork's default branch up to date: update it from `from`
+/// (usually `upstream`) and push the result to `to` (usually `origin`).
+///
+/// The branch is only ever fast-forwarded: if it has commits of its own,
+/// nothing is changed and the user is asked to combine them with a pull.
+pub fn sync_fork<G: GitBackend>(git: &G, from: &str, to: &str) -> Result<SyncReport> {
+    let names = remote_names(git)?;
+    for remote in [from, to] {
+        if !names.iter().any(|n| n == remote) {
+            return Err(RfxError::Validation(format!("There is no remote named '{}'.", remote)));
+        }
+    }
+
+    git.fetch()?;
+
+    let branch = match git.remote_head(from)? {
+        Some(head) => split_remote(&names, &head).map(|(_, b)| b).unwrap_or(head),
+        None => default_branch(git)?,
+    };
+    let source = format!("{}/{}", from, branch);
+    let source_sha = git.rev_parse(&format!("refs/remotes/{}", source))?;
+
+    let refs = [format!("refs/heads/{}", branch)];
+    let local_ref = &refs[0];
+    let before = git.rev_parse(local_ref).ok();
+    let description = format!("from {}", source);
+
+    if git.current_branch()? == branch {
+        journal::record(git, OperationKind::Pull, &description, &refs, || {
+            git.pull_branch(from, &branch, PullStrategy::FastForwardOnly)
+        })?;
+    } else {
+        if let Some(local) = &before
+            && !git.is_ancestor(local, &source_sha)?
+        {
+            return Err(RfxError::Diverged(format!(
+                "'{}' has commits that are not in '{}'. Switch to it and pull to combine them.",
+                branch, source
+            )));
+        }
+        journal::record(git, OperationKind::Pull, &description, &refs, || {
+            git.update_ref(local_ref, &source_sha)
+        })?;
+    }
+
+    let updated = git.rev_parse(local_ref).ok() != before;
+
+    let refs = [format!("refs/remotes/{}/{}", to, branch)];
+    let description = format!("{} to {}", branch, to);
+    journal::record(git, OperationKind::Push, &description, &refs, || git.push(to, &branch, false))?;
+
+    Ok(SyncReport { branch, updated })
+}
+
+/// Name of the checked-out branch
+pub fn current_branch<G: GitBackend>(git: &G) -> Result<String> {
+    git.current_branch()
+}
+
+/// Subject line of the commit at HEAD
+pub fn last_commit_message<G: GitBackend>(git: &G) -> Result<String> {
+    let raw = git.last_commit("HEAD")?;
+    Ok(raw.split('|').nth(1).unwrap_or("Unknown").to_string())
+}
+
+/// Whole message of the commit at HEAD, description and trailers included
+pub fn last_commit_full_message<G: GitBackend>(git: &G) -> Result<String> {
+    git.commit_message("HEAD")
+}
+
+/// Full hash of HEAD (`None` on a branch without commits)
+pub fn current_commit<G: GitBackend>(git: &G) -> Option<String> {
+    git.rev_parse("HEAD").ok()
+}
+
+/// Remote branches that already contain a commit (empty = local only)
+pub fn pushed_to<G: GitBackend>(git: &G, sha: &str) -> Vec<String> {
+    git.remote_refs_containing(sha).unwrap_or_default()
+}
+
+/// How the last commit can be undone without losing work or rewriting shared history
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UndoPlan {
+    /// Local commit: move the branch back one commit, keeping its changes staged
+    SoftReset,
+    /// First commit of the repository: remove it, keeping every file staged
+    RootCommit,
+    /// Local merge commit: return the branch to its state before the merge
+    Merge,
+    /// Already on a remote: add a new commit that reverses it instead
+    Revert { merge: bool, remotes: Vec<String> },
+}
+
+/// The commit at HEAD and the safe way to undo it
+#[derive(Clone, Debug)]
+pub struct LastCommit {
+    pub sha: String,
+    pub message: String,
+    pub plan: UndoPlan,
+}
+
+/// Inspect HEAD (root? merge? already pushed?) and pick the safe undo strategy
+pub fn plan_undo_last_commit<G: GitBackend>(git: &G) -> Result<LastCommit> {
+    let sha = git
+        .rev_parse("HEAD")
+        .map_err(|_| RfxError::Validation("There are no commits to undo yet.".to_string()))?;
+    let message = last_commit_message(git).unwrap_or_else(|_| "Unknown".to_string());
+    let parents = git.parents(&sha)?;
+    let remotes = pushed_to(git, &sha);
+
+    let plan = if !remotes.is_empty() {
+        UndoPlan::Revert { merge: parents.len() > 1, remotes }
+    } else if parents.is_empty() {
+        UndoPlan::RootCommit
+    } else if parents.len() > 1 {
+        UndoPlan::Merge
+    } else {
+        UndoPlan::SoftReset
+    };
+
+    Ok(LastCommit { sha, message, plan })
+}
+
+/// Undo the last commit using the strategy from `plan_undo_last_commit`
+pub fn undo_last_commit<G: GitBackend>(git: &G, last: &LastCommit) -> Result<String> {
+    // Refuse if HEAD moved while the user was reading the prompt
+    if git.rev_parse("HEAD")? != last.sha {
+        return Err(RfxError::Validation("The last commit changed while rfx was waiting. Please run 'rfx undo' again.".to_string()));
+    }
+
+    let refs = [journal::current_ref(git)];
+    let description = last.message.as_str();
+
+    match &last.plan {
+        UndoPlan::SoftReset => {
+            journal::record(git, OperationKind::Reset, description, &refs, || git.reset(ResetMode::Soft, "HEAD~1"))
+        }
+        // `HEAD~1` does not exist: make the branch unborn again, the index keeps the files
+        UndoPlan::RootCommit => {
+            journal::record(git, OperationKind::Reset, description, &refs, || git.delete_ref(&refs[0]))
+        }
+        // Back to the first parent (the branch before the merge); the merged
+        // changes stay on the other branch
+        UndoPlan::Merge => {
+            journal::record(git, OperationKind::UndoMerge, description, &refs, || git.reset(ResetMode::Keep, "HEAD^1"))
+        }
+        UndoPlan::Revert { merge, .. } => {
+            let mainline = if *merge { Some(1) } else { None };
+            journal::record(git, OperationKind::Revert, description, &refs, || git.revert(&last.sha, mainline))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::fake::{fake_commit, FakeBackend};
+
+    #[test]
+    fn status_splits_staged_and_unstaged_changes() {
+        let git = FakeBackend::new()
+            .with_change("M.", "staged.rs")
+            .with_change(".M", "edited.rs")
+            .with_change("MM", "both.rs")
+            .with_change("??", "new.rs")
+            .with_upstream("main", 2, 1);
+
+        let status = get_status(&git).unwrap();
+        assert_eq!(status.branch, "main");
+        assert_eq!((status.ahead, status.behind), (Some(2), Some(1)));
+
+        let staged: Vec<&str> = status.changes.iter().filter(|c| c.is_staged()).map(|c| c.path.as_str()).collect();
+        let unstaged: Vec<&str> = status.changes.iter().filter(|c| c.is_unstaged()).map(|c| c.path.as_str()).collect();
+        assert_eq!(staged, ["staged.rs", "both.rs"]);
+        assert_eq!(unstaged, ["edited.rs", "both.rs", "new.rs"]);
+        assert_eq!(status.changes[3].worktree, FileState::Untracked);
+    }
+
+    #[test]
+    fn conflicts_are_told_apart_and_never_staged() {
+        let git = FakeBackend::new()
+            .with_change("UU", "both.rs")
+            .with_change("DU", "deleted_by_us.rs")
+            .with_change("UD", "deleted_by_them.rs")
+            .with_change("AA", "added.rs");
+
+        let changes = get_changed_files(&git).unwrap();
+        let kinds: Vec<Option<ConflictKind>> = changes.iter().map(|c| c.conflict).collect();
+        assert_eq!(
+            kinds,
+            [
+                Some(ConflictKind::BothModified),
+                Some(ConflictKind::DeletedByUs),
+                Some(ConflictKind::DeletedByThem),
+                Some(ConflictKind::BothAdded),
+            ]
+        );
+        assert!(changes.iter().all(|c| !c.is_staged() && !c.is_unstaged()));
+    }
+
+    #[test]
+    fn renamed_files_show_where_they_came_from() {
+        let change = FileChange {
+            path: "new.rs".to_string(),
+            index: FileState::Renamed,
+            worktree: FileState::Unmodified,
+            renamed_from: Some("old.rs".to_string()),
+            submodule: false,
+            conflict: None,
+        };
+        assert_eq!(change.display_path(), "old.rs -> new.rs");
+        assert!(change.is_staged() && !change.is_unstaged());
+    }
+
+    #[test]
+    fn status_without_upstream_has_no_counts() {
+        let git = FakeBackend::new();
+        let status = get_status(&git).unwrap();
+        assert_eq!((status.ahead, status.behind), (None, None));
+        assert!(status.changes.is_empty());
+    }
+
+    #[test]
+    fn status_reports_git_failures() {
+        let git = FakeBackend::new().fail_on("status", RfxError::NotARepo);
+        assert!(matches!(get_status(&git), Err(RfxError::NotARepo)));
+    }
+
+    #[test]
+    fn branches_show_creator_and_last_commit() {
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("Second"), fake_commit("First")])
+            .with_branch("empty", Vec::new());
+
+        let branches = branches_detailed(&git).unwrap();
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].name, "main");
+        assert_eq!(branches[0].author, "Fake Author");
+        assert_eq!(branches[0].last_commit, "Second");
+        assert_eq!(branches[1].name, "empty");
+        assert_eq!(branches[1].last_commit, "No commit");
+    }
+
+    #[test]
+    fn commit_subjects_may_contain_pipes() {
+        let git = FakeBackend::new().with_branch("main", vec![fake_commit("Use a | b in docs")]);
+        let commits = commits_detailed(&git, "main", 10).unwrap();
+        assert_eq!(commits[0].message, "Use a | b in docs");
+    }
+
+    #[test]
+    fn remotes_are_split_into_host_owner_and_repo() {
+        let git = FakeBackend::new()
+            .with_remote("origin", "git@github.com:octo/app.git")
+            .with_remote("upstream", "https://gitlab.com/team/app.git");
+
+        let remotes = remotes_detailed(&git).unwrap();
+        assert_eq!(remotes.len(), 4);
+        assert_eq!(remotes[0].direction, "fetch");
+        assert_eq!(remotes[0].host.as_deref(), Some("github.com"));
+        assert_eq!(remotes[0].owner.as_deref(), Some("octo"));
+        assert_eq!(remotes[2].repo.as_deref(), Some("app"));
+        assert_eq!(remote_names(&git).unwrap(), ["origin", "upstream"]);
+    }
+
+    #[test]
+    fn new_branch_names_must_be_valid_and_free() {
+        let git = FakeBackend::new().with_branch("feature", Vec::new());
+
+        assert!(validate_new_branch_name(&git, "fix-login").is_ok());
+        assert!(validate_new_branch_name(&git, "feature").is_err());
+        assert!(validate_new_branch_name(&git, "has space").is_err());
+        assert!(validate_new_branch_name(&git, "").is_err());
+    }
+
+    #[test]
+    fn switching_to_the_current_branch_is_refused() {
+        let git = FakeBackend::new().with_branch("feature", Vec::new()).on_branch("feature");
+        assert!(switch_branch(&git, "feature").is_err());
+        switch_branch(&git, "main").unwrap();
+        assert_eq!(current_branch(&git).unwrap(), "main");
+    }
+
+    #[test]
+    fn remote_branches_are_split_by_remote_name() {
+        let git = FakeBackend::new()
+            .with_remote("origin", "git@github.com:octo/app.git")
+            .with_remote("my/fork", "git@github.com:me/app.git")
+            .with_remote_branch("origin/HEAD", "", "")
+            .with_remote_branch("origin/main", "Ann", "2 days ago")
+            .with_remote_branch("my/fork/feature/x", "Bob", "1 hour ago");
+
+        let branches = get_remote_branches(&git).unwrap();
+        let names: Vec<(&str, &str)> = branches.iter().map(|b| (b.remote.as_str(), b.short_name.as_str())).collect();
+        assert_eq!(names, [("origin", "main"), ("my/fork", "feature/x")]);
+        assert_eq!(git.calls()[0], "fetch");
+    }
+
+    #[test]
+    fn default_branch_follows_the_remote_head() {
+        let git = FakeBackend::new()
+            .with_remote("origin", "git@github.com:octo/app.git")
+            .with_remote_head("origin", "trunk");
+        assert_eq!(default_branch(&git).unwrap(), "trunk");
+
+        let git = FakeBackend::new()
+            .with_branch("develop", Vec::new())
+            .with_config("init.defaultBranch", "develop");
+        assert_eq!(default_branch(&git).unwrap(), "develop");
+    }
+
+    #[test]
+    fn file_preview_compares_with_head() {
+        let diff = "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-old\n+new\n";
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("Initial")])
+            .with_change(".M", "a.rs")
+            .with_diff("a.rs", diff);
+
+        let file = get_changed_files(&git).unwrap().remove(0);
+        let preview = diff::file_preview(&git, &file).unwrap().unwrap();
+        assert_eq!((preview.added, preview.removed), (1, 1));
+    }
+
+    #[test]
+    fn branch_start_points_are_recognised() {
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("Second"), fake_commit("First")])
+            .with_remote("origin", "git@github.com:octo/app.git")
+            .with_remote_branch("origin/main", "Ann", "today")
+            .with_tag("v1.0", "main~1");
+
+        let first = fake_commit("First").hash;
+        assert_eq!(resolve_start(&git, "").unwrap(), BranchStart::Head);
+        assert_eq!(resolve_start(&git, "main").unwrap(), BranchStart::Local("main".to_string()));
+        assert_eq!(resolve_start(&git, "origin/main").unwrap(), BranchStart::Remote("origin/main".to_string()));
+        assert_eq!(resolve_start(&git, "v1.0").unwrap(), BranchStart::Tag("v1.0".to_string()));
+        assert_eq!(resolve_start(&git, &first[..5]).unwrap(), BranchStart::Commit(first));
+        assert!(resolve_start(&git, "nope").is_err());
+    }
+
+    #[test]
+    fn staging_nothing_does_not_call_git() {
+        let git = FakeBackend::new();
+        stage_files(&git, &[]).unwrap();
+        unstage_files(&git, &[]).unwrap();
+        assert!(git.calls().is_empty());
+    }
+
+    #[test]
+    fn commit_needs_staged_changes() {
+        let git = FakeBackend::new().with_change(".M", "a.rs");
+        assert!(matches!(create_commit(&git, "Fix the thing"), Err(RfxError::NothingToCommit)));
+
+        stage_files(&git, &["a.rs".to_string()]).unwrap();
+        create_commit(&git, "Fix the thing").unwrap();
+        assert_eq!(last_commit_message(&git).unwrap(), "Fix the thing");
+        assert!(get_changed_files(&git).unwrap().is_empty());
+    }
+
+    #[test]
+    fn undoing_a_local_commit_keeps_its_changes_staged() {
+        let git = FakeBackend::new().with_branch("main", vec![fake_commit("Second"), fake_commit("First")]);
+
+        let last = plan_undo_last_commit(&git).unwrap();
+        assert_eq!(last.plan, UndoPlan::SoftReset);
+        assert_eq!(last.message, "Second");
+
+        undo_last_commit(&git, &last).unwrap();
+        assert_eq!(last_commit_message(&git).unwrap(), "First");
+        assert!(git.calls().contains(&"reset".to_string()));
+    }
+
+    #[test]
+    fn undoing_the_first_commit_makes_the_branch_unborn() {
+        let git = FakeBackend::new().with_branch("main", vec![fake_commit("First")]);
+
+        let last = plan_undo_last_commit(&git).unwrap();
+        assert_eq!(last.plan, UndoPlan::RootCommit);
+        undo_last_commit(&git, &last).unwrap();
+        assert!(current_commit(&git).is_none());
+    }
+
+    #[test]
+    fn pushed_commits_are_reverted_instead_of_rewritten() {
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("Second"), fake_commit("First")])
+            .with_remote("origin", "git@github.com:octo/app.git");
+        git.push("origin", "main", true).unwrap();
+
+        let last = plan_undo_last_commit(&git).unwrap();
+        assert_eq!(last.plan, UndoPlan::Revert { merge: false, remotes: vec!["origin/main".to_string()] });
+
+        undo_last_commit(&git, &last).unwrap();
+        assert_eq!(last_commit_message(&git).unwrap(), "Revert \"Second\"");
+    }
+
+    #[test]
+    fn nothing_to_undo_without_commits() {
+        let git = FakeBackend::new();
+        assert!(matches!(plan_undo_last_commit(&git), Err(RfxError::Validation(_))));
+    }
+
+    #[test]
+    fn undo_refuses_when_head_moved_meanwhile() {
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("Second"), fake_commit("First")])
+            .with_change(".M", "a.rs");
+        let last = plan_undo_last_commit(&git).unwrap();
+
+        stage_files(&git, &["a.rs".to_string()]).unwrap();
+        create_commit(&git, "Third").unwrap();
+        assert!(matches!(undo_last_commit(&git, &last), Err(RfxError::Validation(_))));
+    }
+
+    #[test]
+    fn switching_to_a_remote_branch_creates_a_tracking_branch() {
+        let git = FakeBackend::new()
+            .with_remote("origin", "git@github.com:octo/app.git")
+            .with_remote_branch("origin/feature/x", "Ann", "today");
+        let remote = get_remote_branches(&git).unwrap().remove(0);
+
+        switch_to_remote_branch(&git, &remote).unwrap();
+        assert_eq!(current_branch(&git).unwrap(), "feature/x");
+        assert!(git.calls().contains(&"checkout_tracking".to_string()));
+
+        // Once it exists locally, switching just checks it out
+        switch_branch(&git, "main").unwrap();
+        switch_to_remote_branch(&git, &remote).unwrap();
+        assert_eq!(git.calls().iter().filter(|c| *c == "checkout_tracking").count(), 1);
+        assert_eq!(current_branch(&git).unwrap(), "feature/x");
+    }
+
+    #[test]
+    fn switches_are_undoable() {
+        let git = FakeBackend::new().with_branch("feature", Vec::new());
+        switch_branch(&git, "feature").unwrap();
+
+        let op = journal::undo(&git).unwrap();
+        assert_eq!(op.kind, OperationKind::Switch);
+        assert_eq!(current_branch(&git).unwrap(), "main");
+    }
+
+    #[test]
+    fn default_remote_prefers_origin() {
+        let git = FakeBackend::new()
+            .with_remote("upstream", "git@github.com:octo/app.git")
+            .with_remote("origin", "git@github.com:me/app.git");
+        assert_eq!(default_remote(&git).unwrap(), "origin");
+
+        let git = FakeBackend::new().with_remote("gitlab", "git@gitlab.com:me/app.git");
+        assert_eq!(default_remote(&git).unwrap(), "gitlab");
+
+        assert!(matches!(default_remote(&FakeBackend::new()), Err(RfxError::Validation(_))));
+    }
+
+    #[test]
+    fn default_branch_falls_back_to_well_known_names() {
+        let git = FakeBackend::new()
+            .with_branch("master", Vec::new())
+            .with_branch("feature", Vec::new())
+            .on_branch("feature");
+        assert_eq!(default_branch(&git).unwrap(), "main");
+        git.delete_ref("refs/heads/main").unwrap();
+        assert_eq!(default_branch(&git).unwrap(), "master");
+
+        // Neither: further down the list, then the checked-out branch
+        git.delete_ref("refs/heads/master").unwrap();
+        let git = git.with_branch("topic", Vec::new());
+        assert_eq!(default_branch(&git).unwrap(), "feature");
+        let git = git.with_branch("trunk", Vec::new());
+        assert_eq!(default_branch(&git).unwrap(), "trunk");
+
+        let git = FakeBackend::new()
+            .with_branch("develop", Vec::new())
+            .with_config("init.defaultBranch", "develop");
+        assert_eq!(default_branch(&git).unwrap(), "develop");
+    }
+
+    #[test]
+    fn default_branch_may_only_exist_on_the_remote() {
+        let git = FakeBackend::new()
+            .with_remote("origin", "git@github.com:octo/app.git")
+            .with_remote_branch("origin/trunk", "Ann", "today")
+            .with_branch("topic", Vec::new());
+        git.delete_ref("refs/heads/main").unwrap();
+        assert_eq!(default_branch(&git).unwrap(), "trunk");
+    }
+
+    #[test]
+    fn default_branch_of_a_lone_unusual_branch() {
+        let git = FakeBackend::new().with_branch("prototype", Vec::new()).on_branch("prototype");
+        git.delete_ref("refs/heads/main").unwrap();
+        assert_eq!(default_branch(&git).unwrap(), "prototype");
+    }
+
+    #[test]
+    fn remote_names_may_contain_slashes() {
+        let names = ["origin".to_string(), "team/fork".to_string()];
+        assert_eq!(split_remote(&names, "team/fork/feature/x"), Some(("team/fork".to_string(), "feature/x".to_string())));
+        assert_eq!(split_remote(&names, "origin/main"), Some(("origin".to_string(), "main".to_string())));
+        // Unknown remote: best guess at the first slash
+        assert_eq!(split_remote(&names, "other/main"), Some(("other".to_string(), "main".to_string())));
+        assert_eq!(split_remote(&names, "main"), None);
+    }
+
+    #[test]
+    fn push_remote_follows_git_config() {
+        let git = FakeBackend::new()
+            .with_config("remote.pushDefault", "origin")
+            .with_config("branch.main.remote", "upstream");
+        assert_eq!(configured_push_remote(&git, "main").unwrap().as_deref(), Some("origin"));
+
+        remember_push_remote(&git, "main", "fork").unwrap();
+        assert_eq!(configured_push_remote(&git, "main").unwrap().as_deref(), Some("fork"));
+
+        // "." means the local repository, not a remote
+        let git = FakeBackend::new().with_config("branch.main.remote", ".");
+        assert_eq!(configured_push_remote(&git, "main").unwrap(), None);
+    }
+
+    #[test]
+    fn only_the_first_push_sets_the_upstream() {
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("First")])
+            .with_remote("origin", "git@github.com:me/app.git");
+
+        let out = push_branch(&git, "main", "origin").unwrap();
+        assert!(out.contains("set up to track"));
+
+        // A fork's branch tracking upstream keeps doing so when pushed to origin
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("First")])
+            .with_config("branch.main.remote", "upstream")
+            .with_config("branch.main.merge", "refs/heads/main");
+        assert_eq!(push_branch(&git, "main", "origin").unwrap(), "");
+        assert_eq!(git.config_get("branch.main.remote").unwrap().as_deref(), Some("upstream"));
+    }
+
+    fn fork() -> FakeBackend {
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("Upstream fix"), fake_commit("First")])
+            .with_branch("feature", vec![fake_commit("First")])
+            .on_branch("feature")
+            .with_remote("origin", "git@github.com:me/app.git")
+            .with_remote("upstream", "git@github.com:octo/app.git")
+            .with_remote_head("upstream", "main");
+        // upstream/main has one commit the local main lacks
+        git.push("upstream", "main", false).unwrap();
+        git.update_ref("refs/heads/main", &fake_commit("First").hash).unwrap();
+        git
+    }
+
+    #[test]
+    fn sync_fast_forwards_and_pushes_to_the_fork() {
+        let git = fork();
+        let report = sync_fork(&git, "upstream", "origin").unwrap();
+        assert_eq!(report.branch, "main");
+        assert!(report.updated);
+        assert_eq!(git.rev_parse("refs/remotes/origin/main").unwrap(), fake_commit("Upstream fix").hash);
+    }
+
+    #[test]
+    fn sync_never_drops_local_commits() {
+        let git = fork().with_branch("main", vec![fake_commit("Mine"), fake_commit("First")]);
+        assert!(matches!(sync_fork(&git, "upstream", "origin"), Err(RfxError::Diverged(_))));
+        assert_eq!(git.last_commit("main").unwrap(), "2024-01-01|Mine");
+        assert!(git.rev_parse("refs/remotes/origin/main").is_err());
+    }
+
+    #[test]
+    fn sync_needs_both_remotes() {
+        let git = fork();
+        assert!(matches!(sync_fork(&git, "upstream", "mine"), Err(RfxError::Validation(_))));
+    }
+
+    #[test]
+    fn pull_strategies_accept_short_names() {
+        assert_eq!(parse_pull_strategy("ff-only"), Some(PullStrategy::FastForwardOnly));
+        assert_eq!(parse_pull_strategy(" FF "), Some(PullStrategy::FastForwardOnly));
+        assert_eq!(parse_pull_strategy("fast-forward"), Some(PullStrategy::FastForwardOnly));
+        assert_eq!(parse_pull_strategy("Rebase"), Some(PullStrategy::Rebase));
+        assert_eq!(parse_pull_strategy("merge"), Some(PullStrategy::Merge));
+        assert_eq!(parse_pull_strategy("squash"), None);
+    }
+
+    #[test]
+    fn pull_strategy_falls_back_to_gits_own_settings() {
+        assert_eq!(configured_pull_strategy(&FakeBackend::new()).unwrap(), None);
+
+        let git = FakeBackend::new().with_config("pull.rebase", "merges");
+        assert_eq!(configured_pull_strategy(&git).unwrap(), Some(PullStrategy::Rebase));
+
+        let git = FakeBackend::new().with_config("pull.rebase", "false");
+        assert_eq!(configured_pull_strategy(&git).unwrap(), Some(PullStrategy::Merge));
+
+        let git = FakeBackend::new().with_config("pull.ff", "only");
+        assert_eq!(configured_pull_strategy(&git).unwrap(), Some(PullStrategy::FastForwardOnly));
+    }
+
+    fn diverged() -> (FakeBackend, RemoteBranchInfo) {
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("Theirs"), fake_commit("First")])
+            .with_remote("origin", "git@github.com:octo/app.git")
+            .with_remote_branch("origin/main", "Ann", "today");
+        git.push("origin", "main", false).unwrap();
+        git.update_ref("refs/heads/main", &fake_commit("First").hash).unwrap();
+        let remote = get_remote_branches(&git).unwrap().remove(0);
+        (git, remote)
+    }
+
+    #[test]
+    fn behind_only_can_fast_forward() {
+        let (git, remote) = diverged();
+        let check = check_pull(&git, &remote).unwrap();
+        assert_eq!((check.ahead, check.behind), (0, 1));
+        assert!(!check.up_to_date());
+        assert!(check.allows(PullStrategy::FastForwardOnly));
+    }
+
+    #[test]
+    fn diverged_branches_need_merge_or_rebase() {
+        let (git, remote) = diverged();
+        let git = git.with_branch("main", vec![fake_commit("Mine"), fake_commit("First")]);
+
+        let check = check_pull(&git, &remote).unwrap();
+        assert_eq!((check.ahead, check.behind), (1, 1));
+        assert!(!check.local_pushed);
+        assert!(!check.allows(PullStrategy::FastForwardOnly));
+        assert!(check.allows(PullStrategy::Rebase) && check.allows(PullStrategy::Merge));
+    }
+
+    #[test]
+    fn name_status_keeps_the_new_path_of_renames() {
+        let files = parse_name_status("M\0b\0A\0new\0R100\0a\0renamed\0D\0sp ace\0");
+        let summary: Vec<(FileState, &str)> = files.iter().map(|f| (f.state, f.path.as_str())).collect();
+        assert_eq!(
+            summary,
+            [
+                (FileState::Modified, "b"),
+                (FileState::Added, "new"),
+                (FileState::Renamed, "renamed"),
+                (FileState::Deleted, "sp ace"),
+            ]
+        );
+        assert!(parse_name_status("").is_empty());
+    }
+
+    #[test]
+    fn pull_preview_lists_incoming_commits() {
+        let (git, remote) = diverged();
+        let preview = preview_pull(&git, &remote).unwrap();
+        let incoming: Vec<&str> = preview.incoming.iter().map(|c| c.message.as_str()).collect();
+        assert_eq!(incoming, ["Theirs"]);
+        assert_eq!(preview.conflicts, Some(Vec::new()));
+    }
+
+    #[test]
+    fn pull_preview_without_conflict_prediction() {
+        let (git, remote) = diverged();
+        let git = git.fail_on("merge_tree", RfxError::Git { code: Some(129), stderr: "usage".to_string() });
+        assert_eq!(preview_pull(&git, &remote).unwrap().conflicts, None);
+    }
+
+    #[test]
+    fn protected_patterns_use_wildcards() {
+        let patterns = vec!["main".to_string(), "release/*".to_string(), "*-stable".to_string()];
+        assert!(is_protected(&patterns, "main"));
+        assert!(is_protected(&patterns, "release/1.0"));
+        assert!(is_protected(&patterns, "2.x-stable"));
+        assert!(!is_protected(&patterns, "mainline"));
+        assert!(!is_protected(&patterns, "hotfix/release/1.0"));
+        assert!(wildcard_match("a*b*c", "aXbYbc"));
+        assert!(!wildcard_match("a*b", "ab-c"));
+    }
+
+    /// `feature` rewritten locally after `count` commits were pushed
+    fn rewritten_feature(count: usize) -> FakeBackend {
+        let mut pushed: Vec<_> = (0..count).map(|n| fake_commit(&format!("Old {}", n))).collect();
+        pushed.push(fake_commit("First"));
+        let git = FakeBackend::new().with_branch("feature", pushed).on_branch("feature");
+        git.push("origin", "feature", true).unwrap();
+        git.with_branch("feature", vec![fake_commit("New"), fake_commit("First")])
+    }
+
+    #[test]
+    fn force_push_plans_count_every_overwritten_commit() {
+        let git = rewritten_feature(2);
+        let plan = plan_force_push(&git, "feature", "origin").unwrap();
+        assert_eq!(plan.local, fake_commit("New").hash);
+        assert_eq!(plan.expected, Some(fake_commit("Old 0").hash));
+        assert_eq!(plan.overwritten.iter().map(|c| c.message.as_str()).collect::<Vec<_>>(), ["Old 0", "Old 1"]);
+        assert_eq!(plan.overwritten_count, 2);
+
+        let git = rewritten_feature(FORCE_PUSH_PREVIEW + 5);
+        let plan = plan_force_push(&git, "feature", "origin").unwrap();
+        assert_eq!(plan.overwritten.len(), FORCE_PUSH_PREVIEW);
+        assert_eq!(plan.overwritten_count, FORCE_PUSH_PREVIEW + 5);
+    }
+
+    #[test]
+    fn protected_branches_are_never_force_pushed() {
+        let git = rewritten_feature(1).with_config("rfx.protectedBranches", "main feat*");
+        assert!(matches!(plan_force_push(&git, "feature", "origin"), Err(RfxError::Validation(_))));
+
+        let git = FakeBackend::new().with_branch("main", vec![fake_commit("First")]);
+        assert!(matches!(plan_force_push(&git, "main", "origin"), Err(RfxError::Validation(_))));
+        assert!(!git.calls().contains(&"push_with_lease".to_string()));
+    }
+
+    #[test]
+    fn pushes_to_protected_branches_are_not_undone() {
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("First")])
+            .with_change(".M", "a.rs");
+        stage_files(&git, &["a.rs".to_string()]).unwrap();
+        create_commit(&git, "Second").unwrap();
+        push_branch(&git, "main", "origin").unwrap();
+
+        assert!(matches!(journal::undo(&git), Err(RfxError::Validation(_))));
+        let commit = journal::load(&git).unwrap()[0].id;
+        assert!(matches!(journal::jump_to(&git, commit), Err(RfxError::Validation(_))));
+        assert!(!git.calls().contains(&"push_with_lease".to_string()));
+        assert!(journal::load(&git).unwrap().iter().all(|op| !op.undone));
+    }
+
+    #[test]
+    fn amending_rewrites_the_last_commit() {
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("Tpyo"), fake_commit("First")])
+            .with_change(".M", "a.rs");
+        let before = current_commit(&git).unwrap();
+        stage_files(&git, &["a.rs".to_string()]).unwrap();
+
+        amend_last_commit(&git, "Typo\n\nFixed.\n\nSigned-off-by: Ann <ann@example.com>", true).unwrap();
+        assert_eq!(last_commit_message(&git).unwrap(), "Typo");
+        assert_eq!(
+            last_commit_full_message(&git).unwrap(),
+            "Typo\n\nFixed.\n\nSigned-off-by: Ann <ann@example.com>"
+        );
+        assert_ne!(current_commit(&git).unwrap(), before);
+        assert!(get_status(&git).unwrap().changes.is_empty());
+
+        let op = journal::load(&git).unwrap().remove(0);
+        assert_eq!((op.kind, op.description.as_str()), (OperationKind::Amend, "\"Typo\""));
+    }
+
+    #[test]
+    fn undoing_an_amend_brings_the_old_commit_back() {
+        let git = FakeBackend::new().with_branch("main", vec![fake_commit("Tpyo"), fake_commit("First")]);
+        amend_last_commit(&git, "Typo", false).unwrap();
+        journal::undo(&git).unwrap();
+        assert_eq!(current_commit(&git), Some(fake_commit("Tpyo").hash));
+        assert_eq!(last_commit_message(&git).unwrap(), "Tpyo");
+    }
+
+    #[test]
+    fn amending_needs_a_commit_and_a_message() {
+        let git = FakeBackend::new();
+        assert!(matches!(amend_last_commit(&git, "Anything", false), Err(RfxError::Validation(_))));
+
+        let git = FakeBackend::new().with_branch("main", vec![fake_commit("First")]);
+        assert!(matches!(amend_last_commit(&git, "  ", false), Err(RfxError::Validation(_))));
+        assert!(!git.calls().contains(&"commit_amend".to_string()));
+    }
+
+    /// `main` with a tag and `origin/main` fetched
+    fn published() -> FakeBackend {
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("Second"), fake_commit("First")])
+            .with_remote("origin", "git@github.com:octo/app.git")
+            .with_remote_branch("origin/main", "Ann", "today")
+            .with_tag("v1.0", &fake_commit("First").hash);
+        git.push("origin", "main", false).unwrap();
+        git
+    }
+
+    #[test]
+    fn branches_from_remote_branches_track_them() {
+        let git = published();
+        let start = resolve_start(&git, "origin/main").unwrap();
+        create_branch(&git, "fix/login", &start).unwrap();
+
+        assert_eq!(current_branch(&git).unwrap(), "fix/login");
+        assert_eq!(git.config_get("branch.fix/login.remote").unwrap().as_deref(), Some("origin"));
+        assert_eq!(git.config_get("branch.fix/login.merge").unwrap().as_deref(), Some("refs/heads/main"));
+        let op = journal::load(&git).unwrap().remove(0);
+        assert_eq!((op.kind, op.description.as_str()), (OperationKind::Branch, "fix/login from origin/main"));
+        assert_eq!(op.branch_name(), "fix/login");
+    }
+
+    #[test]
+    fn undoing_a_branch_from_another_start_removes_it() {
+        let git = published();
+        create_branch(&git, "fix/login", &resolve_start(&git, "origin/main").unwrap()).unwrap();
+
+        let op = journal::undo(&git).unwrap();
+        assert_eq!((op.head_before.as_str(), op.branch_name()), ("main", "fix/login"));
+        assert_eq!(current_branch(&git).unwrap(), "main");
+        assert!(!git.list_branches().unwrap().contains(&"fix/login".to_string()));
+
+        let op = journal::redo(&git).unwrap();
+        assert_eq!(op.branch_name(), "fix/login");
+        assert_eq!(current_branch(&git).unwrap(), "fix/login");
+    }
+
+    #[test]
+    fn branches_from_anything_else_do_not_track() {
+        let git = published();
+        let first = fake_commit("First").hash;
+        let from_commit = format!("d from {}", &first[..7]);
+        for (name, input, description) in [
+            ("a", "", "a"),
+            ("b", "main", "b from main"),
+            ("c", "v1.0", "c from v1.0"),
+            ("d", first.as_str(), from_commit.as_str()),
+        ] {
+            git.checkout("main").unwrap();
+            create_branch(&git, name, &resolve_start(&git, input).unwrap()).unwrap();
+            assert_eq!(git.config_get(&format!("branch.{}.remote", name)).unwrap(), None);
+            assert_eq!(journal::load(&git).unwrap().last().unwrap().description, description);
+        }
+        assert!(matches!(create_branch(&git, "main", &BranchStart::Head), Err(RfxError::Validation(_))));
+    }
+
+    #[test]
+    fn first_push_links_a_branch_to_its_own_remote_branch() {
+        let git = published();
+        create_branch(&git, "fix/login", &BranchStart::Remote("origin/main".to_string())).unwrap();
+
+        assert!(!push_branch(&git, "fix/login", "origin").unwrap().is_empty());
+        assert_eq!(git.config_get("branch.fix/login.merge").unwrap().as_deref(), Some("refs/heads/fix/login"));
+
+        // Later pushes leave the link alone
+        assert_eq!(push_branch(&git, "fix/login", "origin").unwrap(), "");
+    }
+}
@@ -0,0 +1,247 @@
+//! Operations that stopped halfway: merges, rebases, cherry-picks, reverts
+//! and bisects.
+//!
+//! Git keeps their state as files in the `.git` directory (`MERGE_HEAD`,
+//! `rebase-merge/`, ...). rfx reads those to tell the user what is going on
+//! and to pick the right `--continue` or `--abort` command.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::conflict::conflicted_files;
+use super::journal::{self, OperationKind};
+use crate::adapters::{GitBackend, InProgress};
+use crate::error::{Result, RfxError};
+
+/// An interrupted operation and where it stands
+#[derive(Clone, Debug, Serialize)]
+pub struct PendingOperation {
+    pub kind: InProgress,
+    /// Branch the operation works on (for a rebase or bisect, the branch it
+    /// started from; HEAD is detached meanwhile)
+    pub branch: Option<String>,
+    /// What is being merged, picked or reverted, or what a rebase replays onto
+    pub target: Option<String>,
+    /// Rebase progress: (current step, total steps)
+    pub step: Option<(usize, usize)>,
+}
+
+impl PendingOperation {
+    pub fn name(&self) -> &'static str {
+        describe(self.kind)
+    }
+}
+
+pub fn describe(kind: InProgress) -> &'static str {
+    match kind {
+        InProgress::Merge => "merge",
+        InProgress::Rebase => "rebase",
+        InProgress::CherryPick => "cherry-pick",
+        InProgress::Revert => "revert",
+        InProgress::Bisect => "bisect",
+    }
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn short(sha: String) -> String {
+    if sha.len() > 7 { sha[..7].to_string() } else { sha }
+}
+
+/// What is in progress in the repository, if anything
+pub fn detect<G: GitBackend>(git: &G) -> Result<Option<PendingOperation>> {
+    let dir = git.git_dir()?;
+
+    // `git rebase` uses rebase-merge/, `git am` and old-style rebases rebase-apply/
+    for (state, step, total) in [("rebase-merge", "msgnum", "end"), ("rebase-apply", "next", "last")] {
+        let state = dir.join(state);
+        if !state.is_dir() {
+            continue;
+        }
+
+        let number = |name: &str| read_trimmed(&state.join(name)).and_then(|n| n.parse::<usize>().ok());
+        let branch = read_trimmed(&state.join("head-name"))
+            .map(|h| h.strip_prefix("refs/heads/").map(str::to_string).unwrap_or(h));
+
+        return Ok(Some(PendingOperation {
+            kind: InProgress::Rebase,
+            branch,
+            target: read_trimmed(&state.join("onto")).map(short),
+            step: number(step).zip(number(total)),
+        }));
+    }
+
+    let branch = git.current_branch().ok().filter(|b| b != "HEAD");
+
+    for (head, kind) in [
+        ("CHERRY_PICK_HEAD", InProgress::CherryPick),
+        ("REVERT_HEAD", InProgress::Revert),
+        ("MERGE_HEAD", InProgress::Merge),
+    ] {
+        if let Some(sha) = read_trimmed(&dir.join(head)) {
+            // MERGE_HEAD lists one commit per line for octopus merges
+            let first = sha.lines().next().unwrap_or_default().to_string();
+            return Ok(Some(PendingOperation {
+                kind,
+                branch,
+                target: Some(short(first)),
+                step: None,
+            }));
+        }
+    }
+
+    if dir.join("BISECT_LOG").exists() {
+        return Ok(Some(PendingOperation {
+            kind: InProgress::Bisect,
+            // BISECT_START holds the branch to return to
+            branch: read_trimmed(&dir.join("BISECT_START")),
+            target: None,
+            step: None,
+        }));
+    }
+
+    Ok(None)
+}
+
+fn require<G: GitBackend>(git: &G) -> Result<PendingOperation> {
+    detect(git)?.ok_or_else(|| RfxError::Validation("There is no merge, rebase, cherry-pick, revert or bisect in progress.".to_string()))
+}
+
+/// Give up on whatever is in progress and go back to how things were
+pub fn abort_operation<G: GitBackend>(git: &G) -> Result<PendingOperation> {
+    let pending = require(git)?;
+    git.abort_in_progress(pending.kind)?;
+    Ok(pending)
+}
+
+/// Carry on with whatever is in progress once its conflicts are resolved.
+///
+/// Finished merges, cherry-picks and reverts are recorded in the journal
+/// like any other commit.
+pub fn continue_operation<G: GitBackend>(git: &G) -> Result<PendingOperation> {
+    let pending = require(git)?;
+
+    if pending.kind == InProgress::Bisect {
+        return Err(RfxError::Validation(
+            "A bisect has no continue step. Mark the current commit with 'git bisect good' or 'git bisect bad'.".to_string(),
+        ));
+    }
+
+    let remaining = conflicted_files(git)?;
+    if !remaining.is_empty() {
+        return Err(RfxError::Validation(format!(
+            "{} file(s) still have conflicts. Run 'rfx resolve' first.",
+            remaining.len()
+        )));
+    }
+
+    if pending.kind == InProgress::Rebase {
+        git.continue_in_progress(pending.kind)?;
+        return Ok(pending);
+    }
+
+    // Git prepared the message ("Merge branch 'x' ..."); use its first line
+    let message = fs::read_to_string(git.git_dir()?.join("MERGE_MSG")).unwrap_or_default();
+    let summary = message.lines().next().unwrap_or(pending.name()).trim().to_string();
+
+    let refs = [journal::current_ref(git)];
+    journal::record(git, OperationKind::Commit, &format!("\"{}\"", summary), &refs, || {
+        git.continue_in_progress(pending.kind)
+    })?;
+    Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::fake::{fake_commit, FakeBackend};
+
+    fn repo() -> FakeBackend {
+        FakeBackend::new().with_branch("main", vec![fake_commit("Initial")])
+    }
+
+    fn state(git: &FakeBackend, name: &str, content: &str) {
+        let path = git.git_dir().unwrap().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn nothing_in_progress() {
+        let git = repo();
+        assert!(detect(&git).unwrap().is_none());
+        assert!(matches!(abort_operation(&git), Err(RfxError::Validation(_))));
+    }
+
+    #[test]
+    fn reads_an_interactive_rebase() {
+        let git = repo();
+        state(&git, "rebase-merge/head-name", "refs/heads/feature\n");
+        state(&git, "rebase-merge/onto", "0123456789abcdef\n");
+        state(&git, "rebase-merge/msgnum", "2\n");
+        state(&git, "rebase-merge/end", "5\n");
+
+        let pending = detect(&git).unwrap().unwrap();
+        assert_eq!(pending.kind, InProgress::Rebase);
+        assert_eq!(pending.branch.as_deref(), Some("feature"));
+        assert_eq!(pending.target.as_deref(), Some("0123456"));
+        assert_eq!(pending.step, Some((2, 5)));
+    }
+
+    #[test]
+    fn reads_merges_and_picks() {
+        let git = repo();
+        state(&git, "MERGE_HEAD", "aaaaaaaaaa\nbbbbbbbbbb\n");
+        let pending = detect(&git).unwrap().unwrap();
+        assert_eq!((pending.name(), pending.branch.as_deref()), ("merge", Some("main")));
+        assert_eq!(pending.target.as_deref(), Some("aaaaaaa"));
+
+        // A cherry-pick stopped during a merge is reported first
+        state(&git, "CHERRY_PICK_HEAD", "ccccccccc\n");
+        assert_eq!(detect(&git).unwrap().unwrap().kind, InProgress::CherryPick);
+    }
+
+    #[test]
+    fn reads_a_bisect() {
+        let git = repo();
+        state(&git, "BISECT_LOG", "# bad: [abc] Broken\n");
+        state(&git, "BISECT_START", "main\n");
+        let pending = detect(&git).unwrap().unwrap();
+        assert_eq!((pending.kind, pending.branch.as_deref()), (InProgress::Bisect, Some("main")));
+        assert!(matches!(continue_operation(&git), Err(RfxError::Validation(_))));
+    }
+
+    #[test]
+    fn continuing_needs_conflicts_resolved() {
+        let git = repo().with_change("UU", "a.rs");
+        state(&git, "MERGE_HEAD", "aaaaaaaaaa\n");
+        assert!(matches!(continue_operation(&git), Err(RfxError::Validation(_))));
+        assert!(!git.calls().contains(&"continue_in_progress".to_string()));
+    }
+
+    #[test]
+    fn continued_merges_are_journaled() {
+        let git = repo().with_change("M.", "a.rs");
+        git.add(&["a.rs".to_string()]).unwrap();
+        state(&git, "MERGE_HEAD", "aaaaaaaaaa\n");
+        state(&git, "MERGE_MSG", "Merge branch 'feature'\n\n# Conflicts:\n");
+
+        assert_eq!(continue_operation(&git).unwrap().kind, InProgress::Merge);
+        let ops = journal::load(&git).unwrap();
+        assert_eq!(ops.last().unwrap().description, "\"Merge branch 'feature'\"");
+    }
+
+    #[test]
+    fn aborting_calls_through() {
+        let git = repo().with_change("UU", "a.rs");
+        state(&git, "MERGE_HEAD", "aaaaaaaaaa\n");
+        assert_eq!(abort_operation(&git).unwrap().kind, InProgress::Merge);
+        assert!(conflicted_files(&git).unwrap().is_empty());
+    }
+}
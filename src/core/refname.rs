@@ -0,0 +1,243 @@
+//! Branch name checks, so a bad name is explained before git refuses it.
+//!
+//! Covers the rules of `git check-ref-format --branch`, clashes with the
+//! directory-like structure of existing refs (`feature` and `feature/x`
+//! cannot both exist), remote branches, and the team's naming convention
+//! from `branch.naming` in the rfx config.
+
+use regex::Regex;
+
+use super::{config, remote_names};
+use crate::adapters::GitBackend;
+use crate::error::{Result, RfxError};
+
+/// Check a branch name against git's ref name rules
+/// (`git check-ref-format --branch`)
+pub fn check_ref_format(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(RfxError::Validation("Branch name cannot be empty.".to_string()));
+    }
+    if name.contains(char::is_whitespace) {
+        return Err(RfxError::Validation("Branch names cannot contain spaces.".to_string()));
+    }
+    if name == "@" || name == "HEAD" {
+        return Err(RfxError::Validation(format!("'{}' has a special meaning in git and cannot be a branch name.", name)));
+    }
+    if name.starts_with('-') {
+        return Err(RfxError::Validation("Branch names cannot start with '-'.".to_string()));
+    }
+    if let Some(c) = name.chars().find(|c| c.is_ascii_control() || "~^:?*[\\".contains(*c)) {
+        let shown = if c.is_ascii_control() { "control characters".to_string() } else { format!("'{}'", c) };
+        return Err(RfxError::Validation(format!("Branch names cannot contain {}.", shown)));
+    }
+    if name.contains("..") {
+        return Err(RfxError::Validation("Branch names cannot contain '..'.".to_string()));
+    }
+    if name.contains("@{") {
+        return Err(RfxError::Validation("Branch names cannot contain '@{'.".to_string()));
+    }
+    if name.starts_with('/') || name.ends_with('/') || name.contains("//") {
+        return Err(RfxError::Validation(
+            "Branch names cannot start or end with '/' or contain '//'.".to_string(),
+        ));
+    }
+    if name.ends_with('.') {
+        return Err(RfxError::Validation("Branch names cannot end with '.'.".to_string()));
+    }
+
+    for part in name.split('/') {
+        if part.starts_with('.') {
+            return Err(RfxError::Validation(format!("'{}' cannot start with '.' (after each '/' too).", name)));
+        }
+        if part.ends_with(".lock") {
+            return Err(RfxError::Validation(format!("'{}' cannot end with '.lock' (before each '/' too).", name)));
+        }
+    }
+
+    Ok(())
+}
+
+/// `a` and `a/b` cannot both exist: git stores refs as files and folders
+fn path_clash(name: &str, existing: &str) -> bool {
+    existing
+        .strip_prefix(name)
+        .is_some_and(|rest| rest.starts_with('/'))
+        || name
+            .strip_prefix(existing)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Everything a new branch name is checked against, collected up front so
+/// prompt validators can use it without the backend
+#[derive(Clone, Debug)]
+pub struct BranchNameRules {
+    pub locals: Vec<String>,
+    /// Remote branches as `origin/x`
+    pub remote_branches: Vec<String>,
+    pub remotes: Vec<String>,
+    /// Team naming convention (`branch.naming`)
+    pub naming: Option<Regex>,
+}
+
+/// Collect the rules for this repository
+pub fn branch_name_rules<G: GitBackend>(git: &G) -> Result<BranchNameRules> {
+    let naming = match config::load(git)?.text("branch.naming") {
+        Some(pattern) => Some(compile_naming(&pattern)?),
+        None => None,
+    };
+
+    Ok(BranchNameRules {
+        locals: git.list_branches()?,
+        remote_branches: git
+            .list_remote_branches()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|line| line.split('|').next().map(String::from))
+            .filter(|b| !b.ends_with("/HEAD"))
+            .collect(),
+        remotes: remote_names(git).unwrap_or_default(),
+        naming,
+    })
+}
+
+/// Compile a `branch.naming` pattern
+pub fn compile_naming(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| {
+        RfxError::Validation(format!("branch.naming is not a valid pattern: {}", e))
+    })
+}
+
+impl BranchNameRules {
+    pub fn check(&self, name: &str) -> Result<()> {
+        let name = name.trim();
+        check_ref_format(name)?;
+
+        if self.locals.iter().any(|b| b == name) {
+            return Err(RfxError::Validation(format!("A branch named '{}' already exists.", name)));
+        }
+        if let Some(existing) = self.locals.iter().find(|b| path_clash(name, b)) {
+            return Err(RfxError::Validation(format!(
+                "'{}' clashes with the existing branch '{}' (a name cannot be both a branch and a folder of branches).",
+                name, existing
+            )));
+        }
+
+        if let Some(remote) = self.remotes.iter().find(|r| name.starts_with(&format!("{}/", r))) {
+            return Err(RfxError::Validation(format!(
+                "'{}' starts with the remote name '{}/' and would be confused with a remote branch.",
+                name, remote
+            )));
+        }
+        for remote in &self.remotes {
+            let prefix = format!("{}/", remote);
+            let on_remote = self.remote_branches.iter().filter_map(|b| b.strip_prefix(&prefix));
+            for existing in on_remote {
+                if existing == name {
+                    return Err(RfxError::Validation(format!(
+                        "'{}' already exists on {}. Use 'rfx switch {}' to work on it.",
+                        name, remote, name
+                    )));
+                }
+                if path_clash(name, existing) {
+                    return Err(RfxError::Validation(format!(
+                        "'{}' clashes with '{}/{}', so it could never be pushed there.",
+                        name, remote, existing
+                    )));
+                }
+            }
+        }
+
+        if let Some(naming) = &self.naming
+            && !naming.is_match(name)
+        {
+            return Err(RfxError::Validation(format!(
+                "'{}' does not follow this repository's branch naming convention ({}).",
+                name,
+                naming.as_str()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::adapters::fake::{fake_commit, FakeBackend};
+
+    #[test]
+    fn follows_git_check_ref_format() {
+        // Verdicts of `git check-ref-format --branch` for the same names
+        for name in ["feat/login", "fix-1.2", "x@y", "é/ü"] {
+            assert!(check_ref_format(name).is_ok(), "{}", name);
+        }
+        for name in [
+            "", "a/.b", ".hidden", "a.lock", "a.lock/b", "a..b", "a@{b", "-x", "a/", "/a", "a//b", "a.", "a b", "a~1",
+            "a^", "a:b", "a?", "a*", "a[b", "a\\b", "a\u{7f}", "HEAD",
+        ] {
+            assert!(matches!(check_ref_format(name), Err(RfxError::Validation(_))), "{:?}", name);
+        }
+        // git reads `@` as the current branch instead
+        assert!(check_ref_format("@").is_err());
+    }
+
+    fn rules() -> BranchNameRules {
+        BranchNameRules {
+            locals: vec!["main".to_string(), "feature/login".to_string()],
+            remote_branches: vec!["origin/main".to_string(), "origin/docs".to_string(), "origin/team/a".to_string()],
+            remotes: vec!["origin".to_string()],
+            naming: None,
+        }
+    }
+
+    fn fails(rules: &BranchNameRules, name: &str, message: &str) {
+        match rules.check(name) {
+            Err(RfxError::Validation(m)) => assert!(m.contains(message), "{}: {}", name, m),
+            other => panic!("{}: {:?}", name, other),
+        }
+    }
+
+    #[test]
+    fn names_must_not_clash_with_existing_refs() {
+        let rules = rules();
+        assert!(rules.check(" feature/signup ").is_ok());
+        fails(&rules, "main", "already exists");
+        fails(&rules, "feature", "clashes with the existing branch 'feature/login'");
+        fails(&rules, "main/next", "clashes with the existing branch 'main'");
+        fails(&rules, "origin/x", "starts with the remote name");
+        fails(&rules, "docs", "already exists on origin");
+        fails(&rules, "team", "clashes with 'origin/team/a'");
+    }
+
+    #[test]
+    fn names_follow_the_team_convention() {
+        let rules = BranchNameRules {
+            naming: Some(compile_naming("^(feat|fix)/[a-z0-9-]+$").unwrap()),
+            ..rules()
+        };
+        assert!(rules.check("fix/crash-on-start").is_ok());
+        fails(&rules, "Fix/Crash", "naming convention (^(feat|fix)/[a-z0-9-]+$)");
+        assert!(compile_naming("(").is_err());
+    }
+
+    #[test]
+    fn rules_are_collected_from_the_repository() {
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("First")])
+            .with_remote("origin", "git@github.com:octo/app.git")
+            .with_remote_branch("origin/docs", "Ann", "today");
+        let rules = branch_name_rules(&git).unwrap();
+        assert_eq!(rules.locals, ["main"]);
+        assert_eq!(rules.remote_branches, ["origin/docs"]);
+        assert_eq!(rules.remotes, ["origin"]);
+        assert!(rules.naming.is_none());
+
+        let root = git.work_tree().unwrap();
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(config::REPO_FILE), "[branch]\nnaming = \"^feat/\"\n").unwrap();
+        assert_eq!(branch_name_rules(&git).unwrap().naming.unwrap().as_str(), "^feat/");
+    }
+}
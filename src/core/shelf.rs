@@ -0,0 +1,178 @@
+//! The shelf: named stashes for setting work aside and bringing it back.
+//!
+//! Shelved changes are ordinary git stash entries, so `git stash list`
+//! shows them too. rfx names them and previews their files.
+
+use serde::Serialize;
+
+use super::{get_changed_files, parse_name_status, FileState, PathChange};
+use crate::adapters::GitBackend;
+use crate::error::{Result, RfxError};
+
+/// One shelved set of changes
+#[derive(Clone, Debug, Serialize)]
+pub struct ShelfEntry {
+    /// Git's reference for the entry (`stash@{0}`)
+    pub reference: String,
+    pub name: String,
+    /// Branch that was checked out when the changes were shelved
+    pub branch: String,
+    pub age: String,
+    pub files: Vec<PathChange>,
+}
+
+/// Result of taking changes off the shelf
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UnshelveOutcome {
+    /// Everything came back as it was; the entry was removed from the shelf
+    Restored,
+    /// Staged changes could not be restored separately: everything is back,
+    /// but unstaged
+    RestoredUnstaged,
+}
+
+/// Shelve local changes under `name` (an automatic name is used when empty)
+pub fn shelve<G: GitBackend>(git: &G, name: &str, include_untracked: bool) -> Result<()> {
+    let changes = get_changed_files(git)?;
+
+    if changes.iter().any(|f| f.conflict.is_some()) {
+        return Err(RfxError::Validation(
+            "You have unresolved conflicts. Resolve them before shelving.".to_string(),
+        ));
+    }
+
+    let tracked = changes.iter().filter(|f| f.worktree != FileState::Untracked).count();
+    if tracked == 0 && (changes.is_empty() || !include_untracked) {
+        let msg = if changes.is_empty() {
+            "There are no changes to shelve."
+        } else {
+            "Only new (untracked) files changed. Include untracked files to shelve them."
+        };
+        return Err(RfxError::Validation(msg.to_string()));
+    }
+
+    git.stash_push(name.trim(), include_untracked)?;
+    Ok(())
+}
+
+/// Everything on the shelf, newest first, with the files of each entry
+pub fn list_shelf<G: GitBackend>(git: &G) -> Result<Vec<ShelfEntry>> {
+    let mut entries = Vec::new();
+
+    for line in git.stash_list()? {
+        let mut parts = line.splitn(3, '|');
+        let (Some(reference), Some(age), Some(subject)) = (parts.next(), parts.next(), parts.next()) else {
+            continue;
+        };
+
+        let (branch, name) = parse_stash_subject(subject);
+        let files = parse_name_status(&git.stash_files(reference)?);
+
+        entries.push(ShelfEntry {
+            reference: reference.to_string(),
+            name,
+            branch,
+            age: age.to_string(),
+            files,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Bring a shelf entry back into the working directory.
+///
+/// The entry stays on the shelf if anything goes wrong (including
+/// conflicts), so nothing is ever lost.
+pub fn unshelve<G: GitBackend>(git: &G, reference: &str) -> Result<UnshelveOutcome> {
+    match git.stash_pop(reference, true) {
+        Ok(_) => Ok(UnshelveOutcome::Restored),
+        // The staged part no longer applies cleanly on its own: retry
+        // without keeping the staged/unstaged split
+        Err(RfxError::Git { stderr, .. }) if stderr.contains("--index") => {
+            git.stash_pop(reference, false)?;
+            Ok(UnshelveOutcome::RestoredUnstaged)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// `On main: name` / `WIP on main: 1a2b3c4 subject` -> (branch, name)
+fn parse_stash_subject(subject: &str) -> (String, String) {
+    let rest = subject
+        .strip_prefix("WIP on ")
+        .or_else(|| subject.strip_prefix("On "))
+        .unwrap_or(subject);
+
+    match rest.split_once(": ") {
+        Some((branch, name)) if subject.starts_with("WIP on ") => {
+            (branch.to_string(), format!("(unnamed, at {})", name))
+        }
+        Some((branch, name)) => (branch.to_string(), name.to_string()),
+        None => (String::new(), rest.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::fake::FakeBackend;
+
+    #[test]
+    fn shelved_changes_come_back() {
+        let git = FakeBackend::new().with_change(".M", "a.rs").with_change("A.", "b.rs");
+
+        shelve(&git, " login form ", false).unwrap();
+        assert!(get_changed_files(&git).unwrap().is_empty());
+
+        let shelf = list_shelf(&git).unwrap();
+        assert_eq!(shelf.len(), 1);
+        assert_eq!(shelf[0].reference, "stash@{0}");
+        assert_eq!((shelf[0].branch.as_str(), shelf[0].name.as_str()), ("main", "login form"));
+        let files: Vec<&str> = shelf[0].files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(files, ["a.rs", "b.rs"]);
+
+        assert_eq!(unshelve(&git, "stash@{0}").unwrap(), UnshelveOutcome::Restored);
+        assert_eq!(get_changed_files(&git).unwrap().len(), 2);
+        assert!(list_shelf(&git).unwrap().is_empty());
+    }
+
+    #[test]
+    fn untracked_files_are_only_shelved_on_request() {
+        let git = FakeBackend::new().with_change("??", "notes.txt");
+        assert!(matches!(shelve(&git, "notes", false), Err(RfxError::Validation(_))));
+
+        shelve(&git, "notes", true).unwrap();
+        assert!(get_changed_files(&git).unwrap().is_empty());
+    }
+
+    #[test]
+    fn nothing_or_conflicts_cannot_be_shelved() {
+        assert!(shelve(&FakeBackend::new(), "", false).is_err());
+
+        let git = FakeBackend::new().with_change("UU", "a.rs");
+        assert!(matches!(shelve(&git, "", false), Err(RfxError::Validation(_))));
+        assert!(!git.calls().contains(&"stash_push".to_string()));
+    }
+
+    #[test]
+    fn failed_unshelve_keeps_the_entry() {
+        let git = FakeBackend::new()
+            .with_change(".M", "a.rs")
+            .fail_on("stash_pop", RfxError::MergeConflict("CONFLICT (content)".to_string()));
+        shelve(&git, "wip", false).unwrap();
+
+        assert!(matches!(unshelve(&git, "stash@{0}"), Err(RfxError::MergeConflict(_))));
+        assert_eq!(list_shelf(&git).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn stash_subjects_give_branch_and_name() {
+        assert_eq!(parse_stash_subject("On main: login form"), ("main".to_string(), "login form".to_string()));
+        assert_eq!(
+            parse_stash_subject("WIP on feature/x: 1a2b3c4 Fix typo"),
+            ("feature/x".to_string(), "(unnamed, at 1a2b3c4 Fix typo)".to_string())
+        );
+        assert_eq!(parse_stash_subject("custom"), (String::new(), "custom".to_string()));
+    }
+}
@@ -0,0 +1,311 @@
+//! The trash: copies of discarded changes, so throwing edits away is never
+//! final.
+//!
+//! Each discard gets its own directory under `.git/rfx/trash/<id>/` with a
+//! `manifest.json` describing the files and a `files/` tree holding their
+//! contents as they were just before being discarded.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::journal;
+use super::{current_commit, FileChange, FileState};
+use crate::adapters::GitBackend;
+use crate::error::{Result, RfxError};
+
+/// One discarded file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrashedFile {
+    pub path: String,
+    /// The file was new (untracked or newly added) and has been deleted
+    pub new_file: bool,
+    /// A copy of the contents was kept (false when the file had been deleted)
+    pub saved: bool,
+}
+
+/// Everything thrown away by one `rfx discard`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub branch: String,
+    pub files: Vec<TrashedFile>,
+}
+
+fn trash_dir<G: GitBackend>(git: &G) -> Result<PathBuf> {
+    Ok(git.git_dir()?.join("rfx").join("trash"))
+}
+
+/// Copy a file, or a whole directory for untracked folders
+fn copy_path(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Throw away all changes (staged and unstaged) of the given files, keeping
+/// a copy of their current contents in the trash first.
+///
+/// New files are deleted; changed and deleted files go back to their last
+/// committed version.
+pub fn discard<G: GitBackend>(git: &G, files: &[FileChange]) -> Result<TrashEntry> {
+    if files.is_empty() {
+        return Err(RfxError::Validation("No files selected.".to_string()));
+    }
+    if let Some(f) = files.iter().find(|f| f.conflict.is_some()) {
+        return Err(RfxError::Validation(format!(
+            "'{}' has merge conflicts. Use 'rfx resolve' or 'rfx abort' instead.",
+            f.path
+        )));
+    }
+    if let Some(f) = files.iter().find(|f| f.submodule) {
+        return Err(RfxError::Validation(format!("'{}' is a submodule; rfx cannot discard it.", f.path)));
+    }
+
+    let is_new = |f: &FileChange| f.worktree == FileState::Untracked || f.index == FileState::Added;
+    let has_head = current_commit(git).is_some();
+    if !has_head && files.iter().any(|f| !is_new(f)) {
+        return Err(RfxError::Validation("There is no commit to go back to yet.".to_string()));
+    }
+
+    let work_tree = git.work_tree()?;
+    let root = trash_dir(git)?;
+    let timestamp = journal::now();
+
+    // Two discards within the same second get separate directories
+    let mut id = timestamp.to_string();
+    let mut n = 1;
+    while root.join(&id).exists() {
+        n += 1;
+        id = format!("{}-{}", timestamp, n);
+    }
+    let entry_dir = root.join(&id);
+
+    // 1. Copy everything first, so nothing is touched if copying fails
+    let mut trashed = Vec::new();
+    for file in files {
+        let source = work_tree.join(&file.path);
+        let saved = source.exists();
+        if saved {
+            copy_path(&source, &entry_dir.join("files").join(&file.path))?;
+        }
+        trashed.push(TrashedFile {
+            path: file.path.clone(),
+            new_file: is_new(file),
+            saved,
+        });
+    }
+
+    let entry = TrashEntry {
+        id,
+        timestamp,
+        branch: git.current_branch().unwrap_or_default(),
+        files: trashed,
+    };
+    let json = serde_json::to_string_pretty(&entry).map_err(|e| RfxError::Io(e.to_string()))?;
+    fs::write(entry_dir.join("manifest.json"), json)?;
+
+    // 2. Throw the changes away
+    let tracked: Vec<String> = files
+        .iter()
+        .filter(|f| f.worktree != FileState::Untracked)
+        .flat_map(|f| std::iter::once(f.path.clone()).chain(f.renamed_from.clone()))
+        .collect();
+    if !tracked.is_empty() {
+        if has_head {
+            git.restore_files(&tracked)?;
+        } else {
+            git.unstage(&tracked)?;
+        }
+    }
+
+    // Untracked files, and added files git no longer knows after the restore
+    for file in files.iter().filter(|f| is_new(f)) {
+        remove_path(&work_tree.join(&file.path))?;
+    }
+
+    Ok(entry)
+}
+
+/// Everything in the trash, newest first
+pub fn list_trash<G: GitBackend>(git: &G) -> Result<Vec<TrashEntry>> {
+    let root = trash_dir(git)?;
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for dir in fs::read_dir(&root)? {
+        let manifest = dir?.path().join("manifest.json");
+        let Ok(raw) = fs::read_to_string(&manifest) else {
+            continue;
+        };
+        match serde_json::from_str::<TrashEntry>(&raw) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                return Err(RfxError::Io(format!("{} is corrupted: {}", manifest.display(), e)));
+            }
+        }
+    }
+
+    entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(b.id.cmp(&a.id)));
+    Ok(entries)
+}
+
+/// Files of a trash entry that would overwrite different current contents
+pub fn restore_conflicts<G: GitBackend>(git: &G, entry: &TrashEntry) -> Result<Vec<String>> {
+    let work_tree = git.work_tree()?;
+    let entry_dir = trash_dir(git)?.join(&entry.id).join("files");
+
+    let mut clashes = Vec::new();
+    for file in entry.files.iter().filter(|f| f.saved) {
+        let current = work_tree.join(&file.path);
+        let saved = entry_dir.join(&file.path);
+        let differs = match (fs::read(&current), fs::read(&saved)) {
+            (Ok(a), Ok(b)) => a != b,
+            // Directories and missing files: only a clash if something is there
+            _ => current.exists() && !current.is_dir(),
+        };
+        if differs {
+            clashes.push(file.path.clone());
+        }
+    }
+    Ok(clashes)
+}
+
+/// Put the files of a trash entry back into the working directory and
+/// remove the entry from the trash
+pub fn restore<G: GitBackend>(git: &G, entry: &TrashEntry) -> Result<usize> {
+    let work_tree = git.work_tree()?;
+    let entry_dir = trash_dir(git)?.join(&entry.id);
+
+    let mut restored = 0;
+    for file in entry.files.iter().filter(|f| f.saved) {
+        let target = work_tree.join(&file.path);
+        if target.is_file() {
+            fs::remove_file(&target)?;
+        }
+        copy_path(&entry_dir.join("files").join(&file.path), &target)?;
+        restored += 1;
+    }
+
+    fs::remove_dir_all(&entry_dir)?;
+    Ok(restored)
+}
+
+/// Delete everything in the trash for good; returns how many entries were removed
+pub fn empty_trash<G: GitBackend>(git: &G) -> Result<usize> {
+    let count = list_trash(git)?.len();
+    let root = trash_dir(git)?;
+    if root.exists() {
+        fs::remove_dir_all(&root)?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::fake::{fake_commit, FakeBackend};
+    use crate::core::get_changed_files;
+
+    fn write(git: &FakeBackend, path: &str, content: &str) {
+        let full = git.work_tree().unwrap().join(path);
+        fs::create_dir_all(full.parent().unwrap()).unwrap();
+        fs::write(full, content).unwrap();
+    }
+
+    fn read(git: &FakeBackend, path: &str) -> Option<String> {
+        fs::read_to_string(git.work_tree().unwrap().join(path)).ok()
+    }
+
+    fn repo() -> FakeBackend {
+        let git = FakeBackend::new()
+            .with_branch("main", vec![fake_commit("First")])
+            .with_change(".M", "edited.rs")
+            .with_change("??", "notes/");
+        write(&git, "edited.rs", "my edit\n");
+        write(&git, "notes/todo.txt", "buy milk\n");
+        git
+    }
+
+    #[test]
+    fn discarded_changes_are_kept_in_the_trash() {
+        let git = repo();
+        let entry = discard(&git, &get_changed_files(&git).unwrap()).unwrap();
+
+        assert_eq!(entry.branch, "main");
+        let files: Vec<(&str, bool, bool)> = entry.files.iter().map(|f| (f.path.as_str(), f.new_file, f.saved)).collect();
+        assert_eq!(files, [("edited.rs", false, true), ("notes/", true, true)]);
+        assert!(git.calls().contains(&"restore_files".to_string()));
+        assert!(!git.work_tree().unwrap().join("notes").exists());
+
+        let trashed = list_trash(&git).unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].id, entry.id);
+    }
+
+    #[test]
+    fn restoring_puts_the_files_back() {
+        let git = repo();
+        let entry = discard(&git, &get_changed_files(&git).unwrap()).unwrap();
+        // What the restore to HEAD would have left behind
+        write(&git, "edited.rs", "committed\n");
+
+        assert_eq!(restore_conflicts(&git, &entry).unwrap(), ["edited.rs"]);
+        assert_eq!(restore(&git, &entry).unwrap(), 2);
+        assert_eq!(read(&git, "edited.rs").as_deref(), Some("my edit\n"));
+        assert_eq!(read(&git, "notes/todo.txt").as_deref(), Some("buy milk\n"));
+        assert!(list_trash(&git).unwrap().is_empty());
+    }
+
+    #[test]
+    fn every_discard_gets_its_own_entry() {
+        let git = repo();
+        let files = get_changed_files(&git).unwrap();
+        let first = discard(&git, &files[..1]).unwrap();
+        write(&git, "edited.rs", "again\n");
+        let second = discard(&git, &files[..1]).unwrap();
+
+        assert_ne!(first.id, second.id);
+        assert_eq!(list_trash(&git).unwrap().len(), 2);
+        assert_eq!(empty_trash(&git).unwrap(), 2);
+        assert!(list_trash(&git).unwrap().is_empty());
+    }
+
+    #[test]
+    fn refuses_what_it_cannot_bring_back() {
+        let git = repo();
+        assert!(matches!(discard(&git, &[]), Err(RfxError::Validation(_))));
+
+        let git = FakeBackend::new().with_branch("main", vec![fake_commit("First")]).with_change("UU", "a.rs");
+        assert!(matches!(discard(&git, &get_changed_files(&git).unwrap()), Err(RfxError::Validation(_))));
+
+        // Before the first commit only new files can go
+        let git = FakeBackend::new().with_change("AM", "a.rs").with_change(".M", "b.rs");
+        assert!(matches!(discard(&git, &get_changed_files(&git).unwrap()), Err(RfxError::Validation(_))));
+        assert!(list_trash(&git).unwrap().is_empty());
+    }
+}
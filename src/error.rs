@@ -0,0 +1,363 @@
+use std::fmt;
+
+/// Everything that can go wrong inside rfx.
+///
+/// Git failures are classified from the exit code and stderr of the
+/// underlying command, so callers can branch on the kind of failure
+/// instead of searching error strings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RfxError {
+    /// The `git` executable could not be found on the PATH.
+    GitNotFound,
+    /// The working directory is not inside a git repository.
+    NotARepo,
+    /// The branch has no upstream (tracking) branch configured.
+    NoUpstream,
+    /// The remote refused the push (usually non-fast-forward).
+    PushRejected(String),
+    /// A merge stopped because of conflicting changes.
+    MergeConflict(String),
+    /// Both sides have new commits, so a fast-forward is impossible.
+    Diverged(String),
+    /// The remote rejected our credentials.
+    AuthFailure(String),
+    /// The remote could not be reached.
+    Network(String),
+    /// A branch, commit or other revision does not exist or is malformed.
+    InvalidRef(String),
+    /// Local changes would be overwritten by the operation.
+    DirtyWorktree(String),
+    /// There was nothing staged to commit.
+    NothingToCommit,
+    /// Input rejected by one of rfx's own rules.
+    Validation(String),
+    /// Reading or writing a file failed.
+    Io(String),
+    /// Any other git failure, with the raw details.
+    Git { code: Option<i32>, stderr: String },
+}
+
+pub type Result<T> = std::result::Result<T, RfxError>;
+
+impl RfxError {
+    /// Classify a failed git invocation from its exit code and output.
+    ///
+    /// `output` should contain both stderr and stdout: some failures
+    /// (merge conflicts in particular) are only reported on stdout.
+    pub fn from_git_failure(code: Option<i32>, output: &str) -> RfxError {
+        let lower = output.to_lowercase();
+        let detail = output.trim().to_string();
+
+        if lower.contains("not a git repository") {
+            return RfxError::NotARepo;
+        }
+
+        // Credentials first: auth failures also mention "could not read from remote".
+        if lower.contains("authentication failed")
+            || lower.contains("permission denied (publickey")
+            || lower.contains("could not read username")
+            || lower.contains("could not read password")
+            || lower.contains("invalid username or password")
+            || lower.contains("the requested url returned error: 403")
+        {
+            return RfxError::AuthFailure(detail);
+        }
+
+        if lower.contains("could not resolve host")
+            || lower.contains("could not read from remote repository")
+            || lower.contains("connection refused")
+            || lower.contains("connection timed out")
+            || lower.contains("operation timed out")
+            || lower.contains("network is unreachable")
+            || lower.contains("unable to access")
+        {
+            return RfxError::Network(detail);
+        }
+
+        if lower.contains("[rejected]")
+            || lower.contains("failed to push some refs")
+            || lower.contains("non-fast-forward")
+            || lower.contains("fetch first")
+        {
+            return RfxError::PushRejected(detail);
+        }
+
+        if lower.contains("not possible to fast-forward")
+            || lower.contains("diverging branches can't be fast-forwarded")
+            // git >= 2.27 without pull.rebase / pull.ff configured
+            || lower.contains("reconcile divergent branches")
+        {
+            return RfxError::Diverged(detail);
+        }
+
+        if output.contains("CONFLICT")
+            || lower.contains("automatic merge failed")
+            || lower.contains("fix conflicts")
+            || lower.contains("unmerged files")
+        {
+            return RfxError::MergeConflict(detail);
+        }
+
+        if lower.contains("no upstream configured")
+            || lower.contains("has no upstream branch")
+            || lower.contains("no upstream branch")
+            || lower.contains("no such branch: ''")
+            || lower.contains("there is no tracking information")
+        {
+            return RfxError::NoUpstream;
+        }
+
+        if lower.contains("would be overwritten")
+            || lower.contains("please commit your changes or stash them")
+            || lower.contains("already exists, no checkout")
+        {
+            return RfxError::DirtyWorktree(detail);
+        }
+
+        if lower.contains("nothing to commit")
+            || lower.contains("no changes added to commit")
+        {
+            return RfxError::NothingToCommit;
+        }
+
+        if lower.contains("ambiguous argument")
+            || lower.contains("unknown revision")
+            || lower.contains("not a valid object name")
+            || lower.contains("not a valid branch name")
+            || lower.contains("not a valid ref")
+            || lower.contains("invalid reference")
+            || lower.contains("bad revision")
+            || lower.contains("couldn't find remote ref")
+            || lower.contains("is not a commit")
+        {
+            return RfxError::InvalidRef(detail);
+        }
+
+        RfxError::Git { code, stderr: detail }
+    }
+
+    /// Short machine-friendly name of the error kind (used in JSON output and scripts).
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RfxError::GitNotFound => "git-not-found",
+            RfxError::NotARepo => "not-a-repo",
+            RfxError::NoUpstream => "no-upstream",
+            RfxError::PushRejected(_) => "push-rejected",
+            RfxError::MergeConflict(_) => "merge-conflict",
+            RfxError::Diverged(_) => "diverged",
+            RfxError::AuthFailure(_) => "auth-failure",
+            RfxError::Network(_) => "network",
+            RfxError::InvalidRef(_) => "invalid-ref",
+            RfxError::DirtyWorktree(_) => "dirty-worktree",
+            RfxError::NothingToCommit => "nothing-to-commit",
+            RfxError::Validation(_) => "validation",
+            RfxError::Io(_) => "io",
+            RfxError::Git { .. } => "git",
+        }
+    }
+}
+
+impl fmt::Display for RfxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RfxError::GitNotFound => write!(f, "Git is not installed (could not find 'git' on your PATH)."),
+            RfxError::NotARepo => write!(f, "This is not a git repository."),
+            RfxError::NoUpstream => write!(f, "This branch is not linked to a remote branch yet."),
+            RfxError::PushRejected(d) => write!(f, "The remote rejected the push.\n{}", d),
+            RfxError::MergeConflict(d) => write!(f, "The changes could not be combined automatically.\n{}", d),
+            RfxError::Diverged(d) => write!(f, "Your branch and the remote branch both have new commits.\n{}", d),
+            RfxError::AuthFailure(d) => write!(f, "The remote rejected your credentials.\n{}", d),
+            RfxError::Network(d) => write!(f, "Could not connect to the remote server.\n{}", d),
+            RfxError::InvalidRef(d) => write!(f, "Unknown branch or commit.\n{}", d),
+            RfxError::DirtyWorktree(d) => write!(f, "Your uncommitted changes would be overwritten.\n{}", d),
+            RfxError::NothingToCommit => write!(f, "There is nothing to commit."),
+            RfxError::Validation(msg) => write!(f, "{}", msg),
+            RfxError::Io(msg) => write!(f, "{}", msg),
+            RfxError::Git { stderr, .. } => write!(f, "{}", stderr),
+        }
+    }
+}
+
+impl std::error::Error for RfxError {}
+
+impl From<std::io::Error> for RfxError {
+    fn from(e: std::io::Error) -> Self {
+        RfxError::Io(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Output captured from real git runs (GitHub for the auth cases)
+
+    const NOT_A_REPO: &str = "fatal: not a git repository (or any of the parent directories): .git";
+
+    const HTTPS_AUTH: &str = "remote: Invalid username or password.
+fatal: Authentication failed for 'https://github.com/octo/app.git/'";
+
+    const HTTPS_403: &str = "remote: Permission to octo/app.git denied to someone.
+fatal: unable to access 'https://github.com/octo/app.git/': The requested URL returned error: 403";
+
+    const SSH_AUTH: &str = "git@github.com: Permission denied (publickey).
+fatal: Could not read from remote repository.
+
+Please make sure you have the correct access rights
+and the repository exists.";
+
+    const NO_USERNAME: &str = "fatal: could not read Username for 'https://github.com': terminal prompts disabled";
+
+    const UNKNOWN_HOST: &str = "fatal: unable to access 'https://nonexistent.invalid/x.git/': Could not resolve host: nonexistent.invalid";
+
+    const SSH_REFUSED: &str = "ssh: connect to host 127.0.0.1 port 1: Connection refused
+fatal: Could not read from remote repository.
+
+Please make sure you have the correct access rights
+and the repository exists.";
+
+    const PUSH_REJECTED: &str = "To /tmp/fxr
+ ! [rejected]        main -> main (non-fast-forward)
+error: failed to push some refs to '/tmp/fxr'
+hint: Updates were rejected because the tip of your current branch is behind
+hint: its remote counterpart. Integrate the remote changes (e.g.
+hint: 'git pull ...') before pushing again.
+hint: See the 'Note about fast-forwards' in 'git push --help' for details.";
+
+    const NOT_FAST_FORWARD: &str = "From /tmp/fxr
+   5aba1c4..153e54c  main       -> origin/main
+fatal: Not possible to fast-forward, aborting.";
+
+    const NEED_RECONCILE: &str = "hint: You have divergent branches and need to specify how to reconcile them.
+hint: You can do so by running one of the following commands sometime before
+hint: your next pull:
+hint: 
+hint:   git config pull.rebase false  # merge
+hint:   git config pull.rebase true   # rebase
+hint:   git config pull.ff only       # fast-forward only
+hint: 
+hint: You can replace \"git config\" with \"git config --global\" to set a default
+hint: preference for all repositories. You can also pass --rebase, --no-rebase,
+hint: or --ff-only on the command line to override the configured default per
+hint: invocation.
+fatal: Need to specify how to reconcile divergent branches.";
+
+    const MERGE_CONFLICT: &str = "Auto-merging a
+CONFLICT (content): Merge conflict in a
+Automatic merge failed; fix conflicts and then commit the result.";
+
+    const REBASE_CONFLICT: &str = "From /tmp/fxr
+   153e54c..65c3cfc  main       -> origin/main
+Rebasing (1/2)Rebasing (2/2)Auto-merging a
+CONFLICT (content): Merge conflict in a
+error: could not apply c73ce63... w
+hint: Resolve all conflicts manually, mark them as resolved with
+hint: \"git add/rm <conflicted_files>\", then run \"git rebase --continue\".
+hint: You can instead skip this commit: run \"git rebase --skip\".
+hint: To abort and get back to the state before \"git rebase\", run \"git rebase --abort\".
+Could not apply c73ce63... w";
+
+    const PUSH_NO_UPSTREAM: &str = "fatal: The current branch feat has no upstream branch.
+To push the current branch and set the remote as upstream, use
+
+    git push --set-upstream origin feat
+";
+
+    const PULL_NO_UPSTREAM: &str = "There is no tracking information for the current branch.
+Please specify which branch you want to merge with.
+See git-pull(1) for details.
+
+    git pull <remote> <branch>
+
+If you wish to set tracking information for this branch you can do so with:
+
+    git branch --set-upstream-to=origin/<branch> feat
+";
+
+    const REV_PARSE_NO_UPSTREAM: &str = "fatal: no upstream configured for branch 'feat'";
+
+    const DIRTY_CHECKOUT: &str = "error: Your local changes to the following files would be overwritten by checkout:
+\ta
+Please commit your changes or stash them before you switch branches.
+Aborting";
+
+    const NOTHING_TO_COMMIT: &str = "On branch main
+Your branch and 'origin/main' have diverged,
+and have 2 and 2 different commits each, respectively.
+  (use \"git pull\" to merge the remote branch into yours)
+
+nothing to commit, working tree clean";
+
+    const UNKNOWN_REVISION: &str = "fatal: ambiguous argument 'nope': unknown revision or path not in the working tree.
+Use '--' to separate paths from revisions, like this:
+'git <command> [<revision>...] -- [<file>...]'";
+
+    const BAD_START_POINT: &str = "fatal: 'nope' is not a commit and a branch 'x' cannot be created from it";
+
+    const NO_REMOTE_REF: &str = "fatal: couldn't find remote ref nope";
+
+    fn kind_of(output: &str) -> &'static str {
+        RfxError::from_git_failure(Some(128), output).kind()
+    }
+
+    #[test]
+    fn classifies_repository_errors() {
+        assert_eq!(RfxError::from_git_failure(Some(128), NOT_A_REPO), RfxError::NotARepo);
+    }
+
+    #[test]
+    fn credentials_win_over_network() {
+        assert_eq!(kind_of(HTTPS_AUTH), "auth-failure");
+        assert_eq!(kind_of(NO_USERNAME), "auth-failure");
+        // Both also say "could not read from remote" / "unable to access"
+        assert_eq!(kind_of(SSH_AUTH), "auth-failure");
+        assert_eq!(kind_of(HTTPS_403), "auth-failure");
+    }
+
+    #[test]
+    fn classifies_network_errors() {
+        assert_eq!(kind_of(UNKNOWN_HOST), "network");
+        assert_eq!(kind_of(SSH_REFUSED), "network");
+    }
+
+    #[test]
+    fn classifies_push_and_pull_failures() {
+        assert_eq!(kind_of(PUSH_REJECTED), "push-rejected");
+        assert_eq!(kind_of(NOT_FAST_FORWARD), "diverged");
+        assert_eq!(kind_of(NEED_RECONCILE), "diverged");
+    }
+
+    #[test]
+    fn conflicts_win_over_other_hints() {
+        assert_eq!(kind_of(MERGE_CONFLICT), "merge-conflict");
+        assert_eq!(kind_of(REBASE_CONFLICT), "merge-conflict");
+    }
+
+    #[test]
+    fn no_upstream_is_not_mistaken_for_a_conflict() {
+        // "Please specify which branch you want to merge with" mentions merging
+        assert_eq!(RfxError::from_git_failure(Some(1), PULL_NO_UPSTREAM), RfxError::NoUpstream);
+        assert_eq!(RfxError::from_git_failure(Some(128), PUSH_NO_UPSTREAM), RfxError::NoUpstream);
+        assert_eq!(RfxError::from_git_failure(Some(128), REV_PARSE_NO_UPSTREAM), RfxError::NoUpstream);
+    }
+
+    #[test]
+    fn classifies_local_state_errors() {
+        assert_eq!(kind_of(DIRTY_CHECKOUT), "dirty-worktree");
+        // Mentions diverged branches, but only in the status summary
+        assert_eq!(RfxError::from_git_failure(Some(1), NOTHING_TO_COMMIT), RfxError::NothingToCommit);
+    }
+
+    #[test]
+    fn classifies_unknown_revisions() {
+        assert_eq!(kind_of(UNKNOWN_REVISION), "invalid-ref");
+        assert_eq!(kind_of(BAD_START_POINT), "invalid-ref");
+        assert_eq!(kind_of(NO_REMOTE_REF), "invalid-ref");
+    }
+
+    #[test]
+    fn keeps_unrecognised_output() {
+        let err = RfxError::from_git_failure(Some(129), "usage: git frobnicate\n");
+        assert_eq!(err, RfxError::Git { code: Some(129), stderr: "usage: git frobnicate".to_string() });
+    }
+}
@@ -1,7 +1,10 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand, ValueEnum};
 
 mod adapters;
 mod core;
+mod error;
 mod ui;
 
 /// Resolve CLI - rfx
@@ -9,6 +12,10 @@ mod ui;
 #[command(name = "rfx")]
 #[command(about = "A beginner-friendly tool to fix Git workflow issues", long_about = None)]
 struct Cli {
+    /// Run as if rfx was started in <PATH> instead of the current directory
+    #[arg(short = 'C', long = "repo", value_name = "PATH", global = true)]
+    repo: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -16,9 +23,19 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Pull changes safely
-    Pull,
+    Pull {
+        /// How to combine diverged branches (default: pull.strategy from .rfx.toml
+        /// or the user config, or ask)
+        #[arg(long, value_enum)]
+        strategy: Option<PullStrategyArg>,
+    },
     /// Push changes safely
-    Push,
+    Push {
+        /// Replace the remote branch (after an amend or rebase); refuses
+        /// protected branches and never overwrites commits rfx has not shown
+        #[arg(long)]
+        force: bool,
+    },
 
     Show {
         #[arg(value_enum)]
@@ -28,9 +45,9 @@ enum Commands {
         #[arg(long)]
         json: bool,
 
-        /// Branch name for commits (optional)
-        #[arg(long, default_value = "main")]
-        branch: String,
+        /// Branch name for commits (defaults to the current branch)
+        #[arg(long)]
+        branch: Option<String>,
 
         /// Number of commits to show
         #[arg(long, default_value_t = 10)]
@@ -45,7 +62,124 @@ enum Commands {
     
     Status,
 
+    /// Show what changed: unstaged by default, staged with --staged, or
+    /// between two branches or commits
+    Diff {
+        /// Branch or commit to compare from (alone: compared with your files)
+        from: Option<String>,
+
+        /// Branch or commit to compare to
+        to: Option<String>,
+
+        /// Show staged changes (what the next commit will contain)
+        #[arg(long)]
+        staged: bool,
+
+        /// Show old and new versions next to each other
+        #[arg(long)]
+        side_by_side: bool,
+
+        /// Only list changed files with line counts
+        #[arg(long)]
+        stat: bool,
+
+        /// Output as JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Fix the last commit: reword its message or add forgotten files
+    Amend,
+
+    /// Delete something (branch)
+    Delete {
+        #[command(subcommand)]
+        entity: DeleteEntity,
+    },
+
+    /// Find merged, orphaned or inactive branches and delete them
+    Cleanup {
+        /// Suggest branches without commits for this many days
+        #[arg(long, default_value_t = 90)]
+        days: u64,
+    },
+
+    /// Update your fork from the original repository and push it to your fork
+    Sync {
+        /// Remote of the original repository
+        #[arg(long, value_name = "REMOTE")]
+        from: Option<String>,
+
+        /// Remote of your fork (defaults to origin)
+        #[arg(long, value_name = "REMOTE")]
+        to: Option<String>,
+    },
+
+    /// Switch to another branch (local or remote)
+    Switch {
+        /// Branch to switch to; pick from a list when omitted
+        branch: Option<String>,
+    },
+
+    /// Set your uncommitted changes aside for later
+    Shelve {
+        /// Name for the shelved changes
+        name: Option<String>,
+
+        /// Also shelve new (untracked) files
+        #[arg(short = 'u', long)]
+        include_untracked: bool,
+    },
+
+    /// List shelved changes and the files in them
+    Shelf {
+        /// Output as JSON instead of a list
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Bring shelved changes back
+    Unshelve {
+        /// Name or number (from 'rfx shelf') of the changes to bring back
+        name: Option<String>,
+    },
+
+    /// Throw away changes to files (a copy is kept in the trash)
+    Discard,
+
+    /// Look at or bring back discarded changes
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+
+    /// Resolve merge conflicts file by file, then finish or abort the merge
+    Resolve,
+
+    /// Give up on an interrupted merge, rebase, cherry-pick, revert or bisect
+    Abort,
+
+    /// Carry on with an interrupted merge, rebase, cherry-pick or revert
+    Continue,
+
+    /// Show or change rfx settings (.rfx.toml and ~/.config/rfx/config.toml)
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Undo the last rfx operation (commit, branch, pull, push...)
     Undo,
+
+    /// Redo the last undone operation
+    Redo,
+
+    /// List recorded operations and jump back to any of them
+    History {
+        /// Output as JSON instead of table
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[derive(ValueEnum, Clone)]
@@ -55,13 +189,99 @@ enum ShowEntity {
     Commits,
 }
 
+#[derive(ValueEnum, Clone, Copy)]
+enum PullStrategyArg {
+    /// Only move forward; refuse if both sides have new commits
+    FfOnly,
+    /// Add a merge commit
+    Merge,
+    /// Replay local commits on top of the remote ones
+    Rebase,
+}
+
+impl From<PullStrategyArg> for adapters::PullStrategy {
+    fn from(arg: PullStrategyArg) -> Self {
+        match arg {
+            PullStrategyArg::FfOnly => adapters::PullStrategy::FastForwardOnly,
+            PullStrategyArg::Merge => adapters::PullStrategy::Merge,
+            PullStrategyArg::Rebase => adapters::PullStrategy::Rebase,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum DeleteEntity {
+    /// Delete local branches
+    Branch {
+        /// Branch to delete; pick from a list when omitted
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the value of a setting, e.g. 'pull.strategy'
+    Get {
+        key: String,
+    },
+
+    /// Change a setting in the repository's .rfx.toml
+    Set {
+        key: String,
+
+        /// New value; separate list items with commas
+        value: String,
+
+        /// Change your user config instead, for every repository
+        #[arg(long)]
+        global: bool,
+    },
+
+    /// List every setting, its value and where it comes from
+    List {
+        /// Output as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashAction {
+    /// List discarded changes, newest first
+    List {
+        /// Output as JSON instead of a list
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Put discarded files back into the working directory
+    Restore {
+        /// Id or number (from 'rfx trash list') of the entry to bring back
+        id: Option<String>,
+    },
+
+    /// Delete everything in the trash for good
+    Empty,
+}
+
 #[derive(Subcommand)]
 enum NewEntity {
     /// Create a new commit
     Commit,
 
-    /// Create a new branch
-    Branch,
+    /// Create a new branch, from the current commit or any other starting point
+    Branch {
+        /// Name of the new branch (prompted when missing)
+        name: Option<String>,
+
+        /// Branch, remote branch (origin/main), tag or commit to start from
+        #[arg(long)]
+        from: Option<String>,
+
+        /// Fetch from the remotes first, so remote branches are up to date
+        #[arg(long)]
+        fetch: bool,
+    },
 }
 
 
@@ -69,19 +289,85 @@ enum NewEntity {
 fn main() {
     let cli = Cli::parse();
 
+    #[cfg(feature = "native")]
+    let opened = adapters::NativeBackend::open(cli.repo.as_deref());
+    #[cfg(not(feature = "native"))]
+    let opened = adapters::CliBackend::open(cli.repo.as_deref());
+
+    let git = match opened {
+        Ok(g) => g,
+        Err(error::RfxError::NotARepo) => {
+            let location = match &cli.repo {
+                Some(p) => format!("'{}'", p.display()),
+                None => "The current directory".to_string(),
+            };
+            eprintln!("Error: {} is not a git repository.", location);
+            eprintln!("(Hint: run rfx inside a git project, or point it at one with --repo <path>).");
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     match cli.command {
-        Commands::Pull => ui::pull(),
-        Commands::Push => ui::push(),
+        Commands::Pull { strategy } => ui::pull(&git, strategy.map(Into::into)),
+        Commands::Push { force } => ui::push(&git, force),
         Commands::Show { entity, json, branch, count } => match entity {
-            ShowEntity::Branches => ui::show_branches(json),
-            ShowEntity::Remotes => ui::show_remotes(json),
-            ShowEntity::Commits => ui::show_commits(&branch, count, json),
+            ShowEntity::Branches => ui::show_branches(&git, json),
+            ShowEntity::Remotes => ui::show_remotes(&git, json),
+            ShowEntity::Commits => ui::show_commits(&git, branch.as_deref(), count, json),
         },
         Commands::New { entity } => match entity {
-            NewEntity::Commit => ui::new_commit(),
-            NewEntity::Branch => ui::new_branch(), 
+            NewEntity::Commit => ui::new_commit(&git),
+            NewEntity::Branch { name, from, fetch } => ui::new_branch(&git, name, from, fetch),
+        },
+        Commands::Status => ui::show_status(&git),
+        Commands::Diff { from, to, staged, side_by_side, stat, json } => {
+            ui::show_diff(&git, from, to, staged, side_by_side, stat, json)
+        }
+        Commands::Amend => ui::amend(&git),
+        Commands::Delete { entity } => match entity {
+            DeleteEntity::Branch { name } => ui::delete_branch(&git, name),
         },
-        Commands::Status => ui::show_status(),
-        Commands::Undo => ui::undo(),
+        Commands::Cleanup { days } => ui::cleanup(&git, days),
+        Commands::Sync { from, to } => ui::sync(&git, from, to),
+        Commands::Switch { branch } => ui::switch(&git, branch),
+        Commands::Shelve { name, include_untracked } => ui::shelve(&git, name, include_untracked),
+        Commands::Shelf { json } => ui::show_shelf(&git, json),
+        Commands::Unshelve { name } => ui::unshelve(&git, name),
+        Commands::Discard => ui::discard(&git),
+        Commands::Trash { action } => match action {
+            TrashAction::List { json } => ui::show_trash(&git, json),
+            TrashAction::Restore { id } => ui::restore_trash(&git, id),
+            TrashAction::Empty => ui::empty_trash(&git),
+        },
+        Commands::Resolve => ui::resolve(&git),
+        Commands::Abort => ui::abort(&git),
+        Commands::Continue => ui::continue_operation(&git),
+        Commands::Config { action } => match action {
+            ConfigAction::Get { key } => ui::config_get(&git, &key),
+            ConfigAction::Set { key, value, global } => ui::config_set(&git, &key, &value, global),
+            ConfigAction::List { json } => ui::config_list(&git, json),
+        },
+        Commands::Undo => ui::undo(&git),
+        Commands::Redo => ui::redo(&git),
+        Commands::History { json } => ui::history(&git, json),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_repository_can_be_given_before_or_after_the_command() {
+        for args in [["rfx", "-C", "../app", "status"], ["rfx", "status", "--repo", "../app"]] {
+            let cli = Cli::try_parse_from(args).unwrap();
+            assert_eq!(cli.repo, Some(PathBuf::from("../app")));
+            assert!(matches!(cli.command, Commands::Status));
+        }
+        assert_eq!(Cli::try_parse_from(["rfx", "status"]).unwrap().repo, None);
     }
 }
@@ -0,0 +1,250 @@
+//! Printing diffs: unified or side by side, with colors when writing to a
+//! terminal.
+
+use std::io::IsTerminal;
+
+use crate::adapters::{DiffTarget, GitBackend};
+use crate::core::config::{self, Config};
+use crate::core::diff::{self, DiffLine, FileDiff, Hunk, LineKind};
+use crate::core::get_changed_files;
+
+use super::print_error_json;
+
+/// ANSI colors, following `output.color`: by default switched off when
+/// output is not a terminal or `NO_COLOR` is set
+#[derive(Clone, Copy)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    pub fn detect<G: GitBackend>(git: &G) -> Self {
+        let config = config::load(git).unwrap_or_else(|_| Config::defaults());
+        let enabled = match config.text("output.color").as_deref() {
+            Some("always") => true,
+            Some("never") => false,
+            _ => std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        };
+        Palette { enabled }
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.enabled {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn green(&self, text: &str) -> String {
+        self.paint("32", text)
+    }
+
+    fn red(&self, text: &str) -> String {
+        self.paint("31", text)
+    }
+
+    fn cyan(&self, text: &str) -> String {
+        self.paint("36", text)
+    }
+
+    fn bold(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+
+    fn dim(&self, text: &str) -> String {
+        self.paint("2", text)
+    }
+}
+
+/// Show changes: unstaged by default, staged with `staged`, or between
+/// `from` and `to` (`from` alone compares a commit with the working directory)
+pub fn show_diff<G: GitBackend>(
+    git: &G,
+    from: Option<String>,
+    to: Option<String>,
+    staged: bool,
+    side_by_side: bool,
+    stat: bool,
+    json: bool,
+) {
+    let target = match (from, to, staged) {
+        (None, None, false) => DiffTarget::Worktree,
+        (None, None, true) => DiffTarget::Staged,
+        (Some(from), None, false) => DiffTarget::Against(from),
+        (Some(from), Some(to), false) => DiffTarget::Between(from, to),
+        _ => {
+            eprintln!("Error: --staged compares the staging area with the last commit; it cannot be combined with commits.");
+            return;
+        }
+    };
+
+    let files = match diff::load_diff(git, &target, &[]) {
+        Ok(f) => f,
+        Err(err) if json => return print_error_json(&err),
+        Err(err) => {
+            eprintln!("Error reading changes: {}", err);
+            return;
+        }
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&files).unwrap());
+        return;
+    }
+
+    if files.is_empty() {
+        println!("No changes.");
+        if target == DiffTarget::Worktree {
+            let staged = get_changed_files(git).unwrap_or_default().iter().any(|f| f.is_staged());
+            if staged {
+                println!("(Staged changes are not shown here. Use 'rfx diff --staged' to see them.)");
+            }
+        }
+        return;
+    }
+
+    let palette = Palette::detect(git);
+
+    if stat {
+        print_stats(&files, palette);
+        return;
+    }
+
+    for file in &files {
+        print_file_diff(file, side_by_side, palette);
+    }
+    print_totals(&files, palette);
+}
+
+/// Header, hunks and all of one file
+pub fn print_file_diff(file: &FileDiff, side_by_side: bool, palette: Palette) {
+    let name = display_name(file);
+    println!(
+        "\n{} {}  {} {}",
+        file.change.label(),
+        palette.bold(&name),
+        palette.green(&format!("+{}", file.added)),
+        palette.red(&format!("-{}", file.removed))
+    );
+
+    if file.binary {
+        println!("   {}", palette.dim("(binary file, contents not shown)"));
+        return;
+    }
+    if file.hunks.is_empty() {
+        println!("   {}", palette.dim("(no content changes)"));
+        return;
+    }
+
+    for hunk in &file.hunks {
+        if side_by_side {
+            print_side_by_side(hunk, palette);
+        } else {
+            print_unified(hunk, palette);
+        }
+    }
+}
+
+fn display_name(file: &FileDiff) -> String {
+    match &file.old_path {
+        Some(old) => format!("{} -> {}", old, file.path),
+        None => file.path.clone(),
+    }
+}
+
+pub fn print_unified(hunk: &Hunk, palette: Palette) {
+    println!("{}", palette.cyan(&hunk.header));
+    for line in &hunk.lines {
+        match line.chars().next() {
+            Some('+') => println!("{}", palette.green(line)),
+            Some('-') => println!("{}", palette.red(line)),
+            Some('\\') => println!("{}", palette.dim(line)),
+            _ => println!("{}", line),
+        }
+    }
+}
+
+/// Total width of the terminal, from `COLUMNS` when the shell exports it
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .filter(|&w: &usize| w >= 40)
+        .unwrap_or(120)
+}
+
+fn print_side_by_side(hunk: &Hunk, palette: Palette) {
+    // Each column: 5 for the line number, then the text
+    let column = (terminal_width() - 3) / 2;
+    let text_width = column.saturating_sub(6);
+
+    println!("{}", palette.cyan(&hunk.header));
+    for row in diff::side_by_side(hunk) {
+        let left = cell(row.left.as_ref(), true, text_width, palette);
+        let right = cell(row.right.as_ref(), false, text_width, palette);
+        println!("{} {} {}", left, palette.dim("|"), right);
+    }
+}
+
+/// One padded column of a side-by-side row
+fn cell(line: Option<&DiffLine>, old_side: bool, width: usize, palette: Palette) -> String {
+    let Some(line) = line else {
+        return " ".repeat(width + 6);
+    };
+
+    let number = if old_side { line.old_no } else { line.new_no };
+    let number = number.map(|n| format!("{:>5}", n)).unwrap_or_else(|| " ".repeat(5));
+
+    let text: String = line.text.replace('\t', "    ").chars().take(width).collect();
+    let padded = format!("{:<width$}", text, width = width);
+
+    let body = match line.kind {
+        LineKind::Added => palette.green(&padded),
+        LineKind::Removed => palette.red(&padded),
+        LineKind::Note => palette.dim(&padded),
+        LineKind::Context => padded,
+    };
+    format!("{} {}", palette.dim(&number), body)
+}
+
+/// `git diff --stat`-style overview
+fn print_stats(files: &[FileDiff], palette: Palette) {
+    let width = files.iter().map(|f| display_name(f).chars().count()).max().unwrap_or(0);
+    let most = files.iter().map(|f| f.added + f.removed).max().unwrap_or(0).max(1);
+
+    println!();
+    for file in files {
+        // Scale the bar so the biggest change gets 30 characters
+        let total = file.added + file.removed;
+        let bar_len = (total * 30).div_ceil(most).min(total);
+        let plus = if total == 0 { 0 } else { (bar_len * file.added).div_ceil(total).min(bar_len) };
+
+        let counts = if file.binary {
+            "binary".to_string()
+        } else {
+            format!("+{} -{}", file.added, file.removed)
+        };
+        println!(
+            " {} {:<width$}  {:>10}  {}{}",
+            file.change.label(),
+            display_name(file),
+            counts,
+            palette.green(&"+".repeat(plus)),
+            palette.red(&"-".repeat(bar_len - plus)),
+            width = width
+        );
+    }
+    print_totals(files, palette);
+}
+
+fn print_totals(files: &[FileDiff], palette: Palette) {
+    let added: usize = files.iter().map(|f| f.added).sum();
+    let removed: usize = files.iter().map(|f| f.removed).sum();
+    println!(
+        "\n{} file(s) changed, {} {}",
+        files.len(),
+        palette.green(&format!("+{}", added)),
+        palette.red(&format!("-{}", removed))
+    );
+}
@@ -1,21 +1,39 @@
+mod diff_view;
+
+pub use diff_view::show_diff;
+
 use prettytable::{Table, Row, Cell, format};
 use inquire::{Confirm, MultiSelect, Text, validator::Validation, Select};
 
 use crate::core::{BranchInfo, branches_detailed, 
     commits_detailed, remotes_detailed, create_commit, get_changed_files, 
-    stage_all_files, stage_files,
-    validate_new_branch_name, create_branch,
-    get_status, get_remote_branches, pull_specific_branch,
-    push_branch,
-    undo_last_commit,
+    stage_all_files, stage_files, unstage_files, amend_last_commit, last_commit_message, last_commit_full_message, FileChange, FileState, ConflictKind,
+    create_branch, resolve_start, BranchStart, local_branch_names,
+    get_status, get_remote_branches, pull_specific_branch, check_pull, PullCheck, preview_pull,
+    configured_pull_strategy, pull_strategy_name,
+    push_branch, current_branch, default_branch, default_remote, remote_names,
+    configured_push_remote, remember_push_remote, plan_force_push, force_push, protected_branches, is_protected, sync_fork, RemoteInfo, delete_branches, switch_branch, switch_to_remote_branch, RemoteBranchInfo,
+    plan_undo_last_commit, undo_last_commit, UndoPlan, current_commit, pushed_to,
 };
 
-use crate::adapters::git_last_commit;
+use crate::core::cleanup;
+use crate::core::config::{self, Config};
+use crate::core::conflict::{self, ConflictFile, Resolution};
+use crate::core::diff;
+use crate::core::progress::{self, PendingOperation};
+use crate::core::refname;
+use crate::core::journal::{self, Operation, OperationKind};
+use crate::core::message::{self, CommitPolicy, MessageParts};
+use crate::core::shelf::{self, ShelfEntry, UnshelveOutcome};
+use crate::core::trash::{self, TrashEntry};
+use crate::adapters::{GitBackend, InProgress, PullStrategy};
+use crate::error::RfxError;
 
 /// Display branches in a table or JSON
-pub fn show_branches(json: bool) {
-    let branches = match crate::core::branches_detailed() {
+pub fn show_branches<G: GitBackend>(git: &G, json: bool) {
+    let branches = match branches_detailed(git) {
         Ok(b) => b,
+        Err(err) if json => return print_error_json(&err),
         Err(err) => {
             eprintln!("Error retrieving branches: {}", err);
             return;
@@ -27,6 +45,7 @@ pub fn show_branches(json: bool) {
         return;
     }
 
+    let config = config::load(git).unwrap_or_else(|_| Config::defaults());
     let mut table = Table::new();
 
     // Compact format: header line only
@@ -41,9 +60,9 @@ pub fn show_branches(json: bool) {
     ]));
 
     for b in branches {
-        let branch = if b.name.len() > 10 { format!("{}…", &b.name[..9]) } else { b.name };
-        let author = if b.author.len() > 15 { format!("{}…", &b.author[..14]) } else { b.author };
-        let commit_msg = if b.last_commit.len() > 25 { format!("{}…", &b.last_commit[..24]) } else { b.last_commit };
+        let branch = shorten(&b.name, column_width(&config, "output.name_width"));
+        let author = shorten(&b.author, column_width(&config, "output.author_width"));
+        let commit_msg = shorten(&b.last_commit, column_width(&config, "output.message_width"));
 
         table.add_row(Row::new(vec![
             Cell::new(&branch),
@@ -57,6 +76,21 @@ pub fn show_branches(json: bool) {
     table.printstd();
 }
 
+/// Cut `text` to `width` characters, marking the cut with `…`
+fn shorten(text: &str, width: usize) -> String {
+    if width == 0 || text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(width - 1).collect();
+    short.push('…');
+    short
+}
+
+/// A column width from the `output.*` settings
+fn column_width(config: &Config, key: &str) -> usize {
+    config.number(key).unwrap_or(0)
+}
+
 /// Print branches as JSON
 pub fn print_branches_json(branches: &[BranchInfo]) {
     match serde_json::to_string_pretty(branches) {
@@ -65,10 +99,22 @@ pub fn print_branches_json(branches: &[BranchInfo]) {
     }
 }
 
+/// Print an error as JSON so scripts can branch on its kind
+fn print_error_json(err: &RfxError) {
+    let value = serde_json::json!({
+        "error": {
+            "kind": err.kind(),
+            "message": err.to_string(),
+        }
+    });
+    println!("{}", value);
+}
+
 /// Display remotes in a table or JSON
-pub fn show_remotes(json: bool) {
-    let remotes = match remotes_detailed() {
+pub fn show_remotes<G: GitBackend>(git: &G, json: bool) {
+    let remotes = match remotes_detailed(git) {
         Ok(r) => r,
+        Err(e) if json => return print_error_json(&e),
         Err(e) => {
             eprintln!("Error fetching remotes: {}", e);
             return;
@@ -112,9 +158,13 @@ pub fn show_remotes(json: bool) {
 }
 
 /// Display commits in a table or JSON
-pub fn show_commits(branch: &str, count: usize, json: bool) {
-    let commits = match commits_detailed(branch, count) {
+pub fn show_commits<G: GitBackend>(git: &G, branch: Option<&str>, count: usize, json: bool) {
+    // "HEAD" also covers a detached checkout
+    let branch = branch.map(String::from).unwrap_or_else(|| current_branch(git).unwrap_or_else(|_| "HEAD".to_string()));
+
+    let commits = match commits_detailed(git, &branch, count) {
         Ok(c) => c,
+        Err(e) if json => return print_error_json(&e),
         Err(e) => {
             eprintln!("Error fetching commits: {}", e);
             return;
@@ -129,6 +179,7 @@ pub fn show_commits(branch: &str, count: usize, json: bool) {
         return;
     }
 
+    let config = config::load(git).unwrap_or_else(|_| Config::defaults());
     let mut table = Table::new();
     table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
 
@@ -141,16 +192,8 @@ pub fn show_commits(branch: &str, count: usize, json: bool) {
 
     for c in commits {
         let hash = if c.hash.len() > 7 { &c.hash[..7] } else { &c.hash };
-        let author = if c.author.len() > 15 {
-            format!("{}…", &c.author[..14])
-        } else {
-            c.author
-        };
-        let message = if c.message.len() > 30 {
-            format!("{}…", &c.message[..29])
-        } else {
-            c.message
-        };
+        let author = shorten(&c.author, column_width(&config, "output.author_width"));
+        let message = shorten(&c.message, column_width(&config, "output.message_width"));
 
         table.add_row(Row::new(vec![
             Cell::new(hash),
@@ -164,9 +207,9 @@ pub fn show_commits(branch: &str, count: usize, json: bool) {
 }
 
 /// Create a new commit with user-provided message
-pub fn new_commit() {
+pub fn new_commit<G: GitBackend>(git: &G) {
     // 1. Get current status via Core
-    let changes = match get_changed_files() {
+    let changes = match get_changed_files(git) {
         Ok(c) => c,
         Err(e) => {
             eprintln!("Failed to check status: {}", e);
@@ -179,441 +222,2834 @@ pub fn new_commit() {
         return;
     }
 
+    if changes.iter().any(|f| f.conflict.is_some()) {
+        println!("You have unresolved merge conflicts. Resolve them before committing.");
+        print_changes(&changes);
+        return;
+    }
+
     // 2. Display changes
-    println!("\nChanged files:");
-    for file in &changes {
-        let label = match file.status.as_str() {
-            "??" => "[New]",
-            "M" => "[Mod]",
-            "D" => "[Del]",
-            _ => "[...]",
-        };
-        println!("  {} {}", label, file.path);
+    print_changes(&changes);
+
+    if !choose_files(git, &changes, "Do you want to commit all changes?") {
+        return;
     }
-    println!();
+
+    // 5. Prompt for Message
+    match prompt_commit_message(git, None) {
+        Some(msg) => {
+            match create_commit(git, &msg) {
+                Ok(out) => {
+                    println!("\nSuccess! Commit created.");
+                    // Only show the summary line from git output
+                    if let Some(line) = out.lines().next() {
+                         println!("{}", line);
+                    }
+                }
+                Err(e) => eprintln!("\nError committing: {}", e),
+            }
+        }
+        None => println!("Commit cancelled."),
+    }
+}
+
+/// Stage everything or a picked set of files; `false` if the user cancelled
+fn choose_files<G: GitBackend>(git: &G, changes: &[FileChange], all_question: &str) -> bool {
+    preview_changes(git, changes);
 
     // 3. Ask: Commit everything?
-    let commit_all = Confirm::new("Do you want to commit all changes?")
+    let commit_all = Confirm::new(all_question)
         .with_default(true)
         .prompt();
 
     match commit_all {
         Ok(true) => {
-            if let Err(e) = stage_all_files() {
+            if let Err(e) = stage_all_files(git) {
                 eprintln!("Error staging files: {}", e);
-                return;
+                return false;
             }
         }
         Ok(false) => {
-            // 4. Interactive Selection
+            // 4. Interactive Selection (files that are already staged start selected)
             let file_options: Vec<String> = changes
                 .iter()
                 .map(|f| f.path.clone())
                 .collect();
+            let staged_indexes: Vec<usize> = changes
+                .iter()
+                .enumerate()
+                .filter(|(_, f)| f.is_staged())
+                .map(|(i, _)| i)
+                .collect();
 
             let selected_files = MultiSelect::new("Select files to include (Space to toggle):", file_options)
+                .with_default(&staged_indexes)
                 .with_page_size(10)
                 .prompt();
 
             match selected_files {
                 Ok(files) if files.is_empty() => {
                     println!("No files selected. Aborting commit.");
-                    return;
+                    return false;
                 }
                 Ok(files) => {
-                    if let Err(e) = stage_files(&files) {
+                    // Staged files the user deselected must not sneak into the commit
+                    let deselected: Vec<String> = changes
+                        .iter()
+                        .filter(|f| f.is_staged() && !files.contains(&f.path))
+                        .flat_map(|f| std::iter::once(f.path.clone()).chain(f.renamed_from.clone()))
+                        .collect();
+                    if let Err(e) = unstage_files(git, &deselected) {
+                        eprintln!("Error unstaging files: {}", e);
+                        return false;
+                    }
+
+                    // Optionally take only some of the edits in modified files
+                    let splittable: Vec<&FileChange> = changes
+                        .iter()
+                        .filter(|f| files.contains(&f.path) && diff::can_split(f))
+                        .collect();
+                    let mut whole = files.clone();
+                    if !splittable.is_empty() {
+                        let pick = Confirm::new("Pick individual changes inside these files?")
+                            .with_help_message("Lets unrelated edits in one file go into separate commits")
+                            .with_default(false)
+                            .prompt();
+                        if let Ok(true) = pick {
+                            for file in splittable {
+                                match pick_hunks_to_stage(git, &file.path) {
+                                    Some(true) => whole.retain(|p| *p != file.path),
+                                    Some(false) => {}
+                                    None => return false,
+                                }
+                            }
+                        }
+                    }
+
+                    if let Err(e) = stage_files(git, &whole) {
                         eprintln!("Error staging files: {}", e);
-                        return;
+                        return false;
                     }
                 }
                 Err(_) => {
                     println!("Selection cancelled.");
-                    return;
+                    return false;
                 }
             }
         }
-        Err(_) => return,
+        Err(_) => return false,
     }
 
-    // 5. Prompt for Message
-    let message_prompt = Text::new("Commit message:")
-        .with_validator(|input: &str| {
-            if input.trim().len() < 3 {
-                Ok(Validation::Invalid("Message is too short.".into()))
-            } else {
-                Ok(Validation::Valid)
-            }
-        })
+    true
+}
+
+/// Let the user look at the diff of any changed file before choosing
+fn preview_changes<G: GitBackend>(git: &G, changes: &[FileChange]) {
+    let wanted = Confirm::new("Preview what changed in a file first?")
+        .with_default(false)
         .prompt();
+    if !matches!(wanted, Ok(true)) {
+        return;
+    }
 
-    match message_prompt {
-        Ok(msg) => {
-            match create_commit(msg.trim()) {
-                Ok(out) => {
-                    println!("\nSuccess! Commit created.");
-                    // Only show the summary line from git output
-                    if let Some(line) = out.lines().next() {
-                         println!("{}", line);
-                    }
-                }
-                Err(e) => eprintln!("\nError committing: {}", e),
-            }
+    const DONE: &str = "Done previewing";
+    let mut options: Vec<String> = changes.iter().map(|f| f.display_path()).collect();
+    options.push(DONE.to_string());
+    let palette = diff_view::Palette::detect(git);
+    let mut cursor = 0;
+
+    loop {
+        let choice = Select::new("Preview which file?", options.clone())
+            .with_starting_cursor(cursor)
+            .with_page_size(10)
+            .prompt();
+
+        let Ok(choice) = choice else { return };
+        let Some(index) = options.iter().position(|o| *o == choice) else { return };
+        if choice == DONE {
+            return;
+        }
+        cursor = index;
+
+        match diff::file_preview(git, &changes[index]) {
+            Ok(Some(file)) => diff_view::print_file_diff(&file, false, palette),
+            Ok(None) => println!("\nNo content changes in '{}'.", changes[index].path),
+            Err(e) => eprintln!("\nError reading changes of '{}': {}", changes[index].path, e),
         }
-        Err(_) => println!("Commit cancelled."),
+        println!();
     }
 }
 
-pub fn new_branch() {
-    // 1. Prompt for Name
-    let name_prompt = Text::new("Name for new branch:")
-        .with_validator(|input: &str| {
-            match validate_new_branch_name(input) {
-                Ok(_) => Ok(Validation::Valid),
-                Err(msg) => Ok(Validation::Invalid(msg.into())),
-            }
-        })
-        .prompt();
-
-    let name = match name_prompt {
-        Ok(n) => n.trim().to_string(),
-        Err(_) => { println!("Cancelled."); return; }
+/// Go through the unstaged changes of one file and stage the chosen ones.
+///
+/// `Some(true)` when the file was handled change by change, `Some(false)`
+/// when it has to be staged whole (binary, nothing to split), `None` if the
+/// user cancelled.
+fn pick_hunks_to_stage<G: GitBackend>(git: &G, path: &str) -> Option<bool> {
+    let file = match diff::unstaged_hunks(git, path) {
+        Ok(Some(f)) => f,
+        Ok(None) => return Some(false),
+        Err(e) => {
+            eprintln!("Error reading changes of '{}': {}", path, e);
+            return None;
+        }
     };
 
-    // 2. Check for Uncommitted Changes (The "Error" Prevention)
-    let changes = match get_changed_files() {
-        Ok(c) => c,
-        Err(_) => Vec::new(), 
-    };
+    const YES: &str = "Include this change";
+    const NO: &str = "Leave it out";
+    const REST: &str = "Include this and the rest of the file";
+    const NONE: &str = "Leave out this and the rest of the file";
 
-    if !changes.is_empty() {
-        println!("\nWarning: You have uncommitted changes.");
-        println!("   If you create a new branch now, these changes will move with you.");
-        
-        let count = changes.len();
-        if count <= 5 {
-            for file in changes {
-                println!("   - {}", file.path);
-            }
-        } else {
-            println!("   - {} files changed...", count);
-        }
-        println!();
+    let total = file.hunks.len();
+    let mut chosen = Vec::new();
+    let palette = diff_view::Palette::detect(git);
 
-        let confirm = Confirm::new("Do you want to proceed and carry these changes over?")
-            .with_default(false)
-            .prompt();
+    for (i, hunk) in file.hunks.iter().enumerate() {
+        println!("\n--- {} (change {}/{}, +{} -{}) ---", path, i + 1, total, hunk.added(), hunk.removed());
+        diff_view::print_unified(hunk, palette);
+        println!();
 
-        match confirm {
-            Ok(true) => (),
-            _ => {
-                println!("Cancelled. Please commit or stash your changes first.");
-                return;
+        match Select::new("Include it in the commit?", vec![YES, NO, REST, NONE]).prompt() {
+            Ok(YES) => chosen.push(i),
+            Ok(NO) => {}
+            Ok(REST) => {
+                chosen.extend(i..total);
+                break;
             }
+            Ok(NONE) => break,
+            _ => return None,
         }
     }
 
-    // 3. Execute
-    match create_branch(&name) {
-        Ok(_) => {
-            println!("\nSuccess! New branch '{}' created.", name);
-            println!("   You have been switched to this branch automatically.");
-        },
-        Err(e) => eprintln!("\nError creating branch: {}", e),
+    if chosen.is_empty() {
+        println!("Nothing from '{}' will be included.", path);
+    } else if let Err(e) = diff::stage_hunks(git, &file, &chosen) {
+        eprintln!("Error staging changes of '{}': {}", path, e);
+        return None;
     }
+
+    Some(true)
 }
 
-pub fn show_status() {
-    let status = match get_status() {
-        Ok(s) => s,
+/// Ask for a commit message, starting from `initial` when given
+fn prompt_commit_message<G: GitBackend>(git: &G, initial: Option<&str>) -> Option<String> {
+    let policy = match message::commit_policy(git) {
+        Ok(p) => p,
         Err(e) => {
-            eprintln!("Error getting status: {}", e);
-            return;
+            eprintln!("Error reading the commit message rules: {}", e);
+            return None;
         }
     };
 
-    println!("\nBranch: {}", status.branch);
-
-    // 1. Sync Status Logic
-    match (status.ahead, status.behind) {
-        (Some(0), Some(0)) => println!("Status: Up to date with remote"),
-        (Some(a), Some(b)) => {
-            if a > 0 { println!("Status: {} commit(s) ahead (Needs Push)", a); }
-            if b > 0 { println!("Status: {} commit(s) behind (Needs Pull)", b); }
+    // New commits under Conventional Commits can be put together step by step
+    if initial.is_none() && policy.is_conventional() {
+        match Confirm::new("Build the message step by step (type, scope, breaking change, issue)?")
+            .with_default(true)
+            .prompt()
+        {
+            Ok(true) => return build_commit_message(&policy),
+            Ok(false) => {}
+            Err(_) => return None,
         }
-        (None, None) => {
-            println!("Status: Not published (Local only)");
-        }
-        _ => {}, 
     }
-    println!();
 
-    // 2. File Status
-    if status.changes.is_empty() {
-        println!("Working directory is clean.");
-    } else {
-        println!("Unsaved Changes:");
-        for file in status.changes {
-            let label = match file.status.as_str() {
-                "??" => "[New]",
-                "M" | "M " => "[Mod]",
-                "D" | "D " => "[Del]",
-                _ => "[...]",
-            };
-            println!("  {} {}", label, file.path);
-        }
-        println!("\nTip: Use 'rfx new commit' to save these.");
+    let rules = policy.clone();
+    let mut prompt = Text::new("Commit message:")
+        .with_validator(move |input: &str| match rules.check_subject(input).into_iter().next() {
+            Some(problem) => Ok(Validation::Invalid(problem.into())),
+            None => Ok(Validation::Valid),
+        });
+    if policy.is_conventional() {
+        prompt = prompt.with_help_message("type(scope): description, e.g. feat(login): add remember-me");
     }
-    println!();
+    // When amending only the first line is edited; the description and
+    // trailers below it are kept as they are
+    let original = initial.map(message::parse_message).unwrap_or_default();
+    let rest = initial
+        .and_then(|m| m.trim().split_once('\n'))
+        .map(|(_, rest)| rest.trim())
+        .unwrap_or_default();
+    if initial.is_some() {
+        prompt = prompt.with_initial_value(&original.subject);
+    }
+
+    let subject = prompt.prompt().ok()?.trim().to_string();
+    let present: Vec<&str> = original.trailers.iter().map(|(k, _)| k.as_str()).collect();
+    let trailers = prompt_required_trailers(&policy, &present)?;
+
+    let mut message = subject;
+    if !rest.is_empty() {
+        message.push_str("\n\n");
+        message.push_str(rest);
+    }
+    if !trailers.is_empty() {
+        let lines: Vec<String> = trailers.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+        // New trailers join the existing ones in the last paragraph
+        message.push_str(if original.trailers.is_empty() { "\n\n" } else { "\n" });
+        message.push_str(&lines.join("\n"));
+    }
+    Some(message)
 }
 
-pub fn pull() {
-    // --- STEP 1: SAFETY CHECK (The "Action Prompt") ---
-    loop {
-        let changes = match get_changed_files() {
-            Ok(c) => c,
-            Err(_) => Vec::new(),
-        };
+/// Ask for the trailers the policy requires that are not in `present`
+fn prompt_required_trailers(policy: &CommitPolicy, present: &[&str]) -> Option<Vec<(String, String)>> {
+    let mut trailers = Vec::new();
 
-        if changes.is_empty() {
-            break;
+    for key in policy.required_trailers() {
+        if present.iter().any(|p| p.eq_ignore_ascii_case(key)) {
+            continue;
         }
+        let value = Text::new(&format!("{}:", key))
+            .with_help_message("Required by this repository's commit rules")
+            .with_validator(|input: &str| {
+                if input.trim().is_empty() {
+                    Ok(Validation::Invalid("This line is required.".into()))
+                } else {
+                    Ok(Validation::Valid)
+                }
+            })
+            .prompt()
+            .ok()?;
+        trailers.push((key.to_string(), value.trim().to_string()));
+    }
 
-        println!("\nYou have uncommitted changes:");
-        for file in changes.iter().take(5) {
-            println!("   - {}", file.path);
+    Some(trailers)
+}
+
+/// Guided Conventional Commits message: type, scope, description, body,
+/// breaking change and issue reference
+fn build_commit_message(policy: &CommitPolicy) -> Option<String> {
+    let types = policy.types().unwrap_or(&[]).to_vec();
+    let options: Vec<String> = types
+        .iter()
+        .map(|t| match message::DEFAULT_TYPES.iter().find(|(name, _)| name == t) {
+            Some((_, about)) => format!("{}: {}", t, about),
+            None => t.clone(),
+        })
+        .collect();
+    let choice = Select::new("Type of change:", options.clone()).with_page_size(11).prompt().ok()?;
+    let kind = types[options.iter().position(|o| *o == choice)?].clone();
+
+    let scopes = policy.scopes();
+    let scope = if scopes.is_empty() {
+        let required = policy.requires_scope();
+        let scope = Text::new("Scope (the part of the code it touches):")
+            .with_help_message(if required { "Required, e.g. parser" } else { "Optional, e.g. parser; leave empty to skip" })
+            .with_validator(move |input: &str| {
+                let input = input.trim();
+                if required && input.is_empty() {
+                    Ok(Validation::Invalid("A scope is required.".into()))
+                } else if input.contains(['(', ')', ':', ' ']) {
+                    Ok(Validation::Invalid("Use a single word without spaces or brackets.".into()))
+                } else {
+                    Ok(Validation::Valid)
+                }
+            })
+            .prompt()
+            .ok()?;
+        Some(scope.trim().to_string()).filter(|s| !s.is_empty())
+    } else {
+        const NONE: &str = "(no scope)";
+        let mut options = scopes.to_vec();
+        if !policy.requires_scope() {
+            options.push(NONE.to_string());
         }
-        if changes.len() > 5 { println!("   ...and {} more.", changes.len() - 5); }
-        println!();
+        let choice = Select::new("Scope:", options).prompt().ok()?;
+        (choice != NONE).then_some(choice)
+    };
 
-        let options = vec!["Commit changes now", "Cancel"];
-        let choice = Select::new("What would you like to do?", options).prompt();
+    let breaking = Confirm::new("Does this break existing behavior (a BREAKING CHANGE)?")
+        .with_default(false)
+        .prompt()
+        .ok()?;
+
+    let mut parts = MessageParts {
+        kind,
+        scope,
+        breaking: breaking.then(String::new),
+        ..MessageParts::default()
+    };
 
-        match choice {
-            Ok("Commit changes now") => {
-                new_commit(); 
+    let prefix = message::build_header(&parts);
+    let rules = policy.clone();
+    parts.description = Text::new("Short description:")
+        .with_help_message("Imperative and lowercase, e.g. 'add remember-me option'")
+        .with_validator(move |input: &str| {
+            if input.trim().is_empty() {
+                return Ok(Validation::Invalid("A description is required.".into()));
             }
-            _ => {
-                println!("Pull cancelled.");
-                return;
+            match rules.check_subject(&format!("{}{}", prefix, input.trim())).into_iter().next() {
+                Some(problem) => Ok(Validation::Invalid(problem.into())),
+                None => Ok(Validation::Valid),
             }
-        }
+        })
+        .prompt()
+        .ok()?
+        .trim()
+        .to_string();
+
+    parts.body = Text::new("Longer explanation (optional):")
+        .with_help_message("Why the change was made; wrapped for you")
+        .prompt()
+        .ok()?;
+
+    if breaking {
+        let what = Text::new("What breaks, and how do users update?")
+            .with_validator(|input: &str| {
+                if input.trim().is_empty() {
+                    Ok(Validation::Invalid("Describe the breaking change.".into()))
+                } else {
+                    Ok(Validation::Valid)
+                }
+            })
+            .prompt()
+            .ok()?;
+        parts.breaking = Some(what);
     }
 
-    // --- STEP 2: BRANCH SELECTION ---
-    println!("\nFetching latest updates from remote...");
-    
-    let branches = match get_remote_branches() {
-        Ok(b) => b,
-        Err(e) => {
-            eprintln!("Error fetching branches: {}", e);
-            return;
-        }
-    };
+    let issue = Text::new("Issue reference (optional):")
+        .with_help_message("e.g. #123; leave empty to skip")
+        .prompt()
+        .ok()?;
+    parts.issue = Some(issue).filter(|i| !i.trim().is_empty());
 
-    if branches.is_empty() {
-        println!("No remote branches found. (Are you connected to the internet?)");
-        return;
+    let mut present = Vec::new();
+    if parts.breaking.is_some() {
+        present.push("BREAKING CHANGE");
+    }
+    if parts.issue.is_some() {
+        present.push(message::ISSUE_TRAILER);
     }
+    parts.trailers = prompt_required_trailers(policy, &present)?;
 
-    let options: Vec<String> = branches.iter().map(|b| {
-        format!("{: <15} | {: <15} | {}", b.short_name, b.author, b.date)
-    }).collect();
+    let msg = message::build_message(policy, &parts);
+    println!("\nCommit message:");
+    for line in msg.lines() {
+        println!("   {}", line);
+    }
+    println!();
 
-    let selection = Select::new("Select branch to pull from:", options)
-        .with_page_size(10)
-        .prompt();
+    match Confirm::new("Use this message?").with_default(true).prompt() {
+        Ok(true) => Some(msg),
+        _ => None,
+    }
+}
 
-    let selected_branch = match selection {
-        Ok(s) => {
-            let index = branches.iter().position(|b| {
-                 let fmt = format!("{: <15} | {: <15} | {}", b.short_name, b.author, b.date);
-                 fmt == s
-            }).unwrap();
-            &branches[index]
-        }
-        Err(_) => {
-            println!("Cancelled.");
+/// Fix the last commit: change its message and/or add forgotten files
+pub fn amend<G: GitBackend>(git: &G) {
+    let Some(sha) = current_commit(git) else {
+        println!("There are no commits yet. Use 'rfx new commit' to create the first one.");
+        return;
+    };
+    let message = match last_commit_full_message(git) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("Error reading the last commit: {}", e);
             return;
         }
     };
 
-    // --- STEP 3: EXECUTE ---
-    println!("\n⬇ Pulling from '{}'...", selected_branch.full_name);
+    println!("\nLast commit: {} {}", short_sha(Some(&sha)), last_commit_message(git).unwrap_or_default());
 
-    match pull_specific_branch(&selected_branch.full_name) {
-        Ok(out) => {
-            if out.contains("Already up to date") {
-                 println!("Already up to date.");
-            } else {
-                 println!("Success! Updates received.");
-                 println!("{}", out);
-            }
+    let pushed = pushed_to(git, &sha);
+    if !pushed.is_empty() {
+        println!("\n[Warning] This commit is already on: {}", pushed.join(", "));
+        println!("Amending replaces it with a new commit, so you will have to run");
+        println!("'rfx push --force' afterwards, and anyone who pulled it will have to fix their copy.");
+        if !matches!(Confirm::new("Amend it anyway?").with_default(false).prompt(), Ok(true)) {
+            println!("Amend cancelled. Nothing was changed.");
+            return;
         }
+    }
+
+    let changes = match get_changed_files(git) {
+        Ok(c) => c,
         Err(e) => {
-            if e.to_lowercase().contains("conflict") {
-                eprintln!("\nMerge Conflict Detected:");
-                eprintln!("   We downloaded the code, but couldn't combine it automatically.");
-                eprintln!("   Please open the conflicting files and resolve the issues.");
-            } else {
-                eprintln!("\nError pulling:");
-                eprintln!("{}", e);
-            }
+            eprintln!("Failed to check status: {}", e);
+            return;
         }
-    }
-}
+    };
 
-pub fn push() {
-    // --- STEP 1: SAFETY CHECK ---
-    loop {
-        let changes = match get_changed_files() {
-            Ok(c) => c,
-            Err(_) => Vec::new(),
-        };
+    if changes.iter().any(|f| f.conflict.is_some()) {
+        println!("You have unresolved merge conflicts. Resolve them before amending.");
+        print_changes(&changes);
+        return;
+    }
 
-        if changes.is_empty() {
-            break;
+    let mut include_staged = false;
+    if !changes.is_empty() {
+        println!();
+        print_changes(&changes);
+        match Confirm::new("Add some of these changes to the commit?").with_default(false).prompt() {
+            Ok(true) => {
+                if !choose_files(git, &changes, "Do you want to add all changes?") {
+                    return;
+                }
+                include_staged = true;
+            }
+            Ok(false) => println!("Your changes stay out of the commit; only the message will change."),
+            Err(_) => return,
         }
+    }
 
-        println!("\nYou have uncommitted changes:");
-        for file in changes.iter().take(5) {
-            println!("   - {}", file.path);
-        }
-        if changes.len() > 5 { println!("   ...and {} more.", changes.len() - 5); }
-        println!();
+    let Some(new_message) = prompt_commit_message(git, Some(&message)) else {
+        println!("Amend cancelled.");
+        return;
+    };
 
-        let options = vec![
-            "Commit changes now (Recommended)",
-            "Push existing commits (Keep changes local)", 
-            "Cancel"
-        ];
-        
-        let choice = Select::new("What would you like to do?", options).prompt();
+    if new_message.trim() == message.trim() && !include_staged {
+        println!("The message is unchanged and no files were added. Nothing to amend.");
+        return;
+    }
 
-        match choice {
-            Ok("Commit changes now (Recommended)") => {
-                new_commit(); 
-            }
-            Ok("Push existing commits (Keep changes local)") => {
-                println!("\n[Note] Your uncommitted changes will NOT be sent to the server.");
-                break;
+    match amend_last_commit(git, &new_message, include_staged) {
+        Ok(out) => {
+            println!("\nSuccess! Last commit amended.");
+            if let Some(line) = out.lines().next() {
+                println!("{}", line);
             }
-            _ => {
-                println!("Push cancelled.");
-                return;
+            if !pushed.is_empty() {
+                println!("   Run 'rfx push --force' to replace the old version on the remote.");
             }
+            println!("   Changed your mind? 'rfx undo' brings the old commit back.");
         }
+        Err(e) => eprintln!("\nError amending: {}", e),
     }
+}
 
-    // --- STEP 2: BRANCH SELECTION ---
-    println!("\nPreparing to push...");
+pub fn new_branch<G: GitBackend>(git: &G, name: Option<String>, from: Option<String>, fetch: bool) {
+    if fetch {
+        println!("\nFetching latest updates from remote...");
+        if let Err(e) = git.fetch() {
+            eprintln!("Warning: Could not fetch: {}", e);
+        }
+    }
 
-    // 1. Get detailed list of LOCAL branches
-    let branches = match branches_detailed() {
-        Ok(b) => b,
+    // 1. Name: given on the command line, or prompted
+    let rules = match refname::branch_name_rules(git) {
+        Ok(r) => r,
         Err(e) => {
-            eprintln!("Error reading branches: {}", e);
+            eprintln!("Error: {}", e);
             return;
         }
     };
+    let name = match name {
+        Some(n) => {
+            if let Err(e) = rules.check(&n) {
+                eprintln!("Error: {}", e);
+                return;
+            }
+            n.trim().to_string()
+        }
+        None => {
+            let help = rules.naming.as_ref().map(|n| format!("Naming convention: {}", n.as_str()));
+            let mut name_prompt = Text::new("Name for new branch:");
+            if let Some(help) = &help {
+                name_prompt = name_prompt.with_help_message(help);
+            }
+            let name_prompt = name_prompt
+                .with_validator(move |input: &str| {
+                    match rules.check(input) {
+                        Ok(_) => Ok(Validation::Valid),
+                        Err(msg) => Ok(Validation::Invalid(msg.to_string().into())),
+                    }
+                })
+                .prompt();
+
+            match name_prompt {
+                Ok(n) => n.trim().to_string(),
+                Err(_) => { println!("Cancelled."); return; }
+            }
+        }
+    };
+
+    // 2. Where it starts
+    let start = match from {
+        Some(rev) => match resolve_start(git, &rev) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return;
+            }
+        },
+        None => match pick_branch_start(git, fetch) {
+            Some(s) => s,
+            None => return,
+        },
+    };
+
+    // 3. Check for Uncommitted Changes (The "Error" Prevention)
+    if !carry_or_shelve(git, "create a new branch", "before new branch") {
+        return;
+    }
+
+    // 4. Execute
+    match create_branch(git, &name, &start) {
+        Ok(_) => {
+            println!("\nSuccess! New branch '{}' created from {}.", name, start.describe());
+            println!("   You have been switched to this branch automatically.");
+            if let BranchStart::Remote(base) = &start {
+                println!("   It tracks '{}' for now; 'rfx push' links it to its own remote branch.", base);
+            }
+        },
+        Err(e) => eprintln!("\nError creating branch: {}", e),
+    }
+}
+
+/// Ask where a new branch should start; `None` when cancelled
+fn pick_branch_start<G: GitBackend>(git: &G, fetched: bool) -> Option<BranchStart> {
+    let current = current_branch(git).unwrap_or_default();
+    let latest = match (default_remote(git), default_branch(git)) {
+        (Ok(remote), Ok(branch)) => Some(format!("{}/{}", remote, branch)),
+        _ => None,
+    };
+
+    let here = if current.is_empty() { "The current commit".to_string() } else { format!("Current branch ({})", current) };
+    let mut options = vec![here];
+    if let Some(latest) = &latest {
+        options.push(format!("Latest {} (fetches first)", latest));
+    }
+    options.extend([
+        "Another local branch".to_string(),
+        "A remote branch".to_string(),
+        "A tag".to_string(),
+        "A commit hash".to_string(),
+    ]);
+
+    let choice = match Select::new("Start the new branch from:", options.clone()).prompt() {
+        Ok(c) => options.iter().position(|o| *o == c).unwrap(),
+        Err(_) => { println!("Cancelled."); return None; }
+    };
+    // Without the "latest" entry everything after the first option moves up one
+    let choice = if latest.is_none() && choice > 0 { choice + 1 } else { choice };
+
+    let fetch = || {
+        if !fetched {
+            println!("\nFetching latest updates from remote...");
+            if let Err(e) = git.fetch() {
+                eprintln!("Warning: Could not fetch: {}", e);
+            }
+        }
+    };
+
+    let picked = match choice {
+        0 => return Some(BranchStart::Head),
+        1 => {
+            fetch();
+            latest?
+        }
+        2 => {
+            let locals: Vec<String> = local_branch_names(git)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|b| *b != current)
+                .collect();
+            pick_from("Start from branch (type to search):", locals, "There are no other local branches.")?
+        }
+        3 => {
+            println!("\nFetching latest updates from remote...");
+            let remotes: Vec<String> = get_remote_branches(git)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|r| r.full_name)
+                .collect();
+            pick_from("Start from remote branch (type to search):", remotes, "No remote branches found.")?
+        }
+        4 => {
+            let tags = git.list_tags().unwrap_or_default();
+            pick_from("Start from tag (type to search):", tags, "This repository has no tags.")?
+        }
+        _ => match Text::new("Commit hash:").prompt() {
+            Ok(hash) => hash,
+            Err(_) => { println!("Cancelled."); return None; }
+        },
+    };
+
+    match resolve_start(git, &picked) {
+        Ok(start) => Some(start),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            None
+        }
+    }
+}
+
+/// Searchable list of refs; `None` when there is nothing to pick or the user cancels
+fn pick_from(prompt: &str, items: Vec<String>, empty: &str) -> Option<String> {
+    if items.is_empty() {
+        println!("{}", empty);
+        return None;
+    }
+    match Select::new(prompt, items)
+        .with_filter(&|input, _, option, _| fuzzy_match(input, option))
+        .with_page_size(10)
+        .prompt()
+    {
+        Ok(item) => Some(item),
+        Err(_) => { println!("Cancelled."); None }
+    }
+}
+
+/// Uncommitted-changes guard for operations that switch branches.
+/// Lets the user carry the changes along, shelve them, or give up;
+/// returns false when the operation should not go ahead.
+fn carry_or_shelve<G: GitBackend>(git: &G, action: &str, shelf_name: &str) -> bool {
+    let changes = get_changed_files(git).unwrap_or_default();
+    if changes.is_empty() {
+        return true;
+    }
+
+    println!("\nWarning: You have uncommitted changes.");
+    println!("   If you {} now, these changes will move with you.", action);
+
+    let count = changes.len();
+    if count <= 5 {
+        for file in changes {
+            println!("   - {}", file.display_path());
+        }
+    } else {
+        println!("   - {} files changed...", count);
+    }
+    println!();
+
+    let options = vec![
+        "Carry these changes over",
+        "Shelve changes and continue",
+        "Cancel",
+    ];
+    let choice = Select::new("What would you like to do?", options).prompt();
+
+    match choice {
+        Ok("Carry these changes over") => true,
+        Ok("Shelve changes and continue") => shelve_before(git, shelf_name),
+        _ => {
+            println!("Cancelled. Please commit or shelve your changes first.");
+            false
+        }
+    }
+}
+
+/// Delete one branch by name, or several picked from a list
+pub fn delete_branch<G: GitBackend>(git: &G, name: Option<String>) {
+    let (target, branches) = match cleanup::local_branches(git) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("Error reading branches: {}", e);
+            return;
+        }
+    };
+    let current = current_branch(git).unwrap_or_default();
+
+    let selected: Vec<(String, bool)> = match name {
+        Some(name) => match branches.iter().find(|b| b.name == name) {
+            Some(b) => vec![(b.name.clone(), b.merged)],
+            None => {
+                eprintln!("There is no local branch named '{}'.", name);
+                return;
+            }
+        },
+        None => {
+            let deletable: Vec<&cleanup::LocalBranch> = branches.iter().filter(|b| b.name != current).collect();
+            if deletable.is_empty() {
+                println!("There are no other branches to delete.");
+                return;
+            }
+
+            let options: Vec<String> = deletable.iter().map(|b| {
+                let state = if b.merged { format!("merged into {}", target) } else { "NOT merged".to_string() };
+                format!("{: <20} | {}", b.name, state)
+            }).collect();
+
+            match MultiSelect::new("Select branches to delete (Space to toggle):", options.clone()).prompt() {
+                Ok(picked) => picked
+                    .iter()
+                    .filter_map(|p| options.iter().position(|o| o == p))
+                    .map(|i| (deletable[i].name.clone(), deletable[i].merged))
+                    .collect(),
+                Err(_) => { println!("Cancelled."); return; }
+            }
+        }
+    };
+
+    confirm_and_delete(git, &target, selected);
+}
+
+/// Suggest merged, orphaned and inactive branches for deletion
+pub fn cleanup<G: GitBackend>(git: &G, days: u64) {
+    println!("\nFetching latest updates from remote...");
+
+    let candidates = match cleanup::cleanup_candidates(git, days) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error looking for stale branches: {}", e);
+            return;
+        }
+    };
+
+    if candidates.is_empty() {
+        println!("Nothing to clean up. Every branch is in use.");
+        return;
+    }
+
+    let options: Vec<String> = candidates.iter().map(|c| {
+        let reasons: Vec<String> = c.reasons.iter().map(|r| r.describe()).collect();
+        let warning = if c.merged { "" } else { " (NOT merged)" };
+        format!("{: <20} | {}{}", c.name, reasons.join(", "), warning)
+    }).collect();
+
+    // Merged branches are safe to delete, so they start selected
+    let defaults: Vec<usize> = candidates.iter().enumerate().filter(|(_, c)| c.merged).map(|(i, _)| i).collect();
+
+    let picked = MultiSelect::new("Select branches to delete (Space to toggle):", options.clone())
+        .with_default(&defaults)
+        .with_page_size(15)
+        .prompt();
+
+    let selected: Vec<(String, bool)> = match picked {
+        Ok(picked) => picked
+            .iter()
+            .filter_map(|p| options.iter().position(|o| o == p))
+            .map(|i| (candidates[i].name.clone(), candidates[i].merged))
+            .collect(),
+        Err(_) => { println!("Cancelled."); return; }
+    };
+
+    let target = default_branch(git).unwrap_or_default();
+    confirm_and_delete(git, &target, selected);
+}
+
+/// Delete `(branch, merged)` pairs, asking before anything unmerged goes
+fn confirm_and_delete<G: GitBackend>(git: &G, target: &str, selected: Vec<(String, bool)>) {
+    if selected.is_empty() {
+        println!("No branches selected.");
+        return;
+    }
+
+    let unmerged: Vec<&String> = selected.iter().filter(|(_, merged)| !merged).map(|(n, _)| n).collect();
+    let mut names: Vec<String> = selected.iter().map(|(n, _)| n.clone()).collect();
+
+    if !unmerged.is_empty() {
+        println!("\n[Warning] These branches have commits that are not in '{}':", target);
+        for name in &unmerged {
+            println!("   - {}", name);
+        }
+        println!("Deleting them hides that work (only 'rfx undo' can bring it back).\n");
+
+        let confirm = Confirm::new("Delete the unmerged branches anyway?")
+            .with_default(false)
+            .prompt();
+
+        if !matches!(confirm, Ok(true)) {
+            names.retain(|n| !unmerged.contains(&n));
+            if names.is_empty() {
+                println!("Cancelled. Nothing was deleted.");
+                return;
+            }
+            println!("Keeping the unmerged branches.");
+        }
+    }
+
+    match delete_branches(git, &names) {
+        Ok(report) => {
+            println!();
+            for (name, sha) in &report.deleted {
+                println!("Deleted '{}' (was {}).", name, short_sha(Some(sha)));
+            }
+            for (name, e) in &report.failed {
+                eprintln!("Could not delete '{}': {}", name, e);
+            }
+            if !report.deleted.is_empty() {
+                println!("\nChanged your mind? Run 'rfx undo' to restore them.");
+            }
+        }
+        Err(e) => {
+            eprintln!("\nError deleting branches:");
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// First 7 characters of a commit hash
+fn short_sha(sha: Option<&str>) -> &str {
+    sha.map(|s| &s[..s.len().min(7)]).unwrap_or("nothing")
+}
+
+/// A branch offered by `rfx switch`
+enum SwitchTarget<'a> {
+    Local(&'a BranchInfo),
+    Remote(&'a RemoteBranchInfo),
+}
+
+/// Switch to an existing branch, picked by name or from a fuzzy list
+pub fn switch<G: GitBackend>(git: &G, branch: Option<String>) {
+    let locals = match branches_detailed(git) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("Error reading branches: {}", e);
+            return;
+        }
+    };
+
+    println!("\nFetching latest updates from remote...");
+    let remotes: Vec<RemoteBranchInfo> = get_remote_branches(git)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|r| !locals.iter().any(|l| l.name == r.short_name))
+        .collect();
+
+    let current = current_branch(git).unwrap_or_default();
+
+    let mut targets: Vec<SwitchTarget> = locals.iter().map(SwitchTarget::Local).collect();
+    targets.extend(remotes.iter().map(SwitchTarget::Remote));
+
+    let chosen = match branch {
+        Some(name) => {
+            let found = targets.iter().position(|t| match t {
+                SwitchTarget::Local(b) => b.name == name,
+                SwitchTarget::Remote(r) => r.short_name == name || r.full_name == name,
+            });
+            match found {
+                Some(i) => &targets[i],
+                None => {
+                    eprintln!("No branch named '{}' was found locally or on the remote.", name);
+                    eprintln!("(Hint: Use 'rfx new branch' to create it).");
+                    return;
+                }
+            }
+        }
+        None => {
+            let options: Vec<String> = targets.iter().map(|t| match t {
+                SwitchTarget::Local(b) => {
+                    let marker = if b.name == current { "*" } else { " " };
+                    format!("{} {: <20} | {: <15} | {}", marker, b.name, b.last_change, b.last_commit)
+                }
+                SwitchTarget::Remote(r) => {
+                    format!("  {: <20} | {: <15} | (on remote only, by {})", r.full_name, r.date, r.author)
+                }
+            }).collect();
+
+            let default_index = locals.iter().position(|b| b.name == current).unwrap_or(0);
+            let selection = Select::new("Switch to branch (type to search):", options.clone())
+                .with_filter(&|input, _, option, _| fuzzy_match(input, option))
+                .with_starting_cursor(default_index)
+                .with_page_size(10)
+                .prompt();
+
+            match selection {
+                Ok(s) => &targets[options.iter().position(|o| *o == s).unwrap()],
+                Err(_) => { println!("Cancelled."); return; }
+            }
+        }
+    };
+
+    let name = match chosen {
+        SwitchTarget::Local(b) => &b.name,
+        SwitchTarget::Remote(r) => &r.short_name,
+    };
+    if *name == current {
+        println!("You are already on '{}'.", current);
+        return;
+    }
+
+    if !carry_or_shelve(git, "switch branches", &format!("before switching to {}", name)) {
+        return;
+    }
+
+    let result = match chosen {
+        SwitchTarget::Local(b) => switch_branch(git, &b.name),
+        SwitchTarget::Remote(r) => switch_to_remote_branch(git, r),
+    };
+
+    match result {
+        Ok(_) => {
+            println!("\nSuccess! Switched to '{}'.", name);
+            if let SwitchTarget::Remote(r) = chosen {
+                println!("   Created a local copy that tracks '{}'.", r.full_name);
+            }
+        }
+        Err(RfxError::DirtyWorktree(_)) => {
+            eprintln!("\n[Switch Blocked]");
+            eprintln!("Your uncommitted changes conflict with the files on '{}'.", name);
+            eprintln!("\nAction: Shelve them with 'rfx shelve' (or commit them) and try again.");
+        }
+        Err(e) => {
+            eprintln!("\nError switching branches:");
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// Case-insensitive subsequence match: "fln" matches "feature/login"
+fn fuzzy_match(input: &str, option: &str) -> bool {
+    let option = option.to_lowercase();
+    let mut rest = option.chars();
+    input
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .all(|c| rest.any(|o| o == c))
+}
+
+pub fn show_status<G: GitBackend>(git: &G) {
+    let status = match get_status(git) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Error getting status: {}", e);
+            return;
+        }
+    };
+
+    println!("\nBranch: {}", status.pending.as_ref().and_then(|p| p.branch.as_deref()).unwrap_or(&status.branch));
+
+    if let Some(pending) = &status.pending {
+        println!();
+        print_pending(pending, git);
+        println!();
+        if matches!(pending.kind, InProgress::Rebase | InProgress::Bisect) {
+            // HEAD is detached, so there is no branch to compare with the remote
+            print_status_changes(&status.changes, true);
+            return;
+        }
+    }
+
+    // 1. Sync Status Logic
+    match (status.ahead, status.behind) {
+        (Some(0), Some(0)) => println!("Status: Up to date with remote"),
+        (Some(a), Some(b)) => {
+            if a > 0 { println!("Status: {} commit(s) ahead (Needs Push)", a); }
+            if b > 0 { println!("Status: {} commit(s) behind (Needs Pull)", b); }
+        }
+        (None, None) => {
+            println!("Status: Not published (Local only)");
+        }
+        _ => {}, 
+    }
+    println!();
+
+    print_status_changes(&status.changes, status.pending.is_some());
+}
+
+// 2. File Status
+fn print_status_changes(changes: &[FileChange], pending: bool) {
+    if changes.is_empty() {
+        println!("Working directory is clean.");
+    } else {
+        print_changes(changes);
+        // Mid-merge or mid-rebase the hints above already say what to do
+        if pending {
+            return;
+        }
+        println!("Tip: Use 'rfx new commit' to save these.");
+    }
+    println!();
+}
+
+/// List changed files grouped into conflicts, staged and unstaged changes
+fn print_changes(changes: &[FileChange]) {
+    let conflicts: Vec<&FileChange> = changes.iter().filter(|f| f.conflict.is_some()).collect();
+    let staged: Vec<&FileChange> = changes.iter().filter(|f| f.is_staged()).collect();
+    let unstaged: Vec<&FileChange> = changes.iter().filter(|f| f.is_unstaged()).collect();
+
+    if !conflicts.is_empty() {
+        println!("Conflicts (must be resolved):");
+        for file in conflicts {
+            let kind = file.conflict.map(|c| c.describe()).unwrap_or("");
+            println!("  [!!!] {} ({})", file.path, kind);
+        }
+        println!("Tip: Use 'rfx resolve' to settle them.");
+        println!();
+    }
+
+    if !staged.is_empty() {
+        println!("Staged Changes (ready to commit):");
+        for file in staged {
+            println!("  {} {}{}", file.index.label(), file.display_path(), submodule_note(file));
+        }
+        println!();
+    }
+
+    if !unstaged.is_empty() {
+        println!("Unsaved Changes:");
+        for file in unstaged {
+            println!("  {} {}{}", file.worktree.label(), file.path, submodule_note(file));
+        }
+        println!();
+    }
+}
+
+fn submodule_note(file: &FileChange) -> &'static str {
+    if file.submodule { " (submodule)" } else { "" }
+}
+
+pub fn pull<G: GitBackend>(git: &G, strategy: Option<PullStrategy>) {
+    // --- STEP 1: SAFETY CHECK (The "Action Prompt") ---
+    loop {
+        let changes = get_changed_files(git).unwrap_or_default();
+
+        if changes.is_empty() {
+            break;
+        }
+
+        println!("\nYou have uncommitted changes:");
+        for file in changes.iter().take(5) {
+            println!("   - {}", file.display_path());
+        }
+        if changes.len() > 5 { println!("   ...and {} more.", changes.len() - 5); }
+        println!();
+
+        let options = vec!["Commit changes now", "Shelve changes and continue", "Cancel"];
+        let choice = Select::new("What would you like to do?", options).prompt();
+
+        match choice {
+            Ok("Commit changes now") => {
+                new_commit(git); 
+            }
+            Ok("Shelve changes and continue") => {
+                if !shelve_before(git, "before pull") {
+                    return;
+                }
+            }
+            _ => {
+                println!("Pull cancelled.");
+                return;
+            }
+        }
+    }
+
+    // --- STEP 2: BRANCH SELECTION ---
+    println!("\nFetching latest updates from remote...");
+    
+    let branches = match get_remote_branches(git) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("Error fetching branches: {}", e);
+            return;
+        }
+    };
+
+    if branches.is_empty() {
+        println!("No remote branches found. (Are you connected to the internet?)");
+        return;
+    }
+
+    let options: Vec<String> = branches.iter().map(|b| {
+        format!("{: <25} | {: <15} | {}", b.full_name, b.author, b.date)
+    }).collect();
+
+    let selection = Select::new("Select branch to pull from:", options)
+        .with_page_size(10)
+        .prompt();
+
+    let selected_branch = match selection {
+        Ok(s) => {
+            let index = branches.iter().position(|b| {
+                 let fmt = format!("{: <25} | {: <15} | {}", b.full_name, b.author, b.date);
+                 fmt == s
+            }).unwrap();
+            &branches[index]
+        }
+        Err(_) => {
+            println!("Cancelled.");
+            return;
+        }
+    };
+
+    // --- STEP 3: CHOOSE HOW TO COMBINE ---
+    let check = match check_pull(git, selected_branch) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("\nError comparing with '{}': {}", selected_branch.full_name, e);
+            return;
+        }
+    };
+
+    if check.up_to_date() {
+        println!("\nAlready up to date. '{}' has nothing new for you.", selected_branch.full_name);
+        return;
+    }
+
+    // --- STEP 4: PREVIEW ---
+    if !show_pull_preview(git, selected_branch) {
+        return;
+    }
+
+    let strategy = match choose_pull_strategy(git, selected_branch, &check, strategy) {
+        Some(s) => s,
+        None => return,
+    };
+
+    // --- STEP 5: EXECUTE ---
+    println!("\n⬇ Pulling from '{}'...", selected_branch.full_name);
+
+    match pull_specific_branch(git, selected_branch, strategy) {
+        Ok(out) => {
+            if out.contains("Already up to date") {
+                 println!("Already up to date.");
+            } else {
+                 println!("Success! Updates received.");
+                 println!("{}", out);
+            }
+        }
+        Err(RfxError::MergeConflict(_)) => {
+            eprintln!("\nMerge Conflict Detected:");
+            eprintln!("   We downloaded the code, but couldn't combine it automatically.");
+            eprintln!("   Run 'rfx resolve' to go through the conflicting files.");
+            eprintln!("   Then run 'rfx continue' (or 'rfx abort' to give up).");
+        }
+        Err(RfxError::Diverged(_)) => {
+            eprintln!("\n[Pull Blocked]");
+            eprintln!("'{}' and your branch both changed since the check.", selected_branch.full_name);
+            eprintln!("\nAction: Run 'rfx pull --strategy merge' or 'rfx pull --strategy rebase'.");
+        }
+        Err(RfxError::DirtyWorktree(_)) => {
+            eprintln!("\n[Pull Blocked]");
+            eprintln!("Your uncommitted changes would be overwritten by the incoming code.");
+            eprintln!("\nAction: Commit your changes with 'rfx new commit' and try again.");
+        }
+        Err(e @ (RfxError::Network(_) | RfxError::AuthFailure(_))) => {
+            eprintln!("\n[Connection Error]");
+            eprintln!("{}", e);
+        }
+        Err(e) => {
+            eprintln!("\nError pulling:");
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// Show incoming commits, changed files and predicted conflicts, then ask
+/// whether to go ahead (false = cancelled)
+fn show_pull_preview<G: GitBackend>(git: &G, branch: &RemoteBranchInfo) -> bool {
+    let preview = match preview_pull(git, branch) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("\nWarning: Could not preview the pull: {}", e);
+            return matches!(Confirm::new("Pull anyway?").with_default(false).prompt(), Ok(true));
+        }
+    };
+
+    println!("\n[Pull Preview] {}", branch.full_name);
+
+    println!("\nIncoming commits:");
+    for c in preview.incoming.iter().take(10) {
+        let hash = if c.hash.len() > 7 { &c.hash[..7] } else { &c.hash };
+        println!("   {} {} ({}, {})", hash, c.message, c.author, c.date);
+    }
+    if preview.incoming.len() > 10 {
+        println!("   ...and {} more.", preview.incoming.len() - 10);
+    }
+
+    println!("\nFiles changed:");
+    for f in preview.files.iter().take(10) {
+        println!("   {} {}", f.state.label(), f.path);
+    }
+    if preview.files.len() > 10 {
+        println!("   ...and {} more.", preview.files.len() - 10);
+    }
+
+    let conflicts = preview.conflicts.as_deref();
+    match conflicts {
+        Some([]) => println!("\nNo conflicts expected."),
+        Some(files) => {
+            println!("\n[Warning] These files will conflict with your commits:");
+            for f in files {
+                println!("   [!!!] {}", f);
+            }
+            println!("'rfx resolve' will walk you through them after pulling.");
+        }
+        None => println!("\n(Conflict prediction needs git 2.38 or newer.)"),
+    }
+    println!();
+
+    let expect_conflicts = conflicts.is_some_and(|c| !c.is_empty());
+    match Confirm::new("Pull these changes?").with_default(!expect_conflicts).prompt() {
+        Ok(true) => true,
+        _ => {
+            println!("Pull cancelled. Nothing was changed.");
+            false
+        }
+    }
+}
+
+/// Explain what the pull will do and settle on a strategy (`None` = cancelled)
+fn choose_pull_strategy<G: GitBackend>(
+    git: &G,
+    branch: &RemoteBranchInfo,
+    check: &PullCheck,
+    requested: Option<PullStrategy>,
+) -> Option<PullStrategy> {
+    println!("\n'{}' has {} new commit(s) for you.", branch.full_name, check.behind);
+
+    if check.can_fast_forward() {
+        println!("Your branch has no commits of its own, so it simply moves forward (no merge needed).");
+        // Rebase and merge would do exactly the same; keep the user's choice if they made one
+        return Some(requested.unwrap_or(PullStrategy::FastForwardOnly));
+    }
+
+    println!("Your branch also has {} commit(s) that '{}' does not.", check.ahead, branch.full_name);
+    println!("A fast-forward is not possible: the two histories have to be combined.");
+
+    if let Some(strategy) = requested {
+        if check.allows(strategy) {
+            return Some(strategy);
+        }
+        eprintln!("\n'--strategy ff-only' cannot be used here. Use 'merge' or 'rebase' instead.");
+        return None;
+    }
+
+    match configured_pull_strategy(git) {
+        Ok(Some(strategy)) if check.allows(strategy) => {
+            println!("Using your configured strategy: {}.", pull_strategy_name(strategy));
+            return Some(strategy);
+        }
+        Ok(Some(_)) => println!("Your configured strategy (ff-only) cannot be used this time."),
+        Ok(None) => {}
+        Err(e) => eprintln!("Warning: {}", e),
+    }
+    println!();
+
+    let merge = "Merge: keep both histories and add a merge commit";
+    let rebase = if check.local_pushed {
+        "Rebase: replay your commits on top (NOT recommended: they are already pushed)"
+    } else {
+        "Rebase: replay your commits on top for a straight history"
+    };
+    let options = vec![merge, rebase, "Cancel"];
+
+    match Select::new("How should the changes be combined?", options).prompt() {
+        Ok(choice) if choice == merge => Some(PullStrategy::Merge),
+        Ok(choice) if choice == rebase => Some(PullStrategy::Rebase),
+        _ => {
+            println!("Pull cancelled.");
+            None
+        }
+    }
+}
+
+pub fn push<G: GitBackend>(git: &G, force: bool) {
+    // --- STEP 1: SAFETY CHECK ---
+    loop {
+        let changes = get_changed_files(git).unwrap_or_default();
+
+        if changes.is_empty() {
+            break;
+        }
+
+        println!("\nYou have uncommitted changes:");
+        for file in changes.iter().take(5) {
+            println!("   - {}", file.display_path());
+        }
+        if changes.len() > 5 { println!("   ...and {} more.", changes.len() - 5); }
+        println!();
+
+        let options = vec![
+            "Commit changes now (Recommended)",
+            "Push existing commits (Keep changes local)", 
+            "Shelve changes and continue",
+            "Cancel"
+        ];
+        
+        let choice = Select::new("What would you like to do?", options).prompt();
+
+        match choice {
+            Ok("Commit changes now (Recommended)") => {
+                new_commit(git); 
+            }
+            Ok("Shelve changes and continue") => {
+                if !shelve_before(git, "before push") {
+                    return;
+                }
+            }
+            Ok("Push existing commits (Keep changes local)") => {
+                println!("\n[Note] Your uncommitted changes will NOT be sent to the server.");
+                break;
+            }
+            _ => {
+                println!("Push cancelled.");
+                return;
+            }
+        }
+    }
+
+    // --- STEP 2: BRANCH SELECTION ---
+    println!("\nPreparing to push...");
+
+    // 1. Get detailed list of LOCAL branches
+    let branches = match branches_detailed(git) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("Error reading branches: {}", e);
+            return;
+        }
+    };
+
+    if branches.is_empty() {
+        println!("No branches found (this is very strange).");
+        return;
+    }
+
+    // 2. Identify current branch to mark it as default
+    let current_branch = current_branch(git).unwrap_or_default();
+
+    // 3. Format the menu
+    let options: Vec<String> = branches.iter().map(|b| {
+        let marker = if b.name == current_branch { "*" } else { " " };
+        format!("{} {: <15} | {: <15} | {}", marker, b.name, b.author, b.last_change)
+    }).collect();
+
+    let default_index = branches.iter().position(|b| b.name == current_branch).unwrap_or(0);
+
+    let selection = Select::new("Select branch to push:", options)
+        .with_starting_cursor(default_index)
+        .with_page_size(10)
+        .prompt();
+
+    let selected_branch_name = match selection {
+        Ok(s) => {
+            let index = branches.iter().position(|b| {
+                let marker = if b.name == current_branch { "*" } else { " " };
+                let fmt = format!("{} {: <15} | {: <15} | {}", marker, b.name, b.author, b.last_change);
+                fmt == s
+            }).unwrap();
+            &branches[index].name
+        }
+        Err(_) => {
+            println!("Cancelled.");
+            return;
+        }
+    };
+
+    // --- STEP 3: PICK THE REMOTE ---
+    let remote = match pick_push_remote(git, selected_branch_name) {
+        Some(r) => r,
+        None => return,
+    };
+
+    // --- STEP 4: EXECUTE ---
+    if force {
+        force_push_branch(git, selected_branch_name, &remote);
+        return;
+    }
+
+    println!("\nPushing '{}' to {}...", selected_branch_name, remote);
+    
+    match push_branch(git, selected_branch_name, &remote) {
+        Ok(out) => {
+            println!("\nSuccess! Code pushed to {}.", remote);
+            if !out.trim().is_empty() {
+                println!("{}", out); 
+            }
+        }
+        Err(RfxError::PushRejected(_)) => {
+            eprintln!("\n[Push Rejected]");
+            eprintln!("The remote repository has changes that you do not have.");
+            eprintln!("(Someone else pushed code recently, or you amended or rebased commits that were already pushed).");
+            eprintln!("\nAction: Run 'rfx pull' first to update your branch.");
+
+            let protected = protected_branches(git).unwrap_or_default();
+            if !is_protected(&protected, selected_branch_name) {
+                eprintln!("   If you rewrote your own commits, replace the remote branch instead.");
+                let replace = Confirm::new("Replace the remote branch with yours (safe force-push)?")
+                    .with_default(false)
+                    .prompt();
+                if let Ok(true) = replace {
+                    force_push_branch(git, selected_branch_name, &remote);
+                }
+            }
+        }
+        Err(RfxError::AuthFailure(_)) => {
+            eprintln!("\n[Authentication Error]");
+            eprintln!("The remote server did not accept your credentials.");
+            eprintln!("Check your SSH key or access token and try again.");
+        }
+        Err(RfxError::Network(_)) => {
+            eprintln!("\n[Connection Error]");
+            eprintln!("Could not connect to the remote server.");
+        }
+        Err(e) => {
+            eprintln!("\nError pushing changes:");
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// Overwrite the remote branch after showing what would be lost.
+///
+/// The push only goes through if the remote branch is still where rfx saw
+/// it, so commits pushed by someone else in the meantime are never lost.
+fn force_push_branch<G: GitBackend>(git: &G, branch: &str, remote: &str) {
+    println!("\nChecking '{}/{}'...", remote, branch);
+
+    match protected_branches(git) {
+        Ok(patterns) if is_protected(&patterns, branch) => {
+            eprintln!("\n[Protected Branch]");
+            eprintln!("'{}' is a protected branch. rfx will not force-push to it.", branch);
+            eprintln!("(Hint: Change the list with 'rfx config set branch.protected \"main, release/*\"').");
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("\nError reading the protected branches:");
+            eprintln!("{}", e);
+            return;
+        }
+    }
+
+    let plan = match plan_force_push(git, branch, remote) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("\nError checking the remote branch:");
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    if plan.expected.as_deref() == Some(plan.local.as_str()) {
+        println!("'{}/{}' is already up to date. Nothing to push.", remote, branch);
+        return;
+    }
+
+    if plan.overwritten_count == 0 {
+        println!("\nNothing on '{}/{}' will be lost.", remote, branch);
+    } else {
+        println!("\n[Warning] These commit(s) on '{}/{}' will be removed from the remote:", remote, branch);
+        for c in plan.overwritten.iter().take(10) {
+            println!("   {} {} ({}, {})", short_sha(Some(&c.hash)), c.message, c.author, c.date);
+        }
+        if plan.overwritten_count > 10 {
+            println!("   ...and {} more.", plan.overwritten_count - 10);
+        }
+        println!("After an amend or rebase these are usually the old versions of your own commits.");
+        println!("If you do not recognise them, cancel and run 'rfx pull' instead.");
+    }
+    println!();
+
+    match Confirm::new(&format!("Force-push '{}' to {}?", branch, remote)).with_default(false).prompt() {
+        Ok(true) => {}
+        _ => {
+            println!("Push cancelled. Nothing was changed.");
+            return;
+        }
+    }
+
+    match force_push(git, &plan) {
+        Ok(_) => {
+            println!("\nSuccess! '{}/{}' now matches your branch.", remote, branch);
+            if plan.overwritten_count > 0 {
+                println!("   Changed your mind? 'rfx undo' puts the old commits back on {}.", remote);
+            }
+        }
+        Err(RfxError::PushRejected(_)) => {
+            eprintln!("\n[Push Rejected]");
+            eprintln!("Someone pushed to '{}/{}' after rfx checked it, so nothing was overwritten.", remote, branch);
+            eprintln!("\nAction: Run 'rfx pull' to get their commits, or 'rfx push --force' to check again.");
+        }
+        Err(e) => {
+            eprintln!("\nError pushing changes:");
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// The remote to push `branch` to: the remembered choice, the only remote,
+/// or one picked by the user (who can ask rfx to remember it)
+fn pick_push_remote<G: GitBackend>(git: &G, branch: &str) -> Option<String> {
+    match configured_push_remote(git, branch) {
+        Ok(Some(remote)) => return Some(remote),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("\nError reading the push remote: {}", e);
+            return None;
+        }
+    }
+
+    let remotes: Vec<RemoteInfo> = remotes_detailed(git)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|r| r.direction == "push")
+        .collect();
+
+    match remotes.len() {
+        0 => {
+            eprintln!("\nThis repository has no remotes. Add one with 'git remote add origin <url>'.");
+            None
+        }
+        1 => Some(remotes[0].name.clone()),
+        _ => {
+            let options: Vec<String> = remotes.iter().map(|r| format!("{: <12} {}", r.name, r.url)).collect();
+            let default_name = default_remote(git).unwrap_or_default();
+            let start = remotes.iter().position(|r| r.name == default_name).unwrap_or(0);
+
+            let remote = match Select::new("Push to which remote?", options.clone()).with_starting_cursor(start).prompt() {
+                Ok(s) => remotes[options.iter().position(|o| *o == s).unwrap()].name.clone(),
+                Err(_) => { println!("Cancelled."); return None; }
+            };
+
+            let remember = Confirm::new(&format!("Always push '{}' to '{}'?", branch, remote))
+                .with_default(true)
+                .prompt();
+            if matches!(remember, Ok(true)) && let Err(e) = remember_push_remote(git, branch, &remote) {
+                eprintln!("Could not save this choice: {}", e);
+            }
+            Some(remote)
+        }
+    }
+}
+
+/// Update a fork from the original repository and push the result to the fork
+pub fn sync<G: GitBackend>(git: &G, from: Option<String>, to: Option<String>) {
+    let from = from.unwrap_or_else(|| {
+        config::load(git)
+            .ok()
+            .and_then(|c| c.text("remote.upstream"))
+            .unwrap_or_else(|| "upstream".to_string())
+    });
+    let to = match to.map(Ok).unwrap_or_else(|| default_remote(git)) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
+
+    if from == to {
+        eprintln!("Error: The remote to sync from and the one to push to are both '{}'.", from);
+        return;
+    }
+    if !remote_names(git).unwrap_or_default().contains(&from) {
+        eprintln!("Error: There is no remote named '{}'.", from);
+        eprintln!("(Hint: Add the original repository with 'git remote add {} <url>').", from);
+        return;
+    }
+
+    println!("\nSyncing your fork: '{}' -> your branch -> '{}'...", from, to);
+
+    match sync_fork(git, &from, &to) {
+        Ok(report) => {
+            if report.updated {
+                println!("\nSuccess! '{}' now has the latest changes from '{}' and was pushed to '{}'.", report.branch, from, to);
+            } else {
+                println!("\n'{}' was already up to date with '{}'; '{}' is in sync too.", report.branch, from, to);
+            }
+        }
+        Err(RfxError::MergeConflict(_)) => {
+            eprintln!("\nMerge Conflict Detected:");
+            eprintln!("   Your branch and '{}' changed the same lines.", from);
+            eprintln!("   Run 'rfx resolve' to settle the conflicts, then 'rfx push'.");
+        }
+        Err(RfxError::Diverged(_)) => {
+            eprintln!("\n[Sync Blocked]");
+            eprintln!("Your branch has commits that '{}' does not, so it cannot simply be updated.", from);
+            eprintln!("\nAction: Switch to it and run 'rfx pull' to combine them, then sync again.");
+        }
+        Err(RfxError::PushRejected(_)) => {
+            eprintln!("\n[Push Rejected]");
+            eprintln!("Your branch was updated, but '{}' has commits that '{}' does not.", to, from);
+            eprintln!("\nAction: Run 'rfx pull' from '{}' to combine them, then push again.", to);
+        }
+        Err(e @ (RfxError::Network(_) | RfxError::AuthFailure(_))) => {
+            eprintln!("\n[Connection Error]");
+            eprintln!("{}", e);
+        }
+        Err(e) => {
+            eprintln!("\nError syncing fork:");
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// Shelve everything (untracked files included) so a safety loop can continue.
+/// Returns false if shelving failed.
+fn shelve_before<G: GitBackend>(git: &G, name: &str) -> bool {
+    match shelf::shelve(git, name, true) {
+        Ok(()) => {
+            println!("\nYour changes are on the shelf as \"{}\".", name);
+            println!("   Run 'rfx unshelve' to bring them back when you are done.");
+            true
+        }
+        Err(e) => {
+            eprintln!("\nError shelving changes:");
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+/// Set local changes aside under a name
+pub fn shelve<G: GitBackend>(git: &G, name: Option<String>, include_untracked: bool) {
+    let changes = get_changed_files(git).unwrap_or_default();
+    if changes.is_empty() {
+        println!("Working directory is clean. Nothing to shelve.");
+        return;
+    }
+
+    println!("\n[Shelve Changes]");
+    print_changes(&changes);
+    if !include_untracked && changes.iter().any(|f| f.worktree == FileState::Untracked) {
+        println!("Note: New (untracked) files stay where they are. Use -u to shelve them too.\n");
+    }
+
+    let name = match name {
+        Some(n) => n,
+        None => match Text::new("Name for these changes (optional):").prompt() {
+            Ok(n) => n,
+            Err(_) => { println!("Cancelled."); return; }
+        },
+    };
+
+    match shelf::shelve(git, &name, include_untracked) {
+        Ok(()) => {
+            println!("\nSuccess! Changes shelved.");
+            if get_changed_files(git).unwrap_or_default().is_empty() {
+                println!("   Your working directory is clean.");
+            }
+            println!("   Run 'rfx unshelve' to bring them back.");
+        }
+        Err(e) => {
+            eprintln!("\nError shelving changes:");
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// List shelved changes with the files in each entry
+pub fn show_shelf<G: GitBackend>(git: &G, json: bool) {
+    let entries = match shelf::list_shelf(git) {
+        Ok(e) => e,
+        Err(e) => {
+            if json {
+                print_error_json(&e);
+            } else {
+                eprintln!("Error reading the shelf: {}", e);
+            }
+            return;
+        }
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+        return;
+    }
+
+    if entries.is_empty() {
+        println!("The shelf is empty. Use 'rfx shelve' to set changes aside.");
+        return;
+    }
+
+    for (i, entry) in entries.iter().enumerate() {
+        println!("\n#{} {} (from '{}', {})", i, entry.name, entry.branch, entry.age);
+        for file in entry.files.iter().take(5) {
+            println!("   {} {}", file.state.label(), file.path);
+        }
+        if entry.files.len() > 5 {
+            println!("   ...and {} more.", entry.files.len() - 5);
+        }
+    }
+    println!();
+}
+
+/// Bring shelved changes back, by name, `#` number, or picked from a list
+pub fn unshelve<G: GitBackend>(git: &G, name: Option<String>) {
+    let entries = match shelf::list_shelf(git) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("Error reading the shelf: {}", e);
+            return;
+        }
+    };
+
+    if entries.is_empty() {
+        println!("The shelf is empty. Nothing to unshelve.");
+        return;
+    }
+
+    let entry = match name {
+        Some(n) => match find_shelf_entry(&entries, &n) {
+            Some(e) => e,
+            None => {
+                eprintln!("Nothing named '{}' on the shelf. Run 'rfx shelf' to see what is there.", n);
+                return;
+            }
+        },
+        None => {
+            let options: Vec<String> = entries.iter().enumerate().map(|(i, e)| {
+                format!("#{} {} ({} files, from '{}', {})", i, e.name, e.files.len(), e.branch, e.age)
+            }).collect();
+
+            match Select::new("Select changes to bring back:", options.clone()).prompt() {
+                Ok(s) => &entries[options.iter().position(|o| *o == s).unwrap()],
+                Err(_) => { println!("Cancelled."); return; }
+            }
+        }
+    };
+
+    println!("\nBringing back \"{}\"...", entry.name);
+
+    match shelf::unshelve(git, &entry.reference) {
+        Ok(UnshelveOutcome::Restored) => {
+            println!("Success! Your changes are back and have been removed from the shelf.");
+        }
+        Ok(UnshelveOutcome::RestoredUnstaged) => {
+            println!("Success! Your changes are back and have been removed from the shelf.");
+            println!("   (They could not be restored as staged, so all of them are unstaged now.)");
+        }
+        Err(RfxError::MergeConflict(_)) => {
+            eprintln!("\n[Conflicts]");
+            eprintln!("Your changes are back, but some of them conflict with the current code.");
+            eprintln!("Run 'rfx resolve' to settle the files marked below:");
+            for file in get_changed_files(git).unwrap_or_default().iter().filter(|f| f.conflict.is_some()) {
+                eprintln!("   [!!!] {}", file.path);
+            }
+            eprintln!("\nThe entry is kept on the shelf as a backup until you remove it");
+            eprintln!("with 'git stash drop {}'.", entry.reference);
+        }
+        Err(RfxError::DirtyWorktree(_)) => {
+            eprintln!("\n[Unshelve Blocked]");
+            eprintln!("Your current uncommitted changes overlap with the shelved ones.");
+            eprintln!("\nAction: Commit or shelve your current changes, then try again.");
+        }
+        Err(e) => {
+            eprintln!("\nError unshelving:");
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// Match an entry by `#n` / `n`, git reference, or name
+fn find_shelf_entry<'a>(entries: &'a [ShelfEntry], query: &str) -> Option<&'a ShelfEntry> {
+    let number = query.trim_start_matches('#').parse::<usize>().ok();
+    if let Some(i) = number && i < entries.len() {
+        return entries.get(i);
+    }
+    entries.iter().find(|e| e.reference == query || e.name == query)
+}
+
+/// Pick files and throw their changes away, keeping a copy in the trash
+pub fn discard<G: GitBackend>(git: &G) {
+    let changes: Vec<FileChange> = match get_changed_files(git) {
+        Ok(c) => c.into_iter().filter(|f| f.conflict.is_none() && !f.submodule).collect(),
+        Err(e) => {
+            eprintln!("Error reading changes: {}", e);
+            return;
+        }
+    };
+
+    if changes.is_empty() {
+        println!("Nothing to discard. Your working directory is clean.");
+        return;
+    }
+
+    let options: Vec<String> = changes
+        .iter()
+        .map(|f| format!("{} {}", discard_label(f), f.display_path()))
+        .collect();
+    let selected = match MultiSelect::new("Select files to discard (Space to toggle):", options.clone())
+        .with_page_size(10)
+        .prompt()
+    {
+        Ok(s) if s.is_empty() => {
+            println!("No files selected. Nothing was discarded.");
+            return;
+        }
+        Ok(s) => s,
+        Err(_) => {
+            println!("Cancelled.");
+            return;
+        }
+    };
+    let files: Vec<FileChange> = changes
+        .into_iter()
+        .zip(options)
+        .filter(|(_, option)| selected.contains(option))
+        .map(|(f, _)| f)
+        .collect();
+
+    println!("\nThese changes will be thrown away:");
+    for file in &files {
+        let what = if file.worktree == FileState::Untracked || file.index == FileState::Added {
+            "new file, will be deleted".to_string()
+        } else if file.worktree == FileState::Deleted || file.index == FileState::Deleted {
+            "deleted, will come back".to_string()
+        } else if let Some(from) = &file.renamed_from {
+            format!("renamed, goes back to '{}'", from)
+        } else {
+            match diff::file_preview(git, file) {
+                Ok(Some(d)) if d.binary => "binary file, back to the last commit".to_string(),
+                Ok(Some(d)) => format!("+{} -{} lines lost", d.added, d.removed),
+                _ => "back to the last commit".to_string(),
+            }
+        };
+        println!("   {} {} ({})", discard_label(file), file.display_path(), what);
+    }
+    println!("\nStaged and unstaged edits of these files are both discarded.");
+    println!("A copy goes to the trash first ('rfx trash restore' brings it back).");
+
+    let confirmed = Confirm::new(&format!("Discard changes to {} file(s)?", files.len()))
+        .with_default(false)
+        .prompt();
+    if !matches!(confirmed, Ok(true)) {
+        println!("Cancelled. Nothing was discarded.");
+        return;
+    }
+
+    match trash::discard(git, &files) {
+        Ok(entry) => {
+            println!("\nSuccess! Discarded changes to {} file(s).", entry.files.len());
+            println!("   Changed your mind? Run 'rfx trash restore {}'.", entry.id);
+        }
+        Err(e) => {
+            eprintln!("\nError discarding changes:");
+            eprintln!("{}", e);
+        }
+    }
+}
+
+fn discard_label(file: &FileChange) -> &'static str {
+    if file.worktree == FileState::Unmodified {
+        file.index.label()
+    } else {
+        file.worktree.label()
+    }
+}
+
+/// List what is in the trash
+pub fn show_trash<G: GitBackend>(git: &G, json: bool) {
+    let entries = match trash::list_trash(git) {
+        Ok(e) => e,
+        Err(e) => {
+            if json {
+                print_error_json(&e);
+            } else {
+                eprintln!("Error reading the trash: {}", e);
+            }
+            return;
+        }
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+        return;
+    }
+
+    if entries.is_empty() {
+        println!("The trash is empty.");
+        return;
+    }
+
+    for (i, entry) in entries.iter().enumerate() {
+        println!("\n#{} {} (from '{}', {})", i, entry.id, entry.branch, journal::age(entry.timestamp));
+        for file in entry.files.iter().take(5) {
+            let note = if !file.saved {
+                " (was deleted)"
+            } else if file.new_file {
+                " (new file)"
+            } else {
+                ""
+            };
+            println!("   {}{}", file.path, note);
+        }
+        if entry.files.len() > 5 {
+            println!("   ...and {} more.", entry.files.len() - 5);
+        }
+    }
+    println!("\nUse 'rfx trash restore' to bring files back.");
+    println!();
+}
+
+/// Put discarded files back, by id, `#` number, or picked from a list
+pub fn restore_trash<G: GitBackend>(git: &G, id: Option<String>) {
+    let entries = match trash::list_trash(git) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("Error reading the trash: {}", e);
+            return;
+        }
+    };
+
+    if entries.is_empty() {
+        println!("The trash is empty. Nothing to restore.");
+        return;
+    }
+
+    let entry = match id {
+        Some(query) => match find_trash_entry(&entries, &query) {
+            Some(e) => e,
+            None => {
+                eprintln!("Nothing called '{}' in the trash. Run 'rfx trash list' to see what is there.", query);
+                return;
+            }
+        },
+        None => {
+            let options: Vec<String> = entries.iter().enumerate().map(|(i, e)| {
+                format!("#{} {} file(s) from '{}', {}", i, e.files.len(), e.branch, journal::age(e.timestamp))
+            }).collect();
+
+            match Select::new("Select discarded changes to bring back:", options.clone()).prompt() {
+                Ok(s) => &entries[options.iter().position(|o| *o == s).unwrap()],
+                Err(_) => { println!("Cancelled."); return; }
+            }
+        }
+    };
+
+    let clashes = match trash::restore_conflicts(git, entry) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error reading the trash: {}", e);
+            return;
+        }
+    };
+    if !clashes.is_empty() {
+        println!("\nThese files have changed since they were discarded:");
+        for path in &clashes {
+            println!("   {}", path);
+        }
+        let overwrite = Confirm::new("Overwrite them with the discarded version?")
+            .with_default(false)
+            .prompt();
+        if !matches!(overwrite, Ok(true)) {
+            println!("Cancelled. Nothing was restored.");
+            return;
+        }
+    }
+
+    match trash::restore(git, entry) {
+        Ok(count) => {
+            println!("\nSuccess! Restored {} file(s) and removed them from the trash.", count);
+            println!("   They are back as unstaged changes.");
+        }
+        Err(e) => {
+            eprintln!("\nError restoring files:");
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// Match an entry by `#n` / `n` or id
+fn find_trash_entry<'a>(entries: &'a [TrashEntry], query: &str) -> Option<&'a TrashEntry> {
+    if let Some(e) = entries.iter().find(|e| e.id == query) {
+        return Some(e);
+    }
+    let number = query.trim_start_matches('#').parse::<usize>().ok();
+    number.and_then(|i| entries.get(i))
+}
+
+/// Delete the trash after confirmation
+pub fn empty_trash<G: GitBackend>(git: &G) {
+    let count = match trash::list_trash(git) {
+        Ok(e) => e.len(),
+        Err(e) => {
+            eprintln!("Error reading the trash: {}", e);
+            return;
+        }
+    };
+
+    if count == 0 {
+        println!("The trash is already empty.");
+        return;
+    }
+
+    let confirmed = Confirm::new(&format!("Delete {} discarded set(s) of changes for good?", count))
+        .with_default(false)
+        .prompt();
+    if !matches!(confirmed, Ok(true)) {
+        println!("Cancelled. The trash was kept.");
+        return;
+    }
+
+    match trash::empty_trash(git) {
+        Ok(n) => println!("Emptied the trash ({} set(s) deleted).", n),
+        Err(e) => eprintln!("Error emptying the trash: {}", e),
+    }
+}
+
+/// Walk through conflicted files and settle them one at a time
+pub fn resolve<G: GitBackend>(git: &G) {
+    let pending = progress::detect(git).ok().flatten();
+
+    loop {
+        let files = match conflict::conflicted_files(git) {
+            Ok(f) => f,
+            Err(e) => {
+                eprintln!("Error reading conflicts: {}", e);
+                return;
+            }
+        };
+
+        if files.is_empty() {
+            finish_resolving(git);
+            return;
+        }
+
+        println!("\n[Conflicts] {} file(s) need a decision.", files.len());
+
+        const DONE: &str = "Stop here (finish later with 'rfx resolve')";
+        let abort_choice = pending.as_ref().map(|p| format!("Abort the {}", p.name()));
+
+        let mut options: Vec<String> = files
+            .iter()
+            .map(|f| format!("{} ({})", f.path, f.conflict.map(|c| c.describe()).unwrap_or("")))
+            .collect();
+        options.push(DONE.to_string());
+        if let Some(abort) = &abort_choice {
+            options.push(abort.clone());
+        }
+
+        let choice = match Select::new("Which file do you want to resolve?", options).prompt() {
+            Ok(c) => c,
+            Err(_) => return,
+        };
+
+        if choice == DONE {
+            println!("Conflicts left as they are. Run 'rfx resolve' again to continue.");
+            return;
+        }
+        if abort_choice.as_ref() == Some(&choice) {
+            abort(git);
+            return;
+        }
+
+        if let Some(file) = files.iter().find(|f| choice.starts_with(&format!("{} (", f.path))) {
+            resolve_one(git, file);
+        }
+    }
+}
+
+/// Offer every way of resolving a single file
+fn resolve_one<G: GitBackend>(git: &G, file: &FileChange) {
+    let kind = file.conflict.unwrap_or(ConflictKind::BothModified);
+
+    if conflict::is_delete_conflict(kind) {
+        return resolve_deleted(git, file, kind);
+    }
+
+    let parsed = match conflict::load_conflicts(git, &file.path) {
+        Ok(Some(parsed)) => Some(parsed),
+        Ok(None) => return resolve_deleted(git, file, kind),
+        Err(RfxError::Validation(msg)) => {
+            println!("\n{}", msg);
+            None
+        }
+        Err(e) => {
+            eprintln!("Error reading '{}': {}", file.path, e);
+            return;
+        }
+    };
+
+    const WALK: &str = "Go through the conflicts one by one";
+    const ALL_OURS: &str = "Keep our side of every conflict";
+    const ALL_THEIRS: &str = "Keep their side of every conflict";
+    const MARK: &str = "Mark as resolved (keep the file as it is)";
+    const EDITOR: &str = "Open in editor";
+    const TOOL: &str = "Open merge tool";
+    const SKIP: &str = "Skip this file for now";
+
+    let hunks = parsed.as_ref().map(|p| p.hunk_count()).unwrap_or(0);
+    let mut options = Vec::new();
+    if hunks > 0 {
+        println!("\n'{}' has {} conflict(s).", file.path, hunks);
+        options.extend([WALK, ALL_OURS, ALL_THEIRS]);
+    } else {
+        println!("\n'{}' has no conflict markers left.", file.path);
+        options.push(MARK);
+    }
+    if parsed.is_some() {
+        options.push(EDITOR);
+    }
+    options.extend([TOOL, SKIP]);
+
+    let result = match Select::new("How do you want to resolve it?", options).prompt() {
+        Ok(WALK) => match parsed.as_ref().and_then(choose_hunks) {
+            Some(choices) => parsed.as_ref().map(|p| conflict::resolve_file(git, p, &choices)),
+            None => {
+                println!("Left '{}' unresolved.", file.path);
+                return;
+            }
+        },
+        Ok(ALL_OURS) => parsed.as_ref().map(|p| conflict::resolve_file(git, p, &vec![Resolution::Ours; hunks])),
+        Ok(ALL_THEIRS) => parsed.as_ref().map(|p| conflict::resolve_file(git, p, &vec![Resolution::Theirs; hunks])),
+        Ok(MARK) => Some(conflict::mark_resolved(git, &file.path)),
+        Ok(EDITOR) => Some(edit_file(git, &file.path).and_then(|_| conflict::mark_resolved(git, &file.path))),
+        Ok(TOOL) => Some(git.mergetool(&file.path)),
+        _ => return,
+    };
+
+    match result {
+        Some(Ok(())) => println!("Resolved '{}'.", file.path),
+        Some(Err(RfxError::Validation(msg))) => {
+            println!("{}", msg);
+            println!("   It stays on the list. Fix what is left and pick it again.");
+        }
+        Some(Err(e)) => eprintln!("Error resolving '{}': {}", file.path, e),
+        None => {}
+    }
+}
+
+/// Ask for a decision on every hunk; `None` if the user stops early
+fn choose_hunks(file: &ConflictFile) -> Option<Vec<Resolution>> {
+    const OURS: &str = "Keep ours";
+    const THEIRS: &str = "Keep theirs";
+    const BOTH: &str = "Keep both (ours first)";
+    const STOP: &str = "Stop (leave this file unresolved)";
+
+    let total = file.hunk_count();
+    let mut choices = Vec::new();
+
+    for (i, hunk) in file.hunks().enumerate() {
+        println!("\n--- Conflict {}/{} in {} (line {}) ---", i + 1, total, file.path, hunk.line);
+        print_side(&format!("Ours ({})", hunk.ours_label), &hunk.ours, '<');
+        match &hunk.base {
+            Some(base) => print_side("Base (before both changes)", base, '|'),
+            None => println!("\nBase: not available"),
+        }
+        print_side(&format!("Theirs ({})", hunk.theirs_label), &hunk.theirs, '>');
+        println!();
+
+        let choice = match Select::new("Keep which side?", vec![OURS, THEIRS, BOTH, STOP]).prompt() {
+            Ok(OURS) => Resolution::Ours,
+            Ok(THEIRS) => Resolution::Theirs,
+            Ok(BOTH) => Resolution::Both,
+            _ => return None,
+        };
+        choices.push(choice);
+    }
 
-    if branches.is_empty() {
-        println!("No branches found (this is very strange).");
-        return;
+    Some(choices)
+}
+
+fn print_side(title: &str, lines: &str, marker: char) {
+    println!("\n{}:", title);
+    if lines.is_empty() {
+        println!("   {} (nothing)", marker);
     }
+    for line in lines.lines() {
+        println!("   {} {}", marker, line);
+    }
+}
 
-    // 2. Identify current branch to mark it as default
-    let current_branch = crate::adapters::git_branch().unwrap_or_default();
+/// One side deleted the file, the other kept or changed it
+fn resolve_deleted<G: GitBackend>(git: &G, file: &FileChange, kind: ConflictKind) {
+    let (keep, delete) = match kind {
+        ConflictKind::BothDeleted => {
+            println!("\nBoth sides deleted '{}'.", file.path);
+            ("Keep the file anyway", "Delete the file")
+        }
+        ConflictKind::DeletedByUs => {
+            println!("\nYour side deleted '{}', but their side changed it.", file.path);
+            ("Keep their changed file", "Delete the file")
+        }
+        _ => {
+            println!("\nTheir side deleted '{}', but your side changed it.", file.path);
+            ("Keep your changed file", "Delete the file")
+        }
+    };
 
-    // 3. Format the menu
-    let options: Vec<String> = branches.iter().map(|b| {
-        let marker = if b.name == current_branch { "*" } else { " " };
-        format!("{} {: <15} | {: <15} | {}", marker, b.name, b.author, b.last_change)
-    }).collect();
+    let keep_file = match Select::new("What should happen to it?", vec![keep, delete, "Skip this file for now"]).prompt() {
+        Ok(c) if c == keep => true,
+        Ok(c) if c == delete => false,
+        _ => return,
+    };
 
-    let default_index = branches.iter().position(|b| b.name == current_branch).unwrap_or(0);
+    match conflict::keep_or_delete(git, &file.path, keep_file) {
+        Ok(()) => println!("Resolved '{}'.", file.path),
+        Err(e) => eprintln!("Error resolving '{}': {}", file.path, e),
+    }
+}
 
-    let selection = Select::new("Select branch to push:", options)
-        .with_starting_cursor(default_index)
-        .with_page_size(10)
-        .prompt();
+/// Open a file in the user's git editor and wait for it to close
+fn edit_file<G: GitBackend>(git: &G, path: &str) -> Result<(), RfxError> {
+    let editor = git.editor()?;
+    let full = git.work_tree()?.join(path);
+
+    // The editor setting may carry arguments ("code --wait"), so let the shell split it
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(&full)
+        .status()
+        .map_err(|e| RfxError::Io(format!("Could not start editor '{}': {}", editor, e)))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(RfxError::Io(format!("Editor '{}' exited with an error.", editor)))
+    }
+}
 
-    let selected_branch_name = match selection {
-        Ok(s) => {
-            let index = branches.iter().position(|b| {
-                let marker = if b.name == current_branch { "*" } else { " " };
-                let fmt = format!("{} {: <15} | {: <15} | {}", marker, b.name, b.author, b.last_change);
-                fmt == s
-            }).unwrap();
-            &branches[index].name
+/// Every conflict is settled: carry on with (or abort) what was interrupted
+fn finish_resolving<G: GitBackend>(git: &G) {
+    let pending = match progress::detect(git) {
+        Ok(Some(p)) if p.kind != InProgress::Bisect => p,
+        _ => {
+            println!("\nNo conflicts left. Resolved files are staged.");
+            println!("   Run 'rfx new commit' to save them.");
+            return;
         }
-        Err(_) => {
-            println!("Cancelled.");
+    };
+
+    println!("\nAll conflicts are resolved.");
+
+    let carry_on = format!("Continue the {}", pending.name());
+    let give_up = format!("Abort the {}", pending.name());
+    const LATER: &str = "Decide later";
+
+    match Select::new("What now?", vec![carry_on.as_str(), give_up.as_str(), LATER]).prompt() {
+        Ok(c) if c == carry_on => continue_operation(git),
+        Ok(c) if c == give_up => abort(git),
+        _ => println!("The {} is still in progress. Run 'rfx continue' or 'rfx abort' when ready.", pending.name()),
+    }
+}
+
+/// Give up on an interrupted merge, rebase, cherry-pick, revert or bisect
+pub fn abort<G: GitBackend>(git: &G) {
+    let pending = match progress::detect(git) {
+        Ok(Some(p)) => p,
+        Ok(None) => {
+            println!("Nothing to abort: no merge, rebase, cherry-pick, revert or bisect is in progress.");
+            return;
+        }
+        Err(e) => {
+            eprintln!("Error checking the repository state: {}", e);
             return;
         }
     };
 
-    // --- STEP 3: EXECUTE ---
-    println!("\nPushing '{}' to origin...", selected_branch_name);
-    
-    match push_branch(selected_branch_name) {
-        Ok(out) => {
-            println!("\nSuccess! Code pushed to origin.");
-            if !out.trim().is_empty() {
-                println!("{}", out); 
+    let name = pending.name();
+    if pending.kind == InProgress::Bisect {
+        println!("\nStopping the bisect returns you to '{}'.", pending.branch.as_deref().unwrap_or("where you started"));
+    } else {
+        println!("\nAborting puts your branch back as it was before the {}.", name);
+        println!("   Any conflicts you already resolved are thrown away.");
+    }
+    if !matches!(Confirm::new(&format!("Abort the {}?", name)).with_default(false).prompt(), Ok(true)) {
+        println!("Cancelled. The {} is still in progress.", name);
+        return;
+    }
+
+    match progress::abort_operation(git) {
+        Ok(_) if pending.kind == InProgress::Bisect => {
+            println!("Bisect stopped. You are back on '{}'.", pending.branch.as_deref().unwrap_or("your branch"));
+        }
+        Ok(_) => println!("The {} was aborted. Your branch is back to how it was.", name),
+        Err(e) => {
+            eprintln!("\nError aborting the {}:", name);
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// Carry on with an interrupted merge, rebase, cherry-pick or revert
+pub fn continue_operation<G: GitBackend>(git: &G) {
+    let name = match progress::detect(git) {
+        Ok(Some(p)) => p.name(),
+        Ok(None) => {
+            println!("Nothing to continue: no merge, rebase, cherry-pick or revert is in progress.");
+            return;
+        }
+        Err(e) => {
+            eprintln!("Error checking the repository state: {}", e);
+            return;
+        }
+    };
+
+    match progress::continue_operation(git) {
+        Ok(_) => match progress::detect(git) {
+            // A rebase stops again at the next conflicting commit
+            Ok(Some(next)) => print_pending(&next, git),
+            _ if name == "rebase" => println!("\nThe rebase is complete."),
+            _ => {
+                println!("\nThe {} is complete.", name);
+                println!("   Run 'rfx push' to share it, or 'rfx undo' to take it back.");
             }
+        },
+        Err(RfxError::MergeConflict(_)) => {
+            println!("\nThe {} stopped again on new conflicts.", name);
+            println!("   Run 'rfx resolve', then 'rfx continue'.");
+        }
+        Err(RfxError::Validation(msg)) => println!("{}", msg),
+        Err(e) => {
+            eprintln!("\nError continuing the {}:", name);
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// Describe an interrupted operation and what to do next
+fn print_pending<G: GitBackend>(pending: &PendingOperation, git: &G) {
+    let branch = pending.branch.as_deref().unwrap_or("HEAD");
+    let target = pending.target.as_deref().unwrap_or("?");
+
+    match pending.kind {
+        InProgress::Merge => println!("[Merge in progress] Merging {} into '{}'.", target, branch),
+        InProgress::Rebase => {
+            let step = pending.step.map(|(n, total)| format!(" (commit {} of {})", n, total)).unwrap_or_default();
+            println!("[Rebase in progress] Replaying '{}' onto {}{}.", branch, target, step);
+        }
+        InProgress::CherryPick => println!("[Cherry-pick in progress] Copying {} onto '{}'.", target, branch),
+        InProgress::Revert => println!("[Revert in progress] Reverting {} on '{}'.", target, branch),
+        InProgress::Bisect => {
+            println!("[Bisect in progress] Searching for the commit that introduced a problem.");
+            println!("Next: Test this commit, then run 'git bisect good' or 'git bisect bad'.");
+            println!("      Run 'rfx abort' to stop and return to '{}'.", branch);
+            return;
         }
+    }
+
+    let conflicts = conflict::conflicted_files(git).map(|f| f.len()).unwrap_or(0);
+    if conflicts > 0 {
+        println!("Next: Run 'rfx resolve' to settle {} conflicted file(s), then 'rfx continue'.", conflicts);
+    } else {
+        println!("Next: Run 'rfx continue' to finish the {}.", pending.name());
+    }
+    println!("      Or run 'rfx abort' to go back to how things were before.");
+}
+
+pub fn undo<G: GitBackend>(git: &G) {
+    let op = match journal::next_undo(git) {
+        Ok(Some(op)) => op,
+        // Nothing recorded by rfx yet: fall back to undoing the last commit
+        Ok(None) => return undo_commit(git),
         Err(e) => {
-            if e.contains("rejected") || e.contains("fetch first") {
-                eprintln!("\n[Push Rejected]");
-                eprintln!("The remote repository has changes that you do not have.");
-                eprintln!("(This usually means someone else pushed code recently).");
-                eprintln!("\nAction: Run 'rfx pull' first to update your branch.");
-            } 
-            else if e.contains("Could not read from remote") {
-                eprintln!("\n[Connection Error]");
-                eprintln!("Could not connect to the remote server.");
+            eprintln!("Error reading rfx history: {}", e);
+            return;
+        }
+    };
+
+    // History moved on outside of rfx (commits, merges made with plain git):
+    // the last commit is the thing to undo, not the stale journal entry
+    if !journal::is_current(git, &op) {
+        return undo_commit(git);
+    }
+
+    // Commits need the extra safety checks (root, merge, already pushed)
+    if op.kind == OperationKind::Commit {
+        let is_head = op.refs.iter().any(|r| r.after == current_commit(git));
+        let plan = plan_undo_last_commit(git).map(|l| l.plan);
+        if is_head && !matches!(plan, Ok(UndoPlan::SoftReset)) {
+            return undo_commit(git);
+        }
+    }
+
+    // Pulled commits that were pushed again cannot be taken back quietly
+    if op.kind == OperationKind::Pull {
+        let pushed: Vec<String> = op
+            .refs
+            .iter()
+            .filter_map(|r| r.after.as_deref())
+            .flat_map(|sha| pushed_to(git, sha))
+            .filter(|remote| op.description != format!("from {}", remote))
+            .collect();
+        if !pushed.is_empty() {
+            eprintln!("\n[Undo Blocked]");
+            eprintln!("The result of this pull has already been pushed to: {}", pushed.join(", "));
+            eprintln!("Undoing it now would rewrite shared history.");
+            return;
+        }
+    }
+
+    if let Err(e) = journal::ensure_not_protected(git, &op) {
+        eprintln!("\n[Undo Blocked]");
+        eprintln!("{}", e);
+        return;
+    }
+
+    println!("\n[Undo Last Operation]");
+    println!("Last operation: {} {} ({})", op.kind.label(), op.description, journal::age(op.timestamp));
+    explain_operation(git, &op, false);
+    println!();
+
+    let confirm = Confirm::new("Are you sure you want to undo this?")
+        .with_default(false)
+        .prompt();
+
+    match confirm {
+        Ok(true) => match journal::undo(git) {
+            Ok(op) => {
+                println!("\nSuccess! {} undone.", op.kind.label());
+                println!("Changed your mind? Run 'rfx redo' to bring it back.");
             }
-            else {
-                eprintln!("\nError pushing changes:");
+            Err(e) => {
+                eprintln!("\nError undoing {}:", op.kind.label().to_lowercase());
                 eprintln!("{}", e);
             }
-        }
+        },
+        _ => println!("Cancelled."),
     }
 }
 
-pub fn undo() {
-    let last_commit_msg = match git_last_commit("HEAD") {
-        Ok(s) => {
-            s.split('|').nth(1).unwrap_or("Unknown").to_string()
-        },
-        Err(_) => "Unknown".to_string(),
+/// Undo the last commit, picking a safe strategy for root, merge and pushed commits
+fn undo_commit<G: GitBackend>(git: &G) {
+    let last = match plan_undo_last_commit(git) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("\nError undoing commit:");
+            eprintln!("{}", e);
+            return;
+        }
     };
 
     println!("\n[Undo Last Commit]");
-    println!("This will unsave commit: \"{}\"", last_commit_msg);
-    println!("Your files will NOT be deleted. They will move back to 'Unsaved Changes'.");
+    println!("Last commit: \"{}\"", last.message);
+    println!();
+
+    let question = match &last.plan {
+        UndoPlan::SoftReset => {
+            println!("This will unsave this commit.");
+            println!("Your files will NOT be deleted. They will move back to 'Unsaved Changes'.");
+            "Are you sure you want to undo this commit?"
+        }
+        UndoPlan::RootCommit => {
+            println!("This is the very first commit of the repository.");
+            println!("rfx will remove it; your files will NOT be deleted and stay staged.");
+            "Are you sure you want to undo the first commit?"
+        }
+        UndoPlan::Merge => {
+            println!("This is a merge commit: it combined another branch into this one.");
+            println!("Undoing it returns your branch to how it was before the merge.");
+            println!("The merged changes disappear from your files, but they are still safe on the other branch.");
+            "Are you sure you want to undo this merge?"
+        }
+        UndoPlan::Revert { merge, remotes } => {
+            println!("This commit has already been pushed to: {}", remotes.join(", "));
+            println!("Removing it would rewrite history that others may already have.");
+            println!();
+            println!("Instead, rfx can add a NEW commit that reverses its changes (a \"revert\").");
+            if *merge {
+                println!("Because this is a merge, the revert undoes everything the merge brought in.");
+            }
+            println!("Nothing on the server is rewritten; push the revert when you are ready.");
+            "Create a reverting commit?"
+        }
+    };
     println!();
 
-    let confirm = Confirm::new("Are you sure you want to undo this commit?")
+    let confirm = Confirm::new(question)
         .with_default(false)
         .prompt();
 
     match confirm {
         Ok(true) => {
-            match undo_last_commit() {
-                Ok(_) => {
-                    println!("\nSuccess! Commit undone.");
-                    println!("Your changes are now waiting in the staging area.");
+            match undo_last_commit(git, &last) {
+                Ok(_) => match last.plan {
+                    UndoPlan::Revert { .. } => {
+                        println!("\nSuccess! A reverting commit was created.");
+                        println!("Run 'rfx push' to share it.");
+                    }
+                    UndoPlan::Merge => println!("\nSuccess! Merge undone."),
+                    _ => {
+                        println!("\nSuccess! Commit undone.");
+                        println!("Your changes are now waiting in the staging area.");
+                    }
                 },
                 Err(e) => {
                     eprintln!("\nError undoing commit:");
                     eprintln!("{}", e);
-                    if e.contains("ambiguous argument") || e.contains("unknown revision") {
-                         eprintln!("(Hint: You cannot undo if there are no commits yet).");
+                    match e {
+                        RfxError::DirtyWorktree(_) => {
+                            eprintln!("(Hint: Commit or shelve your uncommitted changes first).");
+                        }
+                        RfxError::MergeConflict(_) => {
+                            eprintln!("(Hint: The revert conflicts with later changes. Resolve the conflicts, then commit).");
+                        }
+                        _ => {}
                     }
                 }
             }
         },
         _ => println!("Cancelled."),
     }
-}
\ No newline at end of file
+}
+
+pub fn redo<G: GitBackend>(git: &G) {
+    let op = match journal::next_redo(git) {
+        Ok(Some(op)) => op,
+        Ok(None) => {
+            println!("Nothing to redo.");
+            return;
+        }
+        Err(e) => {
+            eprintln!("Error reading rfx history: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = journal::ensure_not_protected(git, &op) {
+        eprintln!("\n[Redo Blocked]");
+        eprintln!("{}", e);
+        return;
+    }
+
+    println!("\n[Redo]");
+    println!("Undone operation: {} {}", op.kind.label(), op.description);
+    explain_operation(git, &op, true);
+    println!();
+
+    let confirm = Confirm::new("Do you want to redo this?")
+        .with_default(true)
+        .prompt();
+
+    match confirm {
+        Ok(true) => match journal::redo(git) {
+            Ok(op) => println!("\nSuccess! {} restored.", op.kind.label()),
+            Err(e) => {
+                eprintln!("\nError redoing {}:", op.kind.label().to_lowercase());
+                eprintln!("{}", e);
+            }
+        },
+        _ => println!("Cancelled."),
+    }
+}
+
+/// Tell the user in plain words what undoing (or redoing) an operation does
+fn explain_operation<G: GitBackend>(git: &G, op: &Operation, forward: bool) {
+    match (op.kind, forward) {
+        (OperationKind::Commit, false) => {
+            println!("The commit will be removed, but your files will NOT be deleted.");
+            println!("Its changes move back to the staging area.");
+        }
+        (OperationKind::Commit, true) => println!("The commit will be restored from your staged changes."),
+        (OperationKind::Reset, false) => println!("The commit you undid will be restored."),
+        (OperationKind::Reset, true) => println!("The commit will be undone again; its changes stay staged."),
+        (OperationKind::Amend, false) => {
+            println!("The commit goes back to how it was before the amend.");
+            println!("Anything the amend added stays in the staging area.");
+        }
+        (OperationKind::Amend, true) => println!("The amended commit will be restored."),
+        (OperationKind::UndoMerge, false) => println!("The merge you undid will be restored."),
+        (OperationKind::UndoMerge, true) => println!("The merge will be undone again."),
+        (OperationKind::Revert, false) => println!("The reverting commit will be removed."),
+        (OperationKind::Revert, true) => println!("The reverting commit will be restored."),
+        (OperationKind::Branch, false) => {
+            println!("You will be switched back to '{}' and branch '{}' will be removed.", op.head_before, op.branch_name());
+            println!("Its commits are not lost: 'rfx redo' brings the branch back.");
+        }
+        (OperationKind::Branch, true) => println!("Branch '{}' will be re-created and checked out.", op.branch_name()),
+        (OperationKind::DeleteBranch, false) => {
+            for r in &op.refs {
+                println!("Branch '{}' will be restored at {}.", journal::short_ref(&r.name), short_sha(r.before.as_deref()));
+            }
+        }
+        (OperationKind::DeleteBranch, true) => println!("Branches {} will be deleted again.", op.description),
+        (OperationKind::Switch, false) => {
+            println!("You will be switched back to '{}'.", op.head_before);
+            if !op.refs.is_empty() {
+                println!("The local branch '{}' created for it will be removed.", op.head_after);
+            }
+        }
+        (OperationKind::Switch, true) => println!("You will be switched to '{}' again.", op.head_after),
+        (OperationKind::Pull, false) => {
+            println!("Your branch will move back to where it was before the pull.");
+            println!("Uncommitted changes are kept (rfx stops if they would be overwritten).");
+        }
+        (OperationKind::Pull, true) => println!("The pulled commits will be applied again."),
+        (OperationKind::Push, _) => {
+            for (remote, branch) in op.remote_branches(&remote_names(git).unwrap_or_default()) {
+                println!("The branch '{}' on '{}' will be reset to its {} state.", branch, remote, if forward { "pushed" } else { "previous" });
+            }
+            println!("\n[Warning] This rewrites history on the server.");
+            println!("Anyone who already pulled those commits will have to fix their copy.");
+        }
+    }
+}
+
+pub fn history<G: GitBackend>(git: &G, json: bool) {
+    let ops = match journal::load(git) {
+        Ok(ops) => ops,
+        Err(e) if json => return print_error_json(&e),
+        Err(e) => {
+            eprintln!("Error reading rfx history: {}", e);
+            return;
+        }
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&ops) {
+            Ok(j) => println!("{}", j),
+            Err(e) => eprintln!("Failed to serialize history: {}", e),
+        }
+        return;
+    }
+
+    if ops.is_empty() {
+        println!("No operations recorded yet. rfx records commits, branches, pulls and pushes.");
+        return;
+    }
+
+    let current_id = ops.iter().rev().find(|op| !op.undone).map(|op| op.id);
+
+    let config = config::load(git).unwrap_or_else(|_| Config::defaults());
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(Row::new(vec![
+        Cell::new("#"),
+        Cell::new("When"),
+        Cell::new("Operation"),
+        Cell::new("Details"),
+        Cell::new("State"),
+    ]));
+
+    for op in &ops {
+        let state = if op.undone {
+            "undone"
+        } else if Some(op.id) == current_id {
+            "<- you are here"
+        } else {
+            ""
+        };
+        let details = shorten(&op.description, column_width(&config, "output.message_width"));
+
+        table.add_row(Row::new(vec![
+            Cell::new(&op.id.to_string()),
+            Cell::new(&journal::age(op.timestamp)),
+            Cell::new(op.kind.label()),
+            Cell::new(&details),
+            Cell::new(state),
+        ]));
+    }
+    table.printstd();
+    println!();
+
+    // Offer to jump to any point in the list
+    let stay = "Stay here".to_string();
+    let mut options = vec![stay.clone()];
+    options.extend(ops.iter().rev().map(|op| format!("#{} {} {}", op.id, op.kind.label(), op.description)));
+
+    let selection = Select::new("Jump to the state after:", options)
+        .with_page_size(10)
+        .prompt();
+
+    let target = match selection {
+        Ok(s) if s != stay => s
+            .trim_start_matches('#')
+            .split_whitespace()
+            .next()
+            .and_then(|id| id.parse::<u64>().ok()),
+        _ => None,
+    };
+    let Some(target) = target else { return };
+
+    let to_undo = ops.iter().filter(|op| !op.undone && op.id > target).count();
+    let to_redo = ops.iter().filter(|op| op.undone && op.id <= target).count();
+    if to_undo == 0 && to_redo == 0 {
+        println!("You are already there.");
+        return;
+    }
+
+    if to_undo > 0 {
+        println!("This will undo {} operation(s).", to_undo);
+    } else {
+        println!("This will redo {} operation(s).", to_redo);
+    }
+    let steps: Vec<&Operation> = ops
+        .iter()
+        .filter(|op| (!op.undone && op.id > target) || (op.undone && op.id <= target))
+        .collect();
+    if let Some(e) = steps.iter().find_map(|op| journal::ensure_not_protected(git, op).err()) {
+        eprintln!("\n[Jump Blocked]");
+        eprintln!("{}", e);
+        return;
+    }
+    if steps.iter().any(|op| op.kind == OperationKind::Push) {
+        println!("[Warning] This includes a push: history on the server will be rewritten.");
+    }
+
+    match Confirm::new("Continue?").with_default(false).prompt() {
+        Ok(true) => match journal::jump_to(git, target) {
+            Ok(changed) => println!("\nSuccess! {} operation(s) applied. You are now at #{}.", changed.len(), target),
+            Err(e) => {
+                eprintln!("\nStopped before reaching #{}:", target);
+                eprintln!("{}", e);
+            }
+        },
+        _ => println!("Cancelled."),
+    }
+}
+
+/// Print one setting's value (nothing when it is not set)
+pub fn config_get<G: GitBackend>(git: &G, key: &str) {
+    if config::setting(key).is_none() {
+        eprintln!("Unknown setting '{}'. Run 'rfx config list' to see the settings.", key);
+        return;
+    }
+
+    match config::load(git) {
+        Ok(config) => match config.get(key) {
+            Some((value, _)) => println!("{}", value),
+            None => eprintln!("'{}' is not set.", key),
+        },
+        Err(e) => eprintln!("Error reading the config: {}", e),
+    }
+}
+
+/// Change a setting in `.rfx.toml`, or in the user config with `global`
+pub fn config_set<G: GitBackend>(git: &G, key: &str, value: &str, global: bool) {
+    match config::set_value(git, key, value, global) {
+        Ok(path) => {
+            println!("Set {} = {} in {}", key, value.trim(), path.display());
+            if !global {
+                println!("   Commit {} to share the setting with your team.", config::REPO_FILE);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error changing the config:");
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// List every setting with its value and where it comes from
+pub fn config_list<G: GitBackend>(git: &G, json: bool) {
+    let config = match config::load(git) {
+        Ok(c) => c,
+        Err(e) if json => return print_error_json(&e),
+        Err(e) => {
+            eprintln!("Error reading the config: {}", e);
+            return;
+        }
+    };
+    let entries = config.entries();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+        return;
+    }
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(Row::new(vec![
+        Cell::new("Setting"),
+        Cell::new("Value"),
+        Cell::new("From"),
+        Cell::new("Description"),
+    ]));
+
+    for entry in &entries {
+        let value = entry.value.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+        table.add_row(Row::new(vec![
+            Cell::new(&entry.key),
+            Cell::new(&value),
+            Cell::new(entry.source.label()),
+            Cell::new(&entry.about),
+        ]));
+    }
+
+    table.printstd();
+    println!("\nRepository settings go in {}, personal ones in {}.",
+        config::REPO_FILE,
+        config::user_config_path().map(|p| p.display().to_string()).unwrap_or_else(|| "~/.config/rfx/config.toml".to_string()));
+    println!("Change them with 'rfx config set <setting> <value>' (add --global for your own defaults).");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_match_takes_letters_in_order() {
+        assert!(fuzzy_match("flog", "  feature/login | 2 days ago"));
+        assert!(fuzzy_match("FEAT log", "feature/login"));
+        assert!(fuzzy_match("", "anything"));
+        assert!(!fuzzy_match("golf", "feature/login"));
+        assert!(!fuzzy_match("mainx", "main"));
+    }
+}